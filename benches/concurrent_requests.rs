@@ -0,0 +1,280 @@
+//! Throughput of the watch history endpoint while image requests run at the same time.
+//!
+//! Starts the server on a temporary data directory, seeds it with watch history and measures
+//! requests per second, first with watch history requests alone and then with thumbnail requests
+//! running alongside them. The thumbnails are missing on disk, every thumbnail request looks up
+//! the source url and tries to download it again. Run with
+//! `cargo bench --bench concurrent_requests`.
+
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+const CHANNELS: usize = 50;
+const RECORDS: usize = 5000;
+const BATCH_SIZE: usize = 250;
+const HISTORY_CLIENTS: usize = 16;
+const IMAGE_CLIENTS: usize = 16;
+const PHASE_DURATION: Duration = Duration::from_secs(5);
+
+/// Kills the server and removes its data directory when the benchmark ends, also on panics
+struct Server {
+    process: Child,
+    data_dir: PathBuf,
+    url: String,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+        let _ = std::fs::remove_dir_all(&self.data_dir);
+    }
+}
+
+fn start_server() -> Server {
+    let data_dir = std::env::temp_dir().join(format!("chianti-bench-{}", std::process::id()));
+    std::fs::create_dir_all(&data_dir).expect("Failed to create data directory");
+
+    // An empty config file so the config of the user does not change the results
+    let config_file = data_dir.join("config.toml");
+    std::fs::write(&config_file, "").expect("Failed to write config file");
+
+    let port = TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("Failed to find a free port")
+        .port();
+
+    let process = Command::new(env!("CARGO_BIN_EXE_chianti"))
+        .arg("--config")
+        .arg(&config_file)
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .args(["serve", "--listen-address", "127.0.0.1", "--port"])
+        .arg(port.to_string())
+        .env("RUST_LOG", "error")
+        .stdout(Stdio::null())
+        .spawn()
+        .expect("Failed to start the server");
+
+    Server {
+        process,
+        data_dir,
+        url: format!("http://127.0.0.1:{}", port),
+    }
+}
+
+async fn wait_until_ready(client: &reqwest::Client, server: &Server) {
+    for _ in 0..100 {
+        if client
+            .get(format!("{}/api/ping", server.url))
+            .send()
+            .await
+            .is_ok()
+        {
+            return;
+        }
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    panic!("The server did not start");
+}
+
+fn video_id(index: usize) -> String {
+    format!("v{:010}", index)
+}
+
+/// Watch history records of distinct videos, their images point at a closed port so downloads
+/// fail right away instead of depending on the network
+async fn seed(client: &reqwest::Client, server: &Server) {
+    for batch in (0..RECORDS).collect::<Vec<usize>>().chunks(BATCH_SIZE) {
+        let records = batch
+            .iter()
+            .map(|&index| {
+                let start = 1_700_000_000 + index as i64 * 600;
+
+                serde_json::json!({
+                    "watch_duration_seconds": 300,
+                    "session_start_date": start,
+                    "session_end_date": start + 400,
+                    "channel": {
+                        "id": format!("UC{:022}", index % CHANNELS),
+                        "name": format!("Channel {}", index % CHANNELS),
+                        "avater_url": "http://127.0.0.1:1/avatar.png",
+                        "url": "https://www.youtube.com/@channel",
+                        "is_subscribed": index % 2 == 0,
+                        "subscribers_count": 1000
+                    },
+                    "video": {
+                        "id": video_id(index),
+                        "title": format!("Video {}", index),
+                        "description": "",
+                        "thumbnail_url": "http://127.0.0.1:1/thumbnail.png",
+                        "tags": ["music", format!("tag {}", index % 20)],
+                        "likes_count": 10,
+                        "view_count": 100,
+                        "comments_count": 1,
+                        "duration": 600,
+                        "published_at": 1_600_000_000
+                    }
+                })
+            })
+            .collect::<Vec<serde_json::Value>>();
+
+        let res = client
+            .post(format!("{}/api/watch_history", server.url))
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_vec(&records).unwrap())
+            .send()
+            .await
+            .expect("Failed to seed watch history");
+
+        assert!(
+            res.status().is_success(),
+            "Seeding failed: {}",
+            res.status()
+        );
+    }
+}
+
+#[derive(Default)]
+struct Counter {
+    requests: AtomicU64,
+    /// Requests that failed or got a server error
+    errors: AtomicU64,
+    nanos: AtomicU64,
+}
+
+impl Counter {
+    fn report(&self, name: &str) {
+        let requests = self.requests.load(Ordering::Relaxed);
+        let average = Duration::from_nanos(self.nanos.load(Ordering::Relaxed) / requests.max(1));
+
+        println!(
+            "  {:<14} {:>8.1} req/s  {:>10.2?} average latency  {} errors",
+            name,
+            requests as f64 / PHASE_DURATION.as_secs_f64(),
+            average,
+            self.errors.load(Ordering::Relaxed)
+        );
+    }
+}
+
+/// Sends requests to the urls returned by `url` from `clients` tasks until the phase ends
+fn spawn_clients(
+    tasks: &mut tokio::task::JoinSet<()>,
+    client: &reqwest::Client,
+    clients: usize,
+    counter: &Arc<Counter>,
+    done: &Arc<AtomicBool>,
+    url: impl Fn(usize) -> String + Clone + Send + 'static,
+) {
+    for worker in 0..clients {
+        let client = client.clone();
+        let counter = counter.clone();
+        let done = done.clone();
+        let url = url.clone();
+
+        tasks.spawn(async move {
+            let mut i = worker;
+
+            while !done.load(Ordering::Relaxed) {
+                let started = Instant::now();
+                let res = client.get(url(i)).send().await;
+
+                // Missing thumbnails are expected to be not found
+                match res {
+                    Ok(res) if !res.status().is_server_error() => {
+                        let _ = res.bytes().await;
+                    }
+                    _ => {
+                        counter.errors.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+
+                counter.requests.fetch_add(1, Ordering::Relaxed);
+                counter
+                    .nanos
+                    .fetch_add(started.elapsed().as_nanos() as u64, Ordering::Relaxed);
+
+                i += clients;
+            }
+        });
+    }
+}
+
+async fn run_phase(client: &reqwest::Client, server: &Server, with_images: bool) {
+    let history = Arc::new(Counter::default());
+    let images = Arc::new(Counter::default());
+    let done = Arc::new(AtomicBool::new(false));
+    let mut tasks = tokio::task::JoinSet::new();
+
+    let base_url = server.url.clone();
+    spawn_clients(
+        &mut tasks,
+        client,
+        HISTORY_CLIENTS,
+        &history,
+        &done,
+        move |i| {
+            format!(
+                "{}/api/watch_history?limit=50&offset={}",
+                base_url,
+                i * 50 % RECORDS
+            )
+        },
+    );
+
+    if with_images {
+        let base_url = server.url.clone();
+        spawn_clients(
+            &mut tasks,
+            client,
+            IMAGE_CLIENTS,
+            &images,
+            &done,
+            move |i| {
+                format!(
+                    "{}/api/images/thumbnails/{}",
+                    base_url,
+                    video_id(i % RECORDS)
+                )
+            },
+        );
+    }
+
+    tokio::time::sleep(PHASE_DURATION).await;
+    done.store(true, Ordering::Relaxed);
+
+    while tasks.join_next().await.is_some() {}
+
+    history.report("watch history");
+
+    if with_images {
+        images.report("thumbnails");
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let server = start_server();
+    let client = reqwest::Client::new();
+
+    wait_until_ready(&client, &server).await;
+    seed(&client, &server).await;
+
+    println!(
+        "{} watch history records, {} history clients, {} image clients, {:?} per phase",
+        RECORDS, HISTORY_CLIENTS, IMAGE_CLIENTS, PHASE_DURATION
+    );
+
+    println!("Watch history only");
+    run_phase(&client, &server, false).await;
+
+    println!("Watch history and thumbnails");
+    run_phase(&client, &server, true).await;
+}
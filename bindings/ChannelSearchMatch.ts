@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ChannelResponse } from "./ChannelResponse";
+
+export type ChannelSearchMatch = { 
+/**
+ * Higher is a better match
+ */
+score: number, 
+/**
+ * Channel name with `<mark>` around each match, the rest of the name is HTML escaped
+ */
+highlight: string, channel: ChannelResponse, };
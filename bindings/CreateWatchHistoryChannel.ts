@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type CreateWatchHistoryChannel = { id: string, name: string, avater_url: string, url: string, is_subscribed: boolean, subscribers_count: number, };
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type FieldErrorCode = "negative" | "invalid_format" | "out_of_order" | "exceeds_session" | "in_future";
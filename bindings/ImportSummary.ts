@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ImportOutcomeCounts } from "./ImportOutcomeCounts";
+
+export type ImportSummary = { channels: ImportOutcomeCounts, videos: ImportOutcomeCounts, watch_history: ImportOutcomeCounts, skipped: number, 
+/**
+ * Nothing was saved, the counts show what the import would change
+ */
+dry_run: boolean, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type PaginatedResponse<T> = { data: Array<T>, offset: bigint | null, limit: bigint | null, total: bigint, 
+/**
+ * Pass as `cursor` to fetch the next page, only lists with cursor pagination set it and it
+ * is `null` on the last page
+ */
+next_cursor: string | null, };
@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Matched text with `<mark>` around each match, the rest of the text is HTML escaped
+ */
+export type VideoHighlight = { title: string, 
+/**
+ * Part of the description around the matches
+ */
+description: string, 
+/**
+ * Tag names separated by `, `
+ */
+tags: string, };
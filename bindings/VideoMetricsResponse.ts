@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { VideoMetricSnapshot } from "./VideoMetricSnapshot";
+
+export type VideoMetricsResponse = { video_id: string, snapshots: Array<VideoMetricSnapshot>, };
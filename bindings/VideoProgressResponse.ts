@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type VideoProgressResponse = { video_id: string, duration_seconds: number, watched_seconds: number, completion_percentage: number, last_position_seconds: number | null, last_watched_at: number | null, };
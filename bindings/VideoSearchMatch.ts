@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { VideoHighlight } from "./VideoHighlight";
+import type { VideoResponse } from "./VideoResponse";
+
+export type VideoSearchMatch = { 
+/**
+ * Higher is a better match
+ */
+score: number, highlight: VideoHighlight, video: VideoResponse, };
@@ -104,10 +104,16 @@ async function main() {
         return;
     }
 
+    // The other run of a doubled `page-rendered` already tracks this video
+    if (payload) return;
+
     payload = {
         watch_duration_seconds: 0,
         session_start_date: Math.round(Number(Date.now() / 1000)),
         session_end_date: Math.round(Number(Date.now() / 1000)),
+        // New for every navigation, a rewatch of the same video is a new session
+        session_key: crypto.randomUUID(),
+        segments: [],
 
         channel: channelInfo.data!,
         video: videoInfo.data!,
@@ -133,6 +139,32 @@ async function main() {
         if (!videoElement.paused) {
             if (payload) {
                 payload.watch_duration_seconds += 1;
+
+                // Extend the current segment while playback continues, a seek starts a new one.
+                // A tick covers the second it lands in, ends are exclusive
+                const position = Math.floor(videoElement.currentTime);
+                const segments = payload.segments || [];
+                const lastSegment = segments[segments.length - 1];
+                // Faster playback moves further between ticks
+                const tolerance = Math.ceil(videoElement.playbackRate) + 1;
+
+                if (
+                    lastSegment &&
+                    position >= lastSegment.end_seconds - 1 &&
+                    position - lastSegment.end_seconds <= tolerance
+                ) {
+                    lastSegment.end_seconds = Math.max(
+                        lastSegment.end_seconds,
+                        position + 1,
+                    );
+                } else {
+                    segments.push({
+                        start_seconds: position,
+                        end_seconds: position + 1,
+                    });
+                }
+
+                payload.segments = segments;
             }
         }
     }, 1000);
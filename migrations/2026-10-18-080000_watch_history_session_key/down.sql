@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS watch_history_session_key;
+
+ALTER TABLE watch_history DROP COLUMN session_key;
@@ -0,0 +1,5 @@
+-- Client generated key that identifies one watch session, resending the same
+-- session updates the existing row instead of inserting a new one
+ALTER TABLE watch_history ADD COLUMN session_key TEXT;
+
+CREATE UNIQUE INDEX watch_history_session_key ON watch_history(session_key);
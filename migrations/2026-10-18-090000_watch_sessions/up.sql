@@ -0,0 +1,14 @@
+-- Watch sessions that are still open, they are moved to watch_history when the
+-- client ends them or when they stop sending heartbeats
+CREATE TABLE watch_sessions (
+    id                      TEXT    NOT NULL PRIMARY KEY,
+    video_id                TEXT    NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+    channel_id              TEXT    NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+    watch_duration_seconds  BIGINT  NOT NULL,
+    session_start_date      BIGINT  NOT NULL,
+    last_heartbeat_at       BIGINT  NOT NULL,
+
+    added_at                BIGINT  NOT NULL
+);
+
+CREATE INDEX watch_sessions_last_heartbeat_at ON watch_sessions(last_heartbeat_at);
@@ -0,0 +1,13 @@
+-- Playback ranges that were actually played during a watch session, offsets
+-- are seconds from the start of the video
+CREATE TABLE watch_segments (
+    id                      TEXT    NOT NULL PRIMARY KEY,
+    watch_history_id        TEXT    NOT NULL REFERENCES watch_history(id) ON DELETE CASCADE,
+    segment_index           BIGINT  NOT NULL,
+    start_seconds           BIGINT  NOT NULL,
+    end_seconds             BIGINT  NOT NULL,
+
+    added_at                BIGINT  NOT NULL
+);
+
+CREATE INDEX watch_segments_watch_history_id ON watch_segments(watch_history_id);
@@ -0,0 +1,17 @@
+-- Video metrics as they were every time the video was ingested
+CREATE TABLE video_metric_snapshots (
+    id                      TEXT    NOT NULL PRIMARY KEY,
+    video_id                TEXT    NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+    view_count              BIGINT  NOT NULL,
+    likes_count             BIGINT  NOT NULL,
+    comments_count          BIGINT  NOT NULL,
+
+    added_at                BIGINT  NOT NULL
+);
+
+CREATE INDEX video_metric_snapshots_video_id ON video_metric_snapshots(video_id, added_at);
+
+-- Keep the metrics that were stored before snapshots existed
+INSERT INTO video_metric_snapshots (id, video_id, view_count, likes_count, comments_count, added_at)
+SELECT lower(hex(randomblob(16))), id, view_count, likes_count, comments_count, added_at
+FROM videos;
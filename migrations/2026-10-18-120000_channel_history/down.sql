@@ -0,0 +1,2 @@
+DROP TABLE channel_snapshots;
+DROP TABLE channel_events;
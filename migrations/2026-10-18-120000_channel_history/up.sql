@@ -0,0 +1,38 @@
+-- Channel values as they were every time one of them changed
+CREATE TABLE channel_snapshots (
+    id                      TEXT    NOT NULL PRIMARY KEY,
+    channel_id              TEXT    NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+    name                    TEXT    NOT NULL,
+    is_subscribed           BOOLEAN NOT NULL,
+    subscribers_count       BIGINT  NOT NULL,
+
+    added_at                BIGINT  NOT NULL
+);
+
+CREATE INDEX channel_snapshots_channel_id ON channel_snapshots(channel_id, added_at);
+
+-- Name changes and subscribe/unsubscribe events
+CREATE TABLE channel_events (
+    id                      TEXT    NOT NULL PRIMARY KEY,
+    channel_id              TEXT    NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+    kind                    TEXT    NOT NULL,
+    old_value               TEXT,
+    new_value               TEXT,
+
+    added_at                BIGINT  NOT NULL
+);
+
+CREATE INDEX channel_events_channel_id ON channel_events(channel_id, added_at);
+CREATE INDEX channel_events_kind ON channel_events(kind, added_at);
+
+-- Keep the values that were stored before channel history existed, the
+-- subscription date of existing channels is unknown so the date the channel
+-- was first seen is used
+INSERT INTO channel_snapshots (id, channel_id, name, is_subscribed, subscribers_count, added_at)
+SELECT lower(hex(randomblob(16))), id, name, is_subscribed, subscribers_count, added_at
+FROM channels;
+
+INSERT INTO channel_events (id, channel_id, kind, old_value, new_value, added_at)
+SELECT lower(hex(randomblob(16))), id, 'subscribed', NULL, NULL, added_at
+FROM channels
+WHERE is_subscribed;
@@ -0,0 +1,16 @@
+-- Every title and thumbnail a video had, thumbnail_file is relative to the
+-- thumbnail revisions directory and is NULL until a thumbnail was downloaded
+CREATE TABLE video_revisions (
+    id                      TEXT    NOT NULL PRIMARY KEY,
+    video_id                TEXT    NOT NULL REFERENCES videos(id) ON DELETE CASCADE,
+    title                   TEXT    NOT NULL,
+    thumbnail_file          TEXT,
+
+    added_at                BIGINT  NOT NULL
+);
+
+CREATE INDEX video_revisions_video_id ON video_revisions(video_id, added_at);
+
+INSERT INTO video_revisions (id, video_id, title, thumbnail_file, added_at)
+SELECT lower(hex(randomblob(16))), id, title, NULL, added_at
+FROM videos;
@@ -0,0 +1,17 @@
+-- Background jobs, image downloads run here instead of in the request
+CREATE TABLE jobs (
+    id                      TEXT    NOT NULL PRIMARY KEY,
+    kind                    TEXT    NOT NULL,
+    target_id               TEXT    NOT NULL,
+    url                     TEXT    NOT NULL,
+    status                  TEXT    NOT NULL,
+    attempts                BIGINT  NOT NULL,
+    last_error              TEXT,
+    run_at                  BIGINT  NOT NULL,
+    updated_at              BIGINT  NOT NULL,
+
+    added_at                BIGINT  NOT NULL
+);
+
+CREATE INDEX jobs_status_run_at ON jobs(status, run_at);
+CREATE INDEX jobs_kind_target_id ON jobs(kind, target_id);
@@ -0,0 +1,2 @@
+ALTER TABLE channels DROP COLUMN avatar_url;
+ALTER TABLE videos DROP COLUMN thumbnail_url;
@@ -0,0 +1,25 @@
+-- Source urls of channel avatars and video thumbnails so missing images can be
+-- downloaded again later
+ALTER TABLE channels ADD COLUMN avatar_url TEXT;
+ALTER TABLE videos ADD COLUMN thumbnail_url TEXT;
+
+-- Recover the urls of queued image downloads
+UPDATE channels
+SET avatar_url = (
+    SELECT url FROM jobs
+    WHERE jobs.kind = 'channel_avatar' AND jobs.target_id = channels.id
+    ORDER BY jobs.updated_at DESC
+    LIMIT 1
+);
+
+-- Youtube serves thumbnails of every video from a predictable url
+UPDATE videos
+SET thumbnail_url = COALESCE(
+    (
+        SELECT url FROM jobs
+        WHERE jobs.kind = 'video_thumbnail' AND jobs.target_id = videos.id
+        ORDER BY jobs.updated_at DESC
+        LIMIT 1
+    ),
+    'https://i.ytimg.com/vi/' || id || '/hqdefault.jpg'
+);
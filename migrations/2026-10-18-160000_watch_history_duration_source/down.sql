@@ -0,0 +1 @@
+ALTER TABLE watch_history DROP COLUMN duration_source;
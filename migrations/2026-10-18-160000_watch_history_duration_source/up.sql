@@ -0,0 +1,3 @@
+-- How watch_duration_seconds was obtained: 'tracked' by the browser extension, 'estimated' or
+-- 'unknown' for imported records that carry no duration
+ALTER TABLE watch_history ADD COLUMN duration_source TEXT NOT NULL DEFAULT 'tracked';
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS videos_added_at;
+
+DROP INDEX IF EXISTS watch_history_session_start_date;
@@ -0,0 +1,4 @@
+-- Default orders of the cursor paginated lists, the id breaks ties between equal sort keys
+CREATE INDEX watch_history_session_start_date ON watch_history(session_start_date, id);
+
+CREATE INDEX videos_added_at ON videos(added_at, id);
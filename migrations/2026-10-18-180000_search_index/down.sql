@@ -0,0 +1,15 @@
+DROP TRIGGER IF EXISTS tags_fts_delete;
+DROP TRIGGER IF EXISTS tags_fts_update;
+DROP TRIGGER IF EXISTS tags_fts_insert;
+DROP TRIGGER IF EXISTS channels_fts_delete;
+DROP TRIGGER IF EXISTS channels_fts_update;
+DROP TRIGGER IF EXISTS channels_fts_insert;
+DROP TRIGGER IF EXISTS video_tags_fts_delete;
+DROP TRIGGER IF EXISTS video_tags_fts_insert;
+DROP TRIGGER IF EXISTS videos_fts_delete;
+DROP TRIGGER IF EXISTS videos_fts_update;
+DROP TRIGGER IF EXISTS videos_fts_insert;
+
+DROP TABLE IF EXISTS tags_fts;
+DROP TABLE IF EXISTS channels_fts;
+DROP TABLE IF EXISTS videos_fts;
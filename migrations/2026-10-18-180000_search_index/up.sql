@@ -0,0 +1,106 @@
+-- Full-text search indexes of videos, channels and tags. Each row has the rowid of its source
+-- row so the triggers below find it without a scan, the id is stored to join search results
+-- back to their source rows.
+CREATE VIRTUAL TABLE videos_fts USING fts5(
+    id UNINDEXED,
+    title,
+    description,
+    tags,
+    tokenize = 'unicode61 remove_diacritics 2',
+    prefix = '2 3'
+);
+
+CREATE VIRTUAL TABLE channels_fts USING fts5(
+    id UNINDEXED,
+    name,
+    tokenize = 'unicode61 remove_diacritics 2',
+    prefix = '2 3'
+);
+
+CREATE VIRTUAL TABLE tags_fts USING fts5(
+    id UNINDEXED,
+    name,
+    tokenize = 'unicode61 remove_diacritics 2',
+    prefix = '2 3'
+);
+
+-- Matches in titles rank above matches in tags and descriptions
+INSERT INTO videos_fts(videos_fts, rank) VALUES ('rank', 'bm25(0.0, 10.0, 2.0, 5.0)');
+
+INSERT INTO videos_fts(rowid, id, title, description, tags)
+SELECT videos.rowid, videos.id, videos.title, videos.description, COALESCE((
+    SELECT group_concat(tags.name, ', ')
+    FROM video_tags INNER JOIN tags ON tags.id = video_tags.tag_id
+    WHERE video_tags.video_id = videos.id
+), '')
+FROM videos;
+
+INSERT INTO channels_fts(rowid, id, name) SELECT rowid, id, name FROM channels;
+
+INSERT INTO tags_fts(rowid, id, name) SELECT rowid, id, name FROM tags;
+
+CREATE TRIGGER videos_fts_insert AFTER INSERT ON videos BEGIN
+    INSERT INTO videos_fts(rowid, id, title, description, tags)
+    VALUES (new.rowid, new.id, new.title, new.description, '');
+END;
+
+CREATE TRIGGER videos_fts_update AFTER UPDATE OF title, description ON videos BEGIN
+    UPDATE videos_fts SET title = new.title, description = new.description
+    WHERE rowid = new.rowid;
+END;
+
+CREATE TRIGGER videos_fts_delete AFTER DELETE ON videos BEGIN
+    DELETE FROM videos_fts WHERE rowid = old.rowid;
+END;
+
+CREATE TRIGGER video_tags_fts_insert AFTER INSERT ON video_tags BEGIN
+    UPDATE videos_fts SET tags = COALESCE((
+        SELECT group_concat(tags.name, ', ')
+        FROM video_tags INNER JOIN tags ON tags.id = video_tags.tag_id
+        WHERE video_tags.video_id = new.video_id
+    ), '')
+    WHERE rowid = (SELECT rowid FROM videos WHERE id = new.video_id);
+END;
+
+CREATE TRIGGER video_tags_fts_delete AFTER DELETE ON video_tags BEGIN
+    UPDATE videos_fts SET tags = COALESCE((
+        SELECT group_concat(tags.name, ', ')
+        FROM video_tags INNER JOIN tags ON tags.id = video_tags.tag_id
+        WHERE video_tags.video_id = old.video_id
+    ), '')
+    WHERE rowid = (SELECT rowid FROM videos WHERE id = old.video_id);
+END;
+
+CREATE TRIGGER channels_fts_insert AFTER INSERT ON channels BEGIN
+    INSERT INTO channels_fts(rowid, id, name) VALUES (new.rowid, new.id, new.name);
+END;
+
+CREATE TRIGGER channels_fts_update AFTER UPDATE OF name ON channels BEGIN
+    UPDATE channels_fts SET name = new.name WHERE rowid = new.rowid;
+END;
+
+CREATE TRIGGER channels_fts_delete AFTER DELETE ON channels BEGIN
+    DELETE FROM channels_fts WHERE rowid = old.rowid;
+END;
+
+CREATE TRIGGER tags_fts_insert AFTER INSERT ON tags BEGIN
+    INSERT INTO tags_fts(rowid, id, name) VALUES (new.rowid, new.id, new.name);
+END;
+
+CREATE TRIGGER tags_fts_update AFTER UPDATE OF name ON tags BEGIN
+    UPDATE tags_fts SET name = new.name WHERE rowid = new.rowid;
+
+    UPDATE videos_fts SET tags = COALESCE((
+        SELECT group_concat(tags.name, ', ')
+        FROM video_tags INNER JOIN tags ON tags.id = video_tags.tag_id
+        WHERE video_tags.video_id = videos_fts.id
+    ), '')
+    WHERE rowid IN (
+        SELECT videos.rowid FROM video_tags INNER JOIN videos ON videos.id = video_tags.video_id
+        WHERE video_tags.tag_id = new.id
+    );
+END;
+
+CREATE TRIGGER tags_fts_delete AFTER DELETE ON tags BEGIN
+    DELETE FROM tags_fts WHERE rowid = old.rowid;
+END;
@@ -0,0 +1,7 @@
+DROP INDEX IF EXISTS video_tags_tag_id;
+
+DROP INDEX IF EXISTS videos_channel_id;
+
+DROP INDEX IF EXISTS watch_history_channel_id;
+
+DROP INDEX IF EXISTS watch_history_video_id;
@@ -0,0 +1,8 @@
+-- Derived sort keys count or sum the rows of each channel, video and tag
+CREATE INDEX watch_history_video_id ON watch_history(video_id, watch_duration_seconds);
+
+CREATE INDEX watch_history_channel_id ON watch_history(channel_id, watch_duration_seconds);
+
+CREATE INDEX videos_channel_id ON videos(channel_id);
+
+CREATE INDEX video_tags_tag_id ON video_tags(tag_id);
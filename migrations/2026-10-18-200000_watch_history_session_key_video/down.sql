@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS watch_history_session_key;
+
+CREATE UNIQUE INDEX watch_history_session_key ON watch_history(session_key);
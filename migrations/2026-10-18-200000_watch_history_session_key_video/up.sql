@@ -0,0 +1,5 @@
+-- A session key identifies one watch session of one video, the duplicate check of a resent
+-- session looks it up together with the video
+DROP INDEX IF EXISTS watch_history_session_key;
+
+CREATE UNIQUE INDEX watch_history_session_key ON watch_history(session_key, video_id);
@@ -2,8 +2,6 @@
 
 set -e
 
-# cargo install --git https://github.com/Aleph-Alpha/ts-rs --branch feat/cli ts-rs-cli
-
 if [[ ! -d "./scripts/" ]]; then
     echo "ERROR: Script running in the wrong directory '(${PWD})'"
     exit 1
@@ -14,4 +12,16 @@ EXPORT_TARGET="./ts-bindings"
 [[ -d "${EXPORT_TARGET}" ]] && echo "Removing old ${EXPORT_TARGET}" && rm -r "${EXPORT_TARGET}"
 
 echo "Exporting bindings to ${EXPORT_TARGET}"
-ts-rs export --index --output-directory "${EXPORT_TARGET}"
+TS_RS_EXPORT_DIR="${EXPORT_TARGET}" cargo test export_bindings
+
+echo "Writing ${EXPORT_TARGET}/index.ts"
+{
+    echo "// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually."
+    echo
+
+    for file in "${EXPORT_TARGET}"/*.ts; do
+        echo "export * from \"./$(basename "${file}")\";"
+    done
+} > "${EXPORT_TARGET}/index.ts.tmp"
+
+mv "${EXPORT_TARGET}/index.ts.tmp" "${EXPORT_TARGET}/index.ts"
@@ -10,33 +10,206 @@ pub use axum::{
     response::{IntoResponse, Response},
 };
 pub use axum_extra::extract::Query;
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
 use diesel::prelude::*;
+use diesel::sqlite::Sqlite;
+use serde::de::{DeserializeOwned, IntoDeserializer};
 pub use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 pub use ts_rs::TS;
 pub use utils::internal_error;
 
-use diesel::sql_types::{BigInt, Text};
+use diesel::sql_types::{BigInt, Bool, Text};
 
 define_sql_function! {
     #[sql_name = "strftime"]
     fn strftime(fmt: Text, ts: BigInt, modifier: Text) -> Text
 }
 
+/// `rowid` of `table`, it grows with every insert so it keeps rows added in the same second in
+/// insertion order
+pub fn rowid(table: &str) -> diesel::expression::SqlLiteral<BigInt> {
+    diesel::dsl::sql::<BigInt>(&format!("{table}.rowid"))
+}
+
+/// Tag names of each video by video id, loaded with one query for all videos instead of one per
+/// video
+pub fn load_video_tags<'a>(
+    conn: &mut SqliteConnection,
+    video_ids: impl Iterator<Item = &'a String>,
+) -> QueryResult<HashMap<String, Vec<String>>> {
+    use schema::tags::dsl as tags_dsl;
+    use schema::video_tags::dsl as video_tags_dsl;
+
+    let mut video_ids = video_ids.collect::<Vec<&String>>();
+    video_ids.sort_unstable();
+    video_ids.dedup();
+
+    let rows = tags_dsl::tags
+        .inner_join(video_tags_dsl::video_tags)
+        .filter(video_tags_dsl::video_id.eq_any(video_ids))
+        .select((video_tags_dsl::video_id, tags_dsl::name))
+        .order(rowid("video_tags"))
+        .load::<(String, String)>(conn)?;
+
+    let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+
+    for (video_id, name) in rows {
+        tags.entry(video_id).or_default().push(name);
+    }
+
+    Ok(tags)
+}
+
 pub type ApiErr = (StatusCode, String);
 pub type ApiResult<T> = Result<T, ApiErr>;
 
 pub use crate::apply_sort;
 pub use crate::day_unix;
+pub use crate::filter::Condition;
+pub use crate::filter_tags;
+pub use crate::filter_watch_history;
+pub use crate::keyset_step;
 pub use crate::month_unix;
 pub use crate::year_unix;
 
-#[derive(Debug, Deserialize, utoipa::ToSchema)]
+#[derive(Debug, Clone, Copy, Deserialize, utoipa::ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum SortOrder {
     Asc,
     Desc,
 }
 
+/// Key a list can be sorted by, the `SortBy` enum of each list
+pub trait SortKey: DeserializeOwned + Copy + PartialEq {
+    /// Name of the key in `sort` and `sort_by`
+    fn name(&self) -> &'static str;
+}
+
+/// Keys a list is sorted by, most significant first.
+///
+/// Parsed from `sort` like `-watch_counter,title`, where `-` sorts a key in descending order, or
+/// from the single key of `sort_by` and `sort_order`. Rows that tie on every key are ordered by
+/// id in the order of the last key so pages never overlap or skip rows.
+#[derive(Debug)]
+pub struct Sort<K> {
+    pub keys: Vec<(K, SortOrder)>,
+}
+
+impl<K: SortKey> Sort<K> {
+    /// `default` sorts the list when neither `sort` nor `sort_by` is given, its order is also the
+    /// default of `sort_order`
+    pub fn new(
+        sort: Option<&str>,
+        sort_by: Option<K>,
+        sort_order: Option<SortOrder>,
+        default: (K, SortOrder),
+    ) -> ApiResult<Self> {
+        let Some(sort) = sort else {
+            return Ok(Self {
+                keys: vec![(
+                    sort_by.unwrap_or(default.0),
+                    sort_order.unwrap_or(default.1),
+                )],
+            });
+        };
+
+        if sort_by.is_some() || sort_order.is_some() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "sort can not be combined with sort_by or sort_order".to_string(),
+            ));
+        }
+
+        let mut keys: Vec<(K, SortOrder)> = Vec::new();
+
+        for part in sort.split(',').map(str::trim) {
+            let (name, order) = match part.strip_prefix('-') {
+                Some(name) => (name, SortOrder::Desc),
+                None => (part.strip_prefix('+').unwrap_or(part), SortOrder::Asc),
+            };
+
+            if name.is_empty() {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid sort `{}`: expected comma separated keys", sort),
+                ));
+            }
+
+            let key = K::deserialize(name.into_deserializer()).map_err(
+                |e: serde::de::value::Error| {
+                    (
+                        StatusCode::BAD_REQUEST,
+                        format!("Invalid sort key `{}`: {}", name, e),
+                    )
+                },
+            )?;
+
+            if keys.iter().any(|(listed, _)| *listed == key) {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!("Sort key `{}` is listed more than once", name),
+                ));
+            }
+
+            keys.push((key, order));
+        }
+
+        Ok(Self { keys })
+    }
+
+    /// Order of the id that breaks ties
+    pub fn id_order(&self) -> SortOrder {
+        self.keys.last().map_or(SortOrder::Asc, |&(_, order)| order)
+    }
+}
+
+/// The `sort` form of the keys, cursors are tied to it
+impl<K: SortKey> std::fmt::Display for Sort<K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let keys = self
+            .keys
+            .iter()
+            .map(|(key, order)| match order {
+                SortOrder::Asc => key.name().to_string(),
+                SortOrder::Desc => format!("-{}", key.name()),
+            })
+            .collect::<Vec<String>>();
+
+        write!(f, "{}", keys.join(","))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TagsMode {
+    /// Videos that have at least one of the tags
+    #[default]
+    Any,
+    /// Videos that have every tag
+    All,
+}
+
+/// Tag filters shared by `/api/videos`, `/api/channels` and `/api/watch_history`, see
+/// [`crate::filter_tags`]. Channels are listed when at least one of their videos matches, watch
+/// history records when their video matches.
+#[derive(Deserialize, Debug, Default, Clone, utoipa::IntoParams)]
+pub struct TagFilters {
+    /// List only videos that have specified tags
+    pub tags: Option<Vec<String>>,
+    /// Whether videos need `any` of `tags` or `all` of them, `any` by default
+    pub tags_mode: Option<TagsMode>,
+    /// Leave out videos that have any of specified tags
+    pub exclude_tags: Option<Vec<String>>,
+}
+
+impl TagFilters {
+    /// `tags_mode` alone filters nothing
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_none() && self.exclude_tags.is_none()
+    }
+}
+
 #[derive(utoipa::ToSchema, Serialize, Deserialize, TS)]
 #[ts(export)]
 pub struct PaginatedResponse<T> {
@@ -44,6 +217,9 @@ pub struct PaginatedResponse<T> {
     pub offset: Option<i64>,
     pub limit: Option<i64>,
     pub total: i64,
+    /// Pass as `cursor` to fetch the next page, only lists with cursor pagination set it and it
+    /// is `null` on the last page
+    pub next_cursor: Option<String>,
 }
 
 impl<T> PaginatedResponse<T> {
@@ -53,8 +229,142 @@ impl<T> PaginatedResponse<T> {
             offset,
             limit,
             total,
+            next_cursor: None,
         }
     }
+
+    pub fn with_next_cursor(mut self, next_cursor: Option<Cursor>) -> Self {
+        self.next_cursor = next_cursor.map(|cursor| cursor.encode());
+        self
+    }
+}
+
+/// Value of one sort key in a [`Cursor`]
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum CursorKey {
+    Integer(i64),
+    Text(String),
+}
+
+impl CursorKey {
+    /// Value of a key on an integer column
+    pub fn integer(self) -> ApiResult<i64> {
+        match self {
+            CursorKey::Integer(key) => Ok(key),
+            CursorKey::Text(_) => Err((StatusCode::BAD_REQUEST, "Invalid cursor".to_string())),
+        }
+    }
+
+    /// Value of a key on a text column
+    pub fn text(self) -> ApiResult<String> {
+        match self {
+            CursorKey::Text(key) => Ok(key),
+            CursorKey::Integer(_) => Err((StatusCode::BAD_REQUEST, "Invalid cursor".to_string())),
+        }
+    }
+}
+
+/// Position of the last row of a page for keyset pagination, the next page starts after the row
+/// with these sort key values and id. Clients only see it encoded as an opaque string.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Cursor {
+    /// Order the cursor was created for, a cursor is rejected by any other order
+    sort: String,
+    /// Values of the sort keys in the order of `sort`
+    keys: Vec<CursorKey>,
+    id: String,
+}
+
+impl Cursor {
+    pub fn new(sort: String, keys: Vec<CursorKey>, id: String) -> Self {
+        Self { sort, keys, id }
+    }
+
+    pub fn encode(&self) -> String {
+        // Serializing a struct of strings and integers can not fail
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    /// Decodes a cursor of a previous page, `sort` is the order of the current request
+    pub fn decode(value: &str, sort: &str) -> ApiResult<Self> {
+        let invalid = || (StatusCode::BAD_REQUEST, "Invalid cursor".to_string());
+
+        let bytes = URL_SAFE_NO_PAD.decode(value).map_err(|_| invalid())?;
+        let cursor = serde_json::from_slice::<Cursor>(&bytes).map_err(|_| invalid())?;
+
+        if cursor.sort != sort {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Cursor belongs to a different sort order".to_string(),
+            ));
+        }
+
+        Ok(cursor)
+    }
+}
+
+/// Condition that keeps the rows after `cursor` in `sort`.
+///
+/// `step` turns a sort key, its order and the cursor's value of it into the conditions that a row
+/// comes after the value and that it ties with it, see [`crate::keyset_step`]. `id_step` does the
+/// same for the id that breaks ties. A row comes after the cursor when it comes after it on the
+/// first key or ties on it and comes after it on the rest.
+pub fn after_cursor<K, QS>(
+    sort: &Sort<K>,
+    cursor: Cursor,
+    step: impl Fn(K, SortOrder, CursorKey) -> ApiResult<(Condition<QS>, Condition<QS>)>,
+    id_step: impl Fn(SortOrder, String) -> (Condition<QS>, Condition<QS>),
+) -> ApiResult<Condition<QS>>
+where
+    K: SortKey,
+    QS: 'static,
+    Condition<QS>: BoxableExpression<QS, Sqlite, SqlType = Bool>,
+    diesel::dsl::And<Condition<QS>, Condition<QS>>:
+        BoxableExpression<QS, Sqlite, SqlType = Bool> + 'static,
+    diesel::dsl::Or<Condition<QS>, Condition<QS>>:
+        BoxableExpression<QS, Sqlite, SqlType = Bool> + 'static,
+{
+    if cursor.keys.len() != sort.keys.len() {
+        return Err((StatusCode::BAD_REQUEST, "Invalid cursor".to_string()));
+    }
+
+    // Ids are unique, no row ties with the cursor on them
+    let (mut condition, _) = id_step(sort.id_order(), cursor.id);
+
+    for (&(key, order), value) in sort.keys.iter().zip(cursor.keys).rev() {
+        let (after, tie) = step(key, order, value)?;
+        let tie_and_after: Condition<QS> = Box::new(tie.and(condition));
+
+        condition = Box::new(after.or(tie_and_after));
+    }
+
+    Ok(condition)
+}
+
+/// Lists with cursor pagination load one row more than `limit` to find out whether there is a
+/// next page, this drops that row again and returns the last row of the page when there is one
+pub fn last_of_page<T>(data: &mut Vec<T>, limit: Option<i64>) -> Option<&T> {
+    let limit = limit?.max(0) as usize;
+
+    if data.len() <= limit {
+        return None;
+    }
+
+    data.truncate(limit);
+    data.last()
+}
+
+/// Rejects `offset` together with `cursor`, they are two ways to select the same page
+pub fn check_cursor_offset(cursor: &Option<String>, offset: Option<i64>) -> ApiResult<()> {
+    if cursor.is_some() && offset.is_some() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "cursor and offset can not be combined".to_string(),
+        ));
+    }
+
+    Ok(())
 }
 
 #[derive(utoipa::ToSchema, Serialize, Deserialize, TS)]
@@ -1,10 +1,26 @@
+/// Orders `$query` by `$expr` in `$order` after the keys it is already ordered by
 #[macro_export]
 macro_rules! apply_sort {
-    ($query:expr, $column:expr, $order:expr) => {
+    ($query:expr, $expr:expr, $order:expr) => {
         match $order {
-            Some(SortOrder::Asc) => $query.order($column.asc()),
-            Some(SortOrder::Desc) => $query.order($column.desc()),
-            None => $query,
+            SortOrder::Asc => $query.then_order_by($expr.asc()),
+            SortOrder::Desc => $query.then_order_by($expr.desc()),
         }
     };
 }
+
+/// Conditions that a row comes after `$value` of `$expr` in `$order` and that it ties with it, one
+/// step of the condition built by [`crate::api_prelude::after_cursor`]
+#[macro_export]
+macro_rules! keyset_step {
+    ($expr:expr, $order:expr, $value:expr) => {{
+        let value = $value;
+
+        let after: Condition<_> = match $order {
+            SortOrder::Asc => Box::new($expr.gt(value.clone())),
+            SortOrder::Desc => Box::new($expr.lt(value.clone())),
+        };
+
+        (after, Box::new($expr.eq(value)) as Condition<_>)
+    }};
+}
@@ -0,0 +1,386 @@
+//! Backups of the database and the cached images, see [`create`] and [`restore`].
+//!
+//! A backup is a zip archive with a `chianti.db` snapshot and the `images` directory of the data
+//! directory.
+
+use crate::search;
+use crate::state::AppState;
+use diesel::migration::MigrationSource;
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+use diesel::sqlite::Sqlite;
+use diesel_migrations::{EmbeddedMigrations, MigrationHarness};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use zip::write::SimpleFileOptions;
+
+const ARCHIVE_PREFIX: &str = "chianti-backup-";
+const ARCHIVE_EXTENSION: &str = ".zip";
+const DATABASE_FILE: &str = "chianti.db";
+const IMAGES_DIR: &str = "images";
+
+#[derive(Clone, Debug)]
+pub struct BackupConfig {
+    pub dir: PathBuf,
+    /// Number of most recent backups that are kept
+    pub keep: usize,
+}
+
+/// Writes a backup archive to the backups directory and deletes the oldest backups.
+///
+/// The database is copied with `VACUUM INTO`, which reads a consistent snapshot without blocking
+/// the server. The archive is written under a temporary name so an interrupted backup is never
+/// mistaken for a complete one.
+pub fn create(state: &AppState, config: &BackupConfig) -> Result<PathBuf, String> {
+    fs::create_dir_all(&config.dir)
+        .map_err(|e| format!("Failed to create {}: {}", config.dir.display(), e))?;
+
+    let name = format!(
+        "{}{}{}",
+        ARCHIVE_PREFIX,
+        chrono::Utc::now().format("%Y%m%d-%H%M%S"),
+        ARCHIVE_EXTENSION
+    );
+    let archive_path = config.dir.join(&name);
+    let partial_path = config.dir.join(format!(".{}.partial", name));
+    let snapshot_path = config.dir.join(format!(".{}.db", name));
+
+    let result = snapshot_database(state, &snapshot_path)
+        .and_then(|_| write_archive(state, &snapshot_path, &partial_path))
+        .and_then(|_| {
+            fs::rename(&partial_path, &archive_path)
+                .map_err(|e| format!("Failed to move {}: {}", partial_path.display(), e))
+        });
+
+    let _ = fs::remove_file(&snapshot_path);
+
+    if result.is_err() {
+        let _ = fs::remove_file(&partial_path);
+    }
+
+    result?;
+
+    let removed = prune(config)?;
+
+    if removed > 0 {
+        tracing::debug!("Removed {} old backups", removed);
+    }
+
+    Ok(archive_path)
+}
+
+fn snapshot_database(state: &AppState, snapshot_path: &Path) -> Result<(), String> {
+    let mut conn = state.pool.get().map_err(|e| e.to_string())?;
+
+    diesel::sql_query("VACUUM INTO ?")
+        .bind::<Text, _>(snapshot_path.to_string_lossy())
+        .execute(&mut conn)
+        .map_err(|e| format!("Failed to snapshot the database: {}", e))?;
+
+    Ok(())
+}
+
+fn write_archive(
+    state: &AppState,
+    snapshot_path: &Path,
+    archive_path: &Path,
+) -> Result<(), String> {
+    let write_error = |e: io::Error| format!("Failed to write {}: {}", archive_path.display(), e);
+
+    let file = File::create(archive_path).map_err(write_error)?;
+    let mut zip = zip::ZipWriter::new(BufWriter::new(file));
+
+    // Images are already compressed, only the database is worth deflating
+    let deflated = SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .large_file(true);
+    let stored = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+
+    zip.start_file(DATABASE_FILE, deflated)
+        .map_err(|e| write_error(e.into()))?;
+    io::copy(
+        &mut BufReader::new(File::open(snapshot_path).map_err(write_error)?),
+        &mut zip,
+    )
+    .map_err(write_error)?;
+
+    for dir in [&state.channel_avaters_dir, &state.video_thumbnails_dir] {
+        let Some(dir_name) = dir.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+
+        let mut dirs = vec![(dir.clone(), format!("{}/{}", IMAGES_DIR, dir_name))];
+
+        while let Some((dir, prefix)) = dirs.pop() {
+            let entries = match fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(format!("Failed to read {}: {}", dir.display(), e)),
+            };
+
+            for entry in entries {
+                let path = entry.map_err(write_error)?.path();
+                let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+                    continue;
+                };
+                let name = format!("{}/{}", prefix, file_name);
+
+                if path.is_dir() {
+                    dirs.push((path, name));
+                    continue;
+                }
+
+                zip.start_file(name, stored)
+                    .map_err(|e| write_error(e.into()))?;
+                io::copy(&mut File::open(&path).map_err(write_error)?, &mut zip)
+                    .map_err(write_error)?;
+            }
+        }
+    }
+
+    zip.finish().map_err(|e| write_error(e.into()))?;
+
+    Ok(())
+}
+
+/// Backup archives in the backups directory, oldest first
+fn list(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {}: {}", dir.display(), e)),
+    };
+
+    let mut archives = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| {
+                    name.starts_with(ARCHIVE_PREFIX) && name.ends_with(ARCHIVE_EXTENSION)
+                })
+        })
+        .collect::<Vec<PathBuf>>();
+
+    // Names end with the creation time so they sort by age
+    archives.sort();
+
+    Ok(archives)
+}
+
+/// Deletes all but the `keep` most recent backups, returns the number of deleted backups
+fn prune(config: &BackupConfig) -> Result<usize, String> {
+    let archives = list(&config.dir)?;
+    let excess = archives.len().saturating_sub(config.keep);
+
+    for archive in &archives[..excess] {
+        fs::remove_file(archive)
+            .map_err(|e| format!("Failed to remove {}: {}", archive.display(), e))?;
+    }
+
+    Ok(excess)
+}
+
+/// Creates a backup every `interval`, the first one is due `interval` after the newest existing
+/// backup so restarting the server does not delay or repeat backups.
+pub fn spawn_scheduler(state: AppState, config: BackupConfig, interval: Duration) {
+    tokio::spawn(async move {
+        loop {
+            let last_backup = list(&config.dir)
+                .ok()
+                .and_then(|archives| archives.last().cloned())
+                .and_then(|archive| fs::metadata(archive).ok())
+                .and_then(|metadata| metadata.modified().ok());
+
+            let wait = last_backup
+                .and_then(|last_backup| SystemTime::now().duration_since(last_backup).ok())
+                .map_or(Duration::ZERO, |elapsed| interval.saturating_sub(elapsed));
+
+            tokio::time::sleep(wait).await;
+
+            let state = state.clone();
+            let config = config.clone();
+
+            let result = tokio::task::spawn_blocking(move || create(&state, &config))
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|result| result);
+
+            match result {
+                Ok(archive) => tracing::info!("Created backup {}", archive.display()),
+                Err(e) => {
+                    tracing::error!("Failed to create backup: {}", e);
+                    // Retry after an interval instead of right away
+                    tokio::time::sleep(interval).await;
+                }
+            }
+        }
+    });
+}
+
+/// Replaces the database and images of the data directory with the contents of a backup.
+///
+/// The server must not be running. The backup is rejected when it was made by a newer version
+/// that applied migrations this version does not know, older backups are migrated on the next
+/// start. The replaced files are moved to a `pre-restore-<time>` directory in the data directory.
+pub fn restore(
+    archive_path: &Path,
+    data_path: &Path,
+    migrations: EmbeddedMigrations,
+) -> Result<PathBuf, String> {
+    let staging_path = data_path.join(".restore");
+
+    if staging_path.exists() {
+        fs::remove_dir_all(&staging_path)
+            .map_err(|e| format!("Failed to remove {}: {}", staging_path.display(), e))?;
+    }
+
+    let result = extract(archive_path, &staging_path)
+        .and_then(|_| check_migrations(&staging_path.join(DATABASE_FILE), migrations))
+        .and_then(|_| rebuild_search_index(&staging_path.join(DATABASE_FILE)))
+        .and_then(|_| replace(&staging_path, data_path));
+
+    let _ = fs::remove_dir_all(&staging_path);
+
+    result
+}
+
+fn extract(archive_path: &Path, staging_path: &Path) -> Result<(), String> {
+    let read_error =
+        |e: zip::result::ZipError| format!("Failed to read {}: {}", archive_path.display(), e);
+
+    let file = File::open(archive_path)
+        .map_err(|e| format!("Failed to open {}: {}", archive_path.display(), e))?;
+    let mut zip = zip::ZipArchive::new(BufReader::new(file)).map_err(read_error)?;
+
+    if zip.index_for_name(DATABASE_FILE).is_none() {
+        return Err(format!(
+            "{} is not a chianti backup, it has no {}",
+            archive_path.display(),
+            DATABASE_FILE
+        ));
+    }
+
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i).map_err(read_error)?;
+
+        // Entries with absolute paths or `..` are never extracted
+        let Some(name) = entry.enclosed_name() else {
+            continue;
+        };
+
+        if entry.is_dir() || !(name == Path::new(DATABASE_FILE) || name.starts_with(IMAGES_DIR)) {
+            continue;
+        }
+
+        let path = staging_path.join(name);
+        let write_error = |e: io::Error| format!("Failed to write {}: {}", path.display(), e);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(write_error)?;
+        }
+
+        io::copy(&mut entry, &mut File::create(&path).map_err(write_error)?)
+            .map_err(write_error)?;
+    }
+
+    Ok(())
+}
+
+fn check_migrations(database_path: &Path, migrations: EmbeddedMigrations) -> Result<(), String> {
+    let mut conn = SqliteConnection::establish(&database_path.to_string_lossy())
+        .map_err(|e| format!("Failed to open the backup database: {}", e))?;
+
+    let applied = conn
+        .applied_migrations()
+        .map_err(|e| format!("Failed to read the migrations of the backup: {}", e))?;
+
+    if applied.is_empty() {
+        return Err("The backup database has no migrations, it is not a chianti database".into());
+    }
+
+    let known = MigrationSource::<Sqlite>::migrations(&migrations)
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(|migration| migration.name().version().to_string())
+        .collect::<Vec<String>>();
+
+    if let Some(unknown) = applied
+        .iter()
+        .find(|version| !known.contains(&version.to_string()))
+    {
+        return Err(format!(
+            "The backup was made by a newer version of chianti, migration {} is unknown",
+            unknown
+        ));
+    }
+
+    let pending = known.len().saturating_sub(applied.len());
+
+    if pending > 0 {
+        tracing::info!(
+            "The backup is {} migrations behind, they run on the next start",
+            pending
+        );
+    }
+
+    Ok(())
+}
+
+/// Rowids can change when the database is copied into a backup, see [`search::rebuild`]
+fn rebuild_search_index(database_path: &Path) -> Result<(), String> {
+    let mut conn = SqliteConnection::establish(&database_path.to_string_lossy())
+        .map_err(|e| format!("Failed to open the backup database: {}", e))?;
+
+    search::rebuild(&mut conn)
+        .map_err(|e| format!("Failed to rebuild the search index of the backup: {}", e))
+}
+
+/// Moves the current database and images aside and the restored ones into their place
+fn replace(staging_path: &Path, data_path: &Path) -> Result<PathBuf, String> {
+    let previous_path = data_path.join(format!(
+        "pre-restore-{}",
+        chrono::Utc::now().format("%Y%m%d-%H%M%S")
+    ));
+
+    let rename = |from: &Path, to: &Path| {
+        fs::rename(from, to).map_err(|e| {
+            format!(
+                "Failed to move {} to {}: {}",
+                from.display(),
+                to.display(),
+                e
+            )
+        })
+    };
+
+    fs::create_dir_all(&previous_path)
+        .map_err(|e| format!("Failed to create {}: {}", previous_path.display(), e))?;
+
+    for name in [
+        DATABASE_FILE,
+        "chianti.db-journal",
+        "chianti.db-wal",
+        "chianti.db-shm",
+        IMAGES_DIR,
+    ] {
+        let path = data_path.join(name);
+
+        if path.exists() {
+            rename(&path, &previous_path.join(name))?;
+        }
+    }
+
+    for name in [DATABASE_FILE, IMAGES_DIR] {
+        let path = staging_path.join(name);
+
+        if path.exists() {
+            rename(&path, &data_path.join(name))?;
+        }
+    }
+
+    Ok(previous_path)
+}
@@ -0,0 +1,205 @@
+use crate::export::ExportFormat;
+use crate::importers;
+use crate::routes::api::WatchHistoryFilters;
+use clap::{Args as _, CommandFactory, FromArgMatches, Parser, Subcommand, parser::ValueSource};
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+/// Without a command the server is started, `chianti -p 80` is the same as `chianti serve -p 80`.
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct Args {
+    /// Config file, `$XDG_CONFIG_HOME/chianti/config.toml` by default
+    #[arg(short, long, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Directory of the database and cached images
+    #[arg(short, long, global = true)]
+    pub data_dir: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub serve: ServeArgs,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Args {
+    /// Parses the command line, server options are rejected before any other command since they
+    /// would be ignored
+    pub fn parse_checked() -> Self {
+        let matches = Self::command().get_matches();
+
+        if matches.subcommand_name().is_some()
+            && let Some(option) = ServeArgs::augment_args(clap::Command::new("serve"))
+                .get_arguments()
+                .filter(|arg| {
+                    matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine)
+                })
+                .find_map(|arg| arg.get_long())
+        {
+            Self::command()
+                .error(
+                    clap::error::ErrorKind::ArgumentConflict,
+                    format!("--{} is a server option, pass it after `serve`", option),
+                )
+                .exit();
+        }
+
+        Self::from_arg_matches(&matches).unwrap_or_else(|e| e.exit())
+    }
+}
+
+/// Flags that override the config file, see [`crate::config`]
+#[derive(clap::Args, Debug, Default, Clone)]
+pub struct ServeArgs {
+    /// Address the server listens on, `0.0.0.0` by default
+    #[arg(short, long)]
+    pub listen_address: Option<IpAddr>,
+
+    /// Port the server listens on, 8080 by default
+    #[arg(short, long)]
+    pub port: Option<u16>,
+
+    #[arg(short, long)]
+    pub frontend_dir: Option<PathBuf>,
+
+    /// Seconds without a heartbeat after which an open watch session is saved to the watch
+    /// history, 300 by default
+    #[arg(long)]
+    pub session_timeout: Option<u64>,
+
+    /// Number of background workers that download images, 2 by default
+    #[arg(long)]
+    pub image_workers: Option<usize>,
+
+    /// Hours between scheduled backups, no backups are scheduled when not set or 0
+    #[arg(long)]
+    pub backup_interval: Option<u64>,
+
+    #[command(flatten)]
+    pub backup: BackupArgs,
+}
+
+#[derive(clap::Args, Debug, Default, Clone)]
+pub struct BackupArgs {
+    /// Directory backups are written to, `<data dir>/backups` by default
+    #[arg(long)]
+    pub backups_dir: Option<PathBuf>,
+
+    /// Number of most recent backups that are kept, 7 by default
+    #[arg(long)]
+    pub backup_keep: Option<usize>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Start the server, the default command
+    Serve(ServeArgs),
+    /// Apply pending database migrations
+    Migrate {
+        #[command(subcommand)]
+        command: Option<MigrateCommand>,
+    },
+    /// Print an overview of the watch history
+    Stats {
+        /// Print the overview as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Check the database and cached images for problems, exits with an error if any are found
+    Check,
+    /// Import watch history and subscriptions from the export file of another service
+    Import {
+        /// Service the file was exported from
+        #[arg(value_parser = clap::builder::PossibleValuesParser::new(importers::names()))]
+        source: String,
+
+        /// Export file, or a directory of export files
+        file: PathBuf,
+
+        /// Report what the import would create or update without saving anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Export channels, videos, tags and watch history, the json export can be imported again
+    /// with the chianti source
+    Export {
+        /// Export format
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+
+        /// File to write the export to, standard output by default
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        #[command(flatten)]
+        filters: WatchHistoryFilters,
+    },
+    /// Write the OpenAPI spec of the server
+    Openapi {
+        /// File to write the spec to, standard output by default
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Manage cached images
+    Images {
+        #[command(subcommand)]
+        command: ImagesCommand,
+
+        /// Number of concurrent image downloads, `images.workers` of the config by default
+        #[arg(long)]
+        image_workers: Option<usize>,
+    },
+    /// Back up the database and cached images while the server may be running
+    Backup(BackupArgs),
+    /// Replace the database and cached images with the contents of a backup, the server must be
+    /// stopped
+    Restore {
+        /// Backup archive
+        archive: PathBuf,
+    },
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+impl Command {
+    /// Flags of the command that override the config file
+    pub fn config_flags(&self) -> ServeArgs {
+        match self {
+            Command::Serve(args) => args.clone(),
+            Command::Images { image_workers, .. } => ServeArgs {
+                image_workers: *image_workers,
+                ..Default::default()
+            },
+            Command::Backup(args) => ServeArgs {
+                backup: args.clone(),
+                ..Default::default()
+            },
+            _ => ServeArgs::default(),
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum MigrateCommand {
+    /// Apply pending migrations, the default
+    Run,
+    /// List applied and pending migrations
+    Status,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ImagesCommand {
+    /// Download every channel avatar and video thumbnail that is missing on disk
+    Backfill,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Print the effective configuration and where each value came from
+    Show,
+}
@@ -0,0 +1,186 @@
+use crate::database::models;
+use crate::schema;
+use crate::state::AppState;
+use crate::utils;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Text};
+
+/// Rows listed per failed check, the rest are only counted
+const MAX_LISTED: usize = 10;
+
+#[derive(QueryableByName)]
+struct Message {
+    #[diesel(sql_type = Text)]
+    message: String,
+}
+
+/// Returns a description of every problem found
+type Check = fn(&mut SqliteConnection, &AppState) -> QueryResult<Vec<String>>;
+
+/// Runs every check and prints its result, fails when any check found a problem.
+pub fn run(app_state: &AppState) -> Result<(), String> {
+    let mut conn = app_state.pool.get().map_err(|e| e.to_string())?;
+
+    let checks: [(&str, Check); 6] = [
+        ("Database integrity", integrity),
+        ("Foreign keys", foreign_keys),
+        ("Watch history dates and durations", watch_history_ranges),
+        ("Video watch counters", watch_counters),
+        ("Failed image downloads", failed_jobs),
+        ("Cached images", missing_images),
+    ];
+
+    let mut failed = 0;
+
+    for (name, check) in checks {
+        let problems = check(&mut conn, app_state)
+            .map_err(|e| format!("Failed to check {}: {}", name.to_lowercase(), e))?;
+
+        if problems.is_empty() {
+            println!("ok    {}", name);
+            continue;
+        }
+
+        failed += 1;
+        println!("fail  {} ({} problems)", name, problems.len());
+
+        for problem in problems.iter().take(MAX_LISTED) {
+            println!("      {}", problem);
+        }
+
+        if problems.len() > MAX_LISTED {
+            println!("      ...");
+        }
+    }
+
+    if failed > 0 {
+        return Err(format!("{} checks found problems", failed));
+    }
+
+    Ok(())
+}
+
+fn integrity(conn: &mut SqliteConnection, _: &AppState) -> QueryResult<Vec<String>> {
+    let messages =
+        diesel::sql_query("SELECT integrity_check AS message FROM pragma_integrity_check()")
+            .load::<Message>(conn)?;
+
+    Ok(messages
+        .into_iter()
+        .map(|row| row.message)
+        .filter(|message| message != "ok")
+        .collect())
+}
+
+fn foreign_keys(conn: &mut SqliteConnection, _: &AppState) -> QueryResult<Vec<String>> {
+    let messages = diesel::sql_query(
+        "SELECT \"table\" || ' row ' || rowid || ' references a missing ' || parent AS message
+        FROM pragma_foreign_key_check()",
+    )
+    .load::<Message>(conn)?;
+
+    Ok(messages.into_iter().map(|row| row.message).collect())
+}
+
+fn watch_history_ranges(conn: &mut SqliteConnection, _: &AppState) -> QueryResult<Vec<String>> {
+    use schema::watch_history::dsl as watch_history_dsl;
+
+    let rows = watch_history_dsl::watch_history
+        .filter(
+            watch_history_dsl::session_end_date
+                .lt(watch_history_dsl::session_start_date)
+                .or(watch_history_dsl::watch_duration_seconds.lt(0)),
+        )
+        .load::<models::WatchHistory>(conn)?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            format!(
+                "{} ends at {} before it starts at {} or has a negative duration of {}s",
+                row.id, row.session_end_date, row.session_start_date, row.watch_duration_seconds
+            )
+        })
+        .collect())
+}
+
+#[derive(QueryableByName)]
+struct WatchCounter {
+    #[diesel(sql_type = Text)]
+    id: String,
+    #[diesel(sql_type = BigInt)]
+    watch_counter: i64,
+    #[diesel(sql_type = BigInt)]
+    watched: i64,
+}
+
+fn watch_counters(conn: &mut SqliteConnection, _: &AppState) -> QueryResult<Vec<String>> {
+    let rows = diesel::sql_query(
+        "SELECT videos.id, videos.watch_counter, COUNT(watch_history.id) AS watched
+        FROM videos LEFT JOIN watch_history ON watch_history.video_id = videos.id
+        GROUP BY videos.id
+        HAVING videos.watch_counter != watched",
+    )
+    .load::<WatchCounter>(conn)?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            format!(
+                "Video {} has a watch counter of {} but {} watch history records",
+                row.id, row.watch_counter, row.watched
+            )
+        })
+        .collect())
+}
+
+fn failed_jobs(conn: &mut SqliteConnection, _: &AppState) -> QueryResult<Vec<String>> {
+    use schema::jobs::dsl as jobs_dsl;
+
+    let jobs = jobs_dsl::jobs
+        .filter(jobs_dsl::status.eq(models::JobStatus::Failed.as_str()))
+        .load::<models::Job>(conn)?;
+
+    Ok(jobs
+        .into_iter()
+        .map(|job| {
+            format!(
+                "{} of {} failed: {}",
+                job.kind,
+                job.target_id,
+                job.last_error.unwrap_or_default()
+            )
+        })
+        .collect())
+}
+
+/// Images that `chianti images backfill` would download
+fn missing_images(conn: &mut SqliteConnection, app_state: &AppState) -> QueryResult<Vec<String>> {
+    use schema::channels::dsl as channels_dsl;
+    use schema::videos::dsl as videos_dsl;
+
+    let channel_ids = channels_dsl::channels
+        .select(channels_dsl::id)
+        .load::<String>(conn)?;
+    let video_ids = videos_dsl::videos
+        .select(videos_dsl::id)
+        .load::<String>(conn)?;
+
+    let missing_avatars = channel_ids.into_iter().filter_map(|id| {
+        let path = app_state
+            .channel_avaters_dir
+            .join(utils::build_avater_cache_image_filename(&id));
+
+        (!path.exists()).then(|| format!("Avatar of channel {} is missing", id))
+    });
+
+    let missing_thumbnails = video_ids.into_iter().filter_map(|id| {
+        let path = app_state
+            .video_thumbnails_dir
+            .join(utils::build_thumbnail_cache_image_filename(&id));
+
+        (!path.exists()).then(|| format!("Thumbnail of video {} is missing", id))
+    });
+
+    Ok(missing_avatars.chain(missing_thumbnails).collect())
+}
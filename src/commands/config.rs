@@ -0,0 +1,30 @@
+use crate::config::Config;
+
+pub fn show(config: &Config) -> Result<(), String> {
+    match &config.file {
+        Some(path) if path.exists() => println!("Config file: {}", path.display()),
+        Some(path) => println!("Config file: {} (not found)", path.display()),
+        None => println!("Config file: none"),
+    }
+
+    println!();
+
+    let entries = config.entries();
+
+    let key_width = entries
+        .iter()
+        .map(|(key, _, _)| key.len())
+        .max()
+        .unwrap_or(0);
+    let value_width = entries
+        .iter()
+        .map(|(_, value, _)| value.len())
+        .max()
+        .unwrap_or(0);
+
+    for (key, value, source) in entries {
+        println!("{:<key_width$}  {:<value_width$}  {}", key, value, source);
+    }
+
+    Ok(())
+}
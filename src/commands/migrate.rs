@@ -0,0 +1,58 @@
+use crate::cli::MigrateCommand;
+use crate::state::AppState;
+use diesel::migration::MigrationSource;
+use diesel::sqlite::Sqlite;
+use diesel_migrations::MigrationHarness;
+
+pub fn run(app_state: &AppState, command: Option<MigrateCommand>) -> Result<(), String> {
+    let mut conn = app_state.pool.get().map_err(|e| e.to_string())?;
+
+    match command.unwrap_or(MigrateCommand::Run) {
+        MigrateCommand::Run => {
+            let applied = conn
+                .run_pending_migrations(crate::MIGRATIONS)
+                .map_err(|e| format!("Failed to run migrations: {}", e))?;
+
+            if applied.is_empty() {
+                println!("No pending migrations");
+            }
+
+            for version in applied {
+                println!("Applied {}", version);
+            }
+        }
+        MigrateCommand::Status => {
+            let applied = conn
+                .applied_migrations()
+                .map_err(|e| format!("Failed to read applied migrations: {}", e))?
+                .iter()
+                .map(|version| version.to_string())
+                .collect::<Vec<String>>();
+
+            let known = MigrationSource::<Sqlite>::migrations(&crate::MIGRATIONS)
+                .map_err(|e| e.to_string())?;
+
+            for migration in &known {
+                let status = if applied.contains(&migration.name().version().to_string()) {
+                    "applied"
+                } else {
+                    "pending"
+                };
+
+                println!("{}  {}", status, migration.name());
+            }
+
+            // Applied by a newer version of chianti
+            for version in &applied {
+                if !known
+                    .iter()
+                    .any(|migration| migration.name().version().to_string() == *version)
+                {
+                    println!("unknown  {}", version);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
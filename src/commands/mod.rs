@@ -0,0 +1,225 @@
+mod check;
+mod config;
+mod migrate;
+mod serve;
+mod stats;
+
+use crate::backup;
+use crate::cli::{Command, ConfigCommand, ImagesCommand};
+use crate::config::Config;
+use crate::database;
+use crate::export;
+use crate::images;
+use crate::importers;
+use crate::routes;
+use crate::search;
+use crate::state::AppState;
+use diesel_migrations::MigrationHarness;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Creates the data directory of the config
+fn data_path(config: &Config) -> Result<PathBuf, String> {
+    let data_path = config.data_dir.value.clone();
+
+    if !data_path.exists() {
+        std::fs::create_dir_all(&data_path)
+            .map_err(|e| format!("Failed to create data directory: {}", e))?;
+    }
+
+    tracing::debug!("Data directory: {}", data_path.display());
+
+    Ok(data_path)
+}
+
+/// Opens the database of the data directory and creates the image directories, migrations are
+/// not run, see [`open_migrated`].
+#[allow(clippy::collapsible_if)]
+fn open(data_path: &Path, config: &Config) -> AppState {
+    let images_directory = data_path.join("images");
+
+    let channel_avaters_dir = images_directory.join("channel-avatars");
+    let video_thumbnails_dir = images_directory.join("video-thumbnails");
+    let video_thumbnail_revisions_dir = video_thumbnails_dir.join("revisions");
+
+    if !channel_avaters_dir.exists() {
+        if let Err(e) = std::fs::create_dir_all(&channel_avaters_dir) {
+            tracing::error!("Failed to create channel avaters directory: {}", e);
+        }
+    }
+
+    if !video_thumbnails_dir.exists() {
+        if let Err(e) = std::fs::create_dir_all(&video_thumbnails_dir) {
+            tracing::error!("Failed to create video thumbnails directory: {}", e);
+        }
+    }
+
+    if !video_thumbnail_revisions_dir.exists() {
+        if let Err(e) = std::fs::create_dir_all(&video_thumbnail_revisions_dir) {
+            tracing::error!(
+                "Failed to create video thumbnail revisions directory: {}",
+                e
+            );
+        }
+    }
+
+    AppState {
+        pool: database::connection::create_connection_pool(data_path.to_path_buf()),
+        channel_avaters_dir,
+        video_thumbnails_dir,
+        video_thumbnail_revisions_dir,
+        http_client: images::build_http_client(Duration::from_secs(
+            config.images.fetch_timeout.value,
+        )),
+    }
+}
+
+/// Opens the data directory, applies pending migrations and repairs the search index
+fn open_migrated(data_path: &Path, config: &Config) -> Result<AppState, String> {
+    let app_state = open(data_path, config);
+    let mut conn = app_state.pool.get().map_err(|e| e.to_string())?;
+
+    conn.run_pending_migrations(crate::MIGRATIONS)
+        .map_err(|e| format!("Failed to run migrations: {}", e))?;
+
+    tracing::debug!("Successfully ran migrations");
+
+    if search::rebuild_if_out_of_sync(&mut conn)
+        .map_err(|e| format!("Failed to check the search index: {}", e))?
+    {
+        tracing::info!("Rebuilt the search index, it no longer matched its tables");
+    }
+
+    drop(conn);
+
+    Ok(app_state)
+}
+
+fn backup_config(config: &Config) -> backup::BackupConfig {
+    backup::BackupConfig {
+        dir: config.backups.dir.value.clone(),
+        keep: config.backups.keep.value,
+    }
+}
+
+pub async fn run(command: Command, config: Config) -> Result<(), String> {
+    // Showing the config must not create the data directory
+    if let Command::Config {
+        command: ConfigCommand::Show,
+    } = command
+    {
+        return config::show(&config);
+    }
+
+    let data_path = data_path(&config)?;
+
+    match command {
+        Command::Serve(_) => {
+            let app_state = open_migrated(&data_path, &config)?;
+
+            serve::run(app_state, &config).await?;
+        }
+        Command::Migrate { command } => {
+            migrate::run(&open(&data_path, &config), command)?;
+        }
+        Command::Stats { json } => {
+            stats::run(&open_migrated(&data_path, &config)?, json)?;
+        }
+        Command::Check => {
+            check::run(&open_migrated(&data_path, &config)?)?;
+        }
+        Command::Import {
+            source,
+            file,
+            dry_run,
+        } => {
+            let importer =
+                importers::find(&source).ok_or_else(|| format!("Unknown source {}", source))?;
+
+            let batch = importers::parse_path(importer, &file)?;
+
+            let app_state = open_migrated(&data_path, &config)?;
+            let mut conn = app_state.pool.get().map_err(|e| e.to_string())?;
+            let summary = importers::apply(&mut conn, &batch, dry_run)
+                .map_err(|e| format!("Failed to import {}: {}", file.display(), e))?;
+
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&summary).map_err(|e| e.to_string())?
+            );
+        }
+        Command::Export {
+            format,
+            output,
+            filters,
+        } => {
+            let app_state = open_migrated(&data_path, &config)?;
+            let mut conn = app_state.pool.get().map_err(|e| e.to_string())?;
+
+            match output {
+                Some(path) => {
+                    let file = std::fs::File::create(&path)
+                        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+
+                    export::write(&mut conn, format, &filters, std::io::BufWriter::new(file))?;
+                }
+                None => {
+                    let stdout = std::io::stdout().lock();
+
+                    export::write(&mut conn, format, &filters, std::io::BufWriter::new(stdout))?;
+                }
+            }
+        }
+        Command::Openapi { output } => {
+            let (_, api_doc) = routes::api_router();
+            let spec = api_doc.to_pretty_json().map_err(|e| e.to_string())?;
+
+            match output {
+                Some(path) => std::fs::write(&path, spec)
+                    .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?,
+                None => println!("{}", spec),
+            }
+        }
+        Command::Images {
+            command: ImagesCommand::Backfill,
+            ..
+        } => {
+            let app_state = open_migrated(&data_path, &config)?;
+
+            // Pending image downloads are left to the server
+            let summary = images::backfill(&app_state, config.images.workers.value)
+                .await
+                .map_err(|e| format!("Failed to backfill images: {}", e))?;
+
+            tracing::info!(
+                "Repaired {} missing images, {} failed, {} left to pending downloads",
+                summary.repaired,
+                summary.failed,
+                summary.queued
+            );
+        }
+        Command::Backup(_) => {
+            let archive = backup::create(
+                &open_migrated(&data_path, &config)?,
+                &backup_config(&config),
+            )?;
+
+            tracing::info!("Created backup {}", archive.display());
+        }
+        Command::Restore { archive } => {
+            // The database is not opened since its file is replaced
+            let previous_path = backup::restore(&archive, &data_path, crate::MIGRATIONS)
+                .map_err(|e| format!("Failed to restore {}: {}", archive.display(), e))?;
+
+            tracing::info!(
+                "Restored {}, the replaced data was moved to {}",
+                archive.display(),
+                previous_path.display()
+            );
+        }
+        // Handled before the data directory is created
+        Command::Config { .. } => {}
+    }
+
+    Ok(())
+}
@@ -0,0 +1,164 @@
+use crate::backup;
+use crate::config::Config;
+use crate::jobs;
+use crate::routes;
+use crate::state::AppState;
+use axum::{
+    body::Bytes,
+    extract::MatchedPath,
+    http::{HeaderMap, HeaderValue, Request},
+    response::Response,
+    routing::{get, get_service},
+};
+use std::{net::SocketAddr, time::Duration};
+use tower_http::{
+    classify::ServerErrorsFailureClass,
+    cors::{AllowOrigin, Any, CorsLayer},
+    services::{ServeDir, ServeFile},
+    trace::TraceLayer,
+};
+use tracing::{Span, info_span};
+use utoipa_rapidoc::RapiDoc;
+
+pub async fn run(app_state: AppState, config: &Config) -> Result<(), String> {
+    let frontend_path = &config.server.frontend_dir.value;
+
+    let html_path = frontend_path.join("index.html");
+    let assets_path = frontend_path.join("assets");
+
+    tracing::debug!("Frontend directory: {}", frontend_path.display());
+
+    let webui_html_file = get_service(ServeFile::new(html_path));
+    let webui_assets = get_service(ServeDir::new(assets_path));
+
+    if let Ok(mut conn) = app_state.pool.get() {
+        match jobs::requeue_interrupted(&mut conn) {
+            Ok(0) => {}
+            Ok(count) => tracing::debug!("Requeued {} interrupted jobs", count),
+            Err(e) => tracing::error!("Failed to requeue interrupted jobs: {}", e),
+        }
+    }
+
+    jobs::spawn_workers(app_state.clone(), &config.images);
+
+    if config.backups.interval.value > 0 {
+        backup::spawn_scheduler(
+            app_state.clone(),
+            super::backup_config(config),
+            Duration::from_secs(config.backups.interval.value * 60 * 60),
+        );
+    }
+
+    let cors_layer = cors_layer(&config.server.cors_origins.value)?;
+
+    let session_timeout = Duration::from_secs(config.server.session_timeout.value);
+    let finalizer_state = app_state.clone();
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+
+        loop {
+            interval.tick().await;
+
+            let result = finalizer_state
+                .db(move |conn| {
+                    routes::api::finalize_stale_watch_sessions(conn, session_timeout)
+                        .map_err(|e| e.to_string())
+                })
+                .await;
+
+            match result {
+                Ok(0) => {}
+                Ok(count) => tracing::debug!("Finalized {} stale watch sessions", count),
+                Err(e) => tracing::error!("Failed to finalize stale watch sessions: {}", e),
+            }
+        }
+    });
+
+    let (openapi_router, api_doc) = routes::api_router();
+
+    let rapi_doc = RapiDoc::with_openapi("/api-docs/openapi.json", api_doc).path("/docs");
+
+    // build our application with a route
+    let app = openapi_router
+        .route("/", get(routes::root))
+        .merge(rapi_doc)
+        .nest_service("/web", webui_html_file)
+        .nest_service("/assets", webui_assets)
+        .fallback(routes::handle_404)
+        .with_state(app_state)
+        .layer(cors_layer)
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(|request: &Request<_>| {
+                    // Log the matched route's path (with placeholders not filled in).
+                    // Use request.uri() or OriginalUri if you want the real path.
+                    let matched_path = request
+                        .extensions()
+                        .get::<MatchedPath>()
+                        .map(MatchedPath::as_str);
+
+                    info_span!(
+                        "http_request",
+                        method = ?request.method(),
+                        matched_path,
+                        some_other_field = tracing::field::Empty,
+                    )
+                })
+                .on_request(|_request: &Request<_>, _span: &Span| {
+                    // You can use `_span.record("some_other_field", value)` in one of these
+                    // closures to attach a value to the initially empty field in the info_span
+                    // created above.
+                })
+                .on_response(|response: &Response, latency: Duration, span: &Span| {
+                    span.record("status_code", response.status().as_u16());
+                    span.record("latency", format!("{latency:?}"));
+                    tracing::info!(
+                        "Finished request. Status: {}, Latency: {:?}",
+                        response.status(),
+                        latency
+                    );
+                })
+                .on_body_chunk(|_chunk: &Bytes, _latency: Duration, _span: &Span| {
+                    // ...
+                })
+                .on_eos(
+                    |_trailers: Option<&HeaderMap>, _stream_duration: Duration, _span: &Span| {
+                        // ...
+                    },
+                )
+                .on_failure(
+                    |error: ServerErrorsFailureClass, _latency: Duration, span: &Span| {
+                        span.record("server_error_failure_class", format!("{error}"));
+                        tracing::error!("Request failed: {:?}", error);
+                    },
+                ),
+        );
+
+    // run our app with hyper
+    let address = SocketAddr::new(config.server.listen_address.value, config.server.port.value);
+    let listener = tokio::net::TcpListener::bind(address)
+        .await
+        .map_err(|e| format!("Failed to listen on {}: {}", address, e))?;
+    tracing::debug!("listening on {}", listener.local_addr().unwrap());
+    axum::serve(listener, app).await.map_err(|e| e.to_string())
+}
+
+/// Allows every origin when `origins` contains `*`, and no cross-origin requests when it is empty
+fn cors_layer(origins: &[String]) -> Result<CorsLayer, String> {
+    if origins.iter().any(|origin| origin == "*") {
+        return Ok(CorsLayer::permissive());
+    }
+
+    let origins = origins
+        .iter()
+        .map(|origin| {
+            HeaderValue::from_str(origin).map_err(|_| format!("Invalid CORS origin {}", origin))
+        })
+        .collect::<Result<Vec<HeaderValue>, String>>()?;
+
+    Ok(CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods(Any)
+        .allow_headers(Any))
+}
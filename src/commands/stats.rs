@@ -0,0 +1,68 @@
+use crate::routes::api;
+use crate::state::AppState;
+
+pub fn run(app_state: &AppState, json: bool) -> Result<(), String> {
+    let mut conn = app_state.pool.get().map_err(|e| e.to_string())?;
+    let overview = api::load_overview(&mut conn).map_err(|e| e.to_string())?;
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&overview).map_err(|e| e.to_string())?
+        );
+
+        return Ok(());
+    }
+
+    let rows = [
+        (
+            "Total watch time",
+            format_duration(overview.total_watch_time_seconds),
+        ),
+        ("Videos watched", overview.total_videos_watched.to_string()),
+        (
+            "Unique videos watched",
+            overview.total_unique_videos_watched.to_string(),
+        ),
+        ("Channels", overview.total_channels.to_string()),
+        ("Tags", overview.total_tags.to_string()),
+        (
+            "Average watch time per session",
+            format_duration(overview.average_watch_time_per_session_seconds),
+        ),
+        (
+            "Average session duration",
+            format_duration(overview.average_session_duration_seconds),
+        ),
+    ];
+
+    let width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+
+    for (label, value) in rows {
+        println!("{:<width$}  {}", label, value);
+    }
+
+    Ok(())
+}
+
+/// e.g. `3d 4h 5m 6s`, leading zero units are left out
+fn format_duration(seconds: i64) -> String {
+    let units = [
+        ("d", seconds / 86400),
+        ("h", seconds % 86400 / 3600),
+        ("m", seconds % 3600 / 60),
+        ("s", seconds % 60),
+    ];
+
+    let parts = units
+        .iter()
+        .skip_while(|(_, value)| *value == 0)
+        .map(|(unit, value)| format!("{}{}", value, unit))
+        .collect::<Vec<String>>();
+
+    if parts.is_empty() {
+        "0s".to_string()
+    } else {
+        parts.join(" ")
+    }
+}
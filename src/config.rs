@@ -0,0 +1,392 @@
+//! Layered configuration, see [`Config::load`].
+//!
+//! Every setting starts at its default and is overridden, in this order, by the config file, a
+//! `CHIANTI_*` environment variable and a command line flag. The environment variable of a setting
+//! is its key in upper case with `.` replaced by `_`, `server.port` is read from
+//! `CHIANTI_SERVER_PORT`.
+//!
+//! ```toml
+//! data_dir = "/var/lib/chianti"
+//!
+//! [server]
+//! listen_address = "127.0.0.1"
+//! port = 8080
+//! cors_origins = ["https://example.com"]
+//!
+//! [images]
+//! workers = 4
+//!
+//! [backups]
+//! interval = 24
+//! keep = 14
+//! ```
+
+use crate::cli::ServeArgs;
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Keys of every setting, other keys in the config file are rejected
+const KEYS: [&str; 13] = [
+    "data_dir",
+    "server.listen_address",
+    "server.port",
+    "server.frontend_dir",
+    "server.cors_origins",
+    "server.session_timeout",
+    "images.workers",
+    "images.fetch_timeout",
+    "images.max_attempts",
+    "images.retention_days",
+    "backups.dir",
+    "backups.interval",
+    "backups.keep",
+];
+
+/// Where the value of a setting came from
+#[derive(Clone, Debug)]
+pub enum Source {
+    Default,
+    /// Debug builds default to the directories of the repository
+    DebugDefault,
+    File(PathBuf),
+    Env(String),
+    Flag(&'static str),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::DebugDefault => write!(f, "debug build default"),
+            Source::File(path) => write!(f, "{}", path.display()),
+            Source::Env(name) => write!(f, "{}", name),
+            Source::Flag(name) => write!(f, "--{}", name),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Setting<T> {
+    pub value: T,
+    pub source: Source,
+}
+
+impl<T> Setting<T> {
+    fn default(value: T) -> Self {
+        Setting {
+            value,
+            source: Source::Default,
+        }
+    }
+
+    /// Replaces the value when the flag was given
+    fn with_flag(self, name: &'static str, flag: Option<T>) -> Self {
+        match flag {
+            Some(value) => Setting {
+                value,
+                source: Source::Flag(name),
+            },
+            None => self,
+        }
+    }
+}
+
+/// A setting value that can be read from the config file and from an environment variable
+trait Value: DeserializeOwned {
+    fn parse_env(value: &str) -> Result<Self, String>;
+
+    fn show(&self) -> String;
+}
+
+macro_rules! impl_value_from_str {
+    ($($type:ty),*) => {
+        $(
+            impl Value for $type {
+                fn parse_env(value: &str) -> Result<Self, String> {
+                    <$type>::from_str(value).map_err(|e| e.to_string())
+                }
+
+                fn show(&self) -> String {
+                    self.to_string()
+                }
+            }
+        )*
+    };
+}
+
+impl_value_from_str!(IpAddr, u16, u64, usize, i64);
+
+impl Value for PathBuf {
+    fn parse_env(value: &str) -> Result<Self, String> {
+        Ok(PathBuf::from(value))
+    }
+
+    fn show(&self) -> String {
+        self.display().to_string()
+    }
+}
+
+/// Lists are comma separated in environment variables
+impl Value for Vec<String> {
+    fn parse_env(value: &str) -> Result<Self, String> {
+        Ok(value
+            .split(',')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect())
+    }
+
+    fn show(&self) -> String {
+        self.join(", ")
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    pub listen_address: Setting<IpAddr>,
+    pub port: Setting<u16>,
+    pub frontend_dir: Setting<PathBuf>,
+    /// Origins allowed to make cross-origin requests, `*` allows every origin
+    pub cors_origins: Setting<Vec<String>>,
+    /// Seconds without a heartbeat after which an open watch session is saved to the watch history
+    pub session_timeout: Setting<u64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ImagesConfig {
+    /// Number of background workers that download images
+    pub workers: Setting<usize>,
+    /// Seconds before a single image download is abandoned
+    pub fetch_timeout: Setting<u64>,
+    /// Attempts before an image download is marked as failed
+    pub max_attempts: Setting<i64>,
+    /// Days finished image downloads are kept in the job list
+    pub retention_days: Setting<i64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct BackupsConfig {
+    pub dir: Setting<PathBuf>,
+    /// Hours between scheduled backups, `0` disables them
+    pub interval: Setting<u64>,
+    /// Number of most recent backups that are kept
+    pub keep: Setting<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Config file that was looked for, it is optional
+    pub file: Option<PathBuf>,
+    pub data_dir: Setting<PathBuf>,
+    pub server: ServerConfig,
+    pub images: ImagesConfig,
+    pub backups: BackupsConfig,
+}
+
+/// `$XDG_DATA_HOME/chianti`, or `/usr/share/chianti`
+fn local_dir() -> PathBuf {
+    match std::env::var("XDG_DATA_HOME") {
+        Ok(p) => PathBuf::from(p).join("chianti"),
+        Err(_) => PathBuf::from("/usr").join("share").join("chianti"),
+    }
+}
+
+/// `$XDG_CONFIG_HOME/chianti/config.toml`, or `~/.config/chianti/config.toml`
+fn default_file() -> Option<PathBuf> {
+    let config_dir = match std::env::var("XDG_CONFIG_HOME") {
+        Ok(p) => PathBuf::from(p),
+        Err(_) => PathBuf::from(std::env::var("HOME").ok()?).join(".config"),
+    };
+
+    Some(config_dir.join("chianti").join("config.toml"))
+}
+
+/// Debug builds use `debug_path` so a checkout of the repository runs without any setup
+fn dir_default(release_path: PathBuf, debug_path: &str) -> Setting<PathBuf> {
+    if cfg!(debug_assertions) {
+        Setting {
+            value: PathBuf::from(debug_path),
+            source: Source::DebugDefault,
+        }
+    } else {
+        Setting::default(release_path)
+    }
+}
+
+/// The config file and environment variables
+struct Layers {
+    file: Option<(PathBuf, toml::Table)>,
+}
+
+impl Layers {
+    fn read(path: &Path) -> Result<toml::Table, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let table = content
+            .parse::<toml::Table>()
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+
+        let mut keys = table.iter().flat_map(|(name, value)| match value {
+            toml::Value::Table(section) => section
+                .keys()
+                .map(|key| format!("{}.{}", name, key))
+                .collect(),
+            _ => vec![name.clone()],
+        });
+
+        if let Some(key) = keys.find(|key| !KEYS.contains(&key.as_str())) {
+            return Err(format!("Unknown setting {} in {}", key, path.display()));
+        }
+
+        Ok(table)
+    }
+
+    fn setting<T: Value>(&self, key: &str, default: Setting<T>) -> Result<Setting<T>, String> {
+        let env_name = format!("CHIANTI_{}", key.replace('.', "_").to_uppercase());
+
+        if let Ok(value) = std::env::var(&env_name) {
+            let value = T::parse_env(&value).map_err(|e| format!("Invalid {}: {}", env_name, e))?;
+
+            return Ok(Setting {
+                value,
+                source: Source::Env(env_name),
+            });
+        }
+
+        let Some((path, table)) = &self.file else {
+            return Ok(default);
+        };
+
+        let value = match key.split_once('.') {
+            Some((section, name)) => table
+                .get(section)
+                .and_then(|section| section.as_table())
+                .and_then(|section| section.get(name)),
+            None => table.get(key),
+        };
+
+        match value {
+            Some(value) => Ok(Setting {
+                value: T::deserialize(value.clone())
+                    .map_err(|e| format!("Invalid {} in {}: {}", key, path.display(), e))?,
+                source: Source::File(path.clone()),
+            }),
+            None => Ok(default),
+        }
+    }
+}
+
+impl Config {
+    /// Resolves every setting. `file` replaces the default config file location and must exist,
+    /// `data_dir` and `flags` are the command line flags.
+    pub fn load(
+        file: Option<PathBuf>,
+        data_dir: Option<PathBuf>,
+        flags: ServeArgs,
+    ) -> Result<Self, String> {
+        let (file, required) = match file {
+            Some(path) => (Some(path), true),
+            None => (default_file(), false),
+        };
+
+        let layers = Layers {
+            file: match &file {
+                Some(path) if required || path.exists() => {
+                    Some((path.clone(), Layers::read(path)?))
+                }
+                _ => None,
+            },
+        };
+
+        let data_dir = layers
+            .setting(
+                "data_dir",
+                dir_default(local_dir().join("data"), "./dev-data"),
+            )?
+            .with_flag("data-dir", data_dir);
+
+        let server = ServerConfig {
+            listen_address: layers
+                .setting(
+                    "server.listen_address",
+                    Setting::default(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+                )?
+                .with_flag("listen-address", flags.listen_address),
+            port: layers
+                .setting("server.port", Setting::default(8080))?
+                .with_flag("port", flags.port),
+            frontend_dir: layers
+                .setting(
+                    "server.frontend_dir",
+                    dir_default(local_dir().join("frontend"), "./web/dist"),
+                )?
+                .with_flag("frontend-dir", flags.frontend_dir),
+            cors_origins: layers
+                .setting("server.cors_origins", Setting::default(vec!["*".into()]))?,
+            session_timeout: layers
+                .setting("server.session_timeout", Setting::default(300))?
+                .with_flag("session-timeout", flags.session_timeout),
+        };
+
+        let images = ImagesConfig {
+            workers: layers
+                .setting("images.workers", Setting::default(2))?
+                .with_flag("image-workers", flags.image_workers),
+            fetch_timeout: layers.setting("images.fetch_timeout", Setting::default(30))?,
+            max_attempts: layers.setting("images.max_attempts", Setting::default(5))?,
+            retention_days: layers.setting("images.retention_days", Setting::default(7))?,
+        };
+
+        let backups = BackupsConfig {
+            dir: layers
+                .setting(
+                    "backups.dir",
+                    Setting::default(data_dir.value.join("backups")),
+                )?
+                .with_flag("backups-dir", flags.backup.backups_dir),
+            interval: layers
+                .setting("backups.interval", Setting::default(0))?
+                .with_flag("backup-interval", flags.backup_interval),
+            keep: layers
+                .setting("backups.keep", Setting::default(7))?
+                .with_flag("backup-keep", flags.backup.backup_keep),
+        };
+
+        Ok(Config {
+            file,
+            data_dir,
+            server,
+            images,
+            backups,
+        })
+    }
+
+    /// Key, value and source of every setting
+    pub fn entries(&self) -> Vec<(&'static str, String, &Source)> {
+        fn entry<'a, T: Value>(
+            key: &'static str,
+            setting: &'a Setting<T>,
+        ) -> (&'static str, String, &'a Source) {
+            (key, setting.value.show(), &setting.source)
+        }
+
+        vec![
+            entry("data_dir", &self.data_dir),
+            entry("server.listen_address", &self.server.listen_address),
+            entry("server.port", &self.server.port),
+            entry("server.frontend_dir", &self.server.frontend_dir),
+            entry("server.cors_origins", &self.server.cors_origins),
+            entry("server.session_timeout", &self.server.session_timeout),
+            entry("images.workers", &self.images.workers),
+            entry("images.fetch_timeout", &self.images.fetch_timeout),
+            entry("images.max_attempts", &self.images.max_attempts),
+            entry("images.retention_days", &self.images.retention_days),
+            entry("backups.dir", &self.backups.dir),
+            entry("backups.interval", &self.backups.interval),
+            entry("backups.keep", &self.backups.keep),
+        ]
+    }
+}
@@ -1,15 +1,79 @@
+use axum::http::StatusCode;
 use diesel::{RunQueryDsl, SqliteConnection, r2d2};
+use std::fmt;
 use std::path::PathBuf;
 
 pub type DbPool = r2d2::Pool<r2d2::ConnectionManager<SqliteConnection>>;
 
+/// Failure to get a connection for, or to finish, a [`run`] call
 #[derive(Debug)]
-struct EnableForeignKeys;
+pub enum RunError {
+    Pool(r2d2::PoolError),
+    Join(tokio::task::JoinError),
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunError::Pool(e) => write!(f, "Failed to get a database connection: {}", e),
+            RunError::Join(e) => write!(f, "Database task failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RunError {}
+
+impl From<RunError> for (StatusCode, String) {
+    fn from(e: RunError) -> Self {
+        crate::utils::internal_error(e)
+    }
+}
+
+impl From<RunError> for String {
+    fn from(e: RunError) -> Self {
+        e.to_string()
+    }
+}
+
+/// Runs `f` with a connection of the pool on Tokio's blocking thread pool.
+///
+/// Diesel queries, and waiting for a free connection, block the calling thread. Async code must
+/// query through this so the runtime's worker threads keep serving other requests.
+pub async fn run<T, E, F>(pool: &DbPool, f: F) -> Result<T, E>
+where
+    F: FnOnce(&mut SqliteConnection) -> Result<T, E> + Send + 'static,
+    T: Send + 'static,
+    E: From<RunError> + Send + 'static,
+{
+    let pool = pool.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get().map_err(RunError::Pool)?;
+
+        f(&mut conn)
+    })
+    .await
+    .map_err(RunError::Join)?
+}
 
-impl r2d2::CustomizeConnection<SqliteConnection, r2d2::Error> for EnableForeignKeys {
+/// Connections wait this long for a lock held by another connection before failing with
+/// `database is locked`
+const BUSY_TIMEOUT_MILLISECONDS: u32 = 5000;
+
+#[derive(Debug)]
+struct ConnectionOptions;
+
+impl r2d2::CustomizeConnection<SqliteConnection, r2d2::Error> for ConnectionOptions {
     fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), r2d2::Error> {
         diesel::sql_query("PRAGMA foreign_keys = ON")
             .execute(conn)
+            .and_then(|_| {
+                diesel::sql_query(format!(
+                    "PRAGMA busy_timeout = {}",
+                    BUSY_TIMEOUT_MILLISECONDS
+                ))
+                .execute(conn)
+            })
             .map(|_| ())
             .map_err(diesel::r2d2::Error::QueryError)
     }
@@ -22,7 +86,7 @@ pub fn create_connection_pool(data_path: PathBuf) -> DbPool {
     // when building a connection pool
     let pool = r2d2::Pool::builder()
         .test_on_check_out(true)
-        .connection_customizer(Box::new(EnableForeignKeys))
+        .connection_customizer(Box::new(ConnectionOptions))
         .build(manager)
         .expect("Could not build connection pool");
 
@@ -32,5 +96,10 @@ pub fn create_connection_pool(data_path: PathBuf) -> DbPool {
         .execute(&mut conn)
         .expect("Failed to enable foreign_keys");
 
+    // Readers no longer block the writer, queries of different requests run at the same time
+    diesel::sql_query("PRAGMA journal_mode = WAL")
+        .execute(&mut conn)
+        .expect("Failed to enable the write-ahead log");
+
     pool
 }
@@ -1,2 +1,4 @@
 pub mod connection;
 pub mod models;
+#[cfg(test)]
+pub mod testing;
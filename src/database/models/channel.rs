@@ -6,10 +6,20 @@ pub struct NewChannelParams {
     pub url: String,
     pub is_subscribed: bool,
     pub subscribers_count: i64,
+    pub avatar_url: Option<String>,
 }
 
 #[derive(
-    Queryable, Identifiable, Insertable, Serialize, Debug, Clone, utoipa::ToSchema, Deserialize, TS,
+    Queryable,
+    Identifiable,
+    Insertable,
+    Serialize,
+    Debug,
+    Clone,
+    PartialEq,
+    utoipa::ToSchema,
+    Deserialize,
+    TS,
 )]
 #[diesel(table_name = schema::channels)]
 #[diesel(check_for_backend(Sqlite))]
@@ -22,6 +32,7 @@ pub struct Channel {
     pub subscribers_count: i64,
     #[ts(type = "number")]
     pub added_at: i64,
+    pub avatar_url: Option<String>,
 }
 
 impl Channel {
@@ -38,6 +49,7 @@ impl Channel {
             is_subscribed: p.is_subscribed,
             subscribers_count: p.subscribers_count,
             added_at: added_at.as_secs() as i64,
+            avatar_url: p.avatar_url,
         }
     }
 }
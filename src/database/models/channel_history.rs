@@ -0,0 +1,112 @@
+use super::{Channel, prelude::*};
+
+/// Stored in `channel_events.kind`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChannelEventKind {
+    Renamed,
+    Subscribed,
+    Unsubscribed,
+}
+
+impl ChannelEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Renamed => "renamed",
+            Self::Subscribed => "subscribed",
+            Self::Unsubscribed => "unsubscribed",
+        }
+    }
+}
+
+#[derive(
+    Queryable,
+    Identifiable,
+    Associations,
+    Insertable,
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    utoipa::ToSchema,
+    TS,
+)]
+#[diesel(table_name = schema::channel_snapshots)]
+#[diesel(belongs_to(Channel, foreign_key = channel_id))]
+#[diesel(check_for_backend(Sqlite))]
+pub struct ChannelSnapshot {
+    pub id: String,
+    #[serde(skip)]
+    pub channel_id: String,
+    pub name: String,
+    pub is_subscribed: bool,
+    #[ts(type = "number")]
+    pub subscribers_count: i64,
+    #[ts(type = "number")]
+    pub added_at: i64,
+}
+
+impl ChannelSnapshot {
+    pub fn new(channel: &Channel) -> Self {
+        let Ok(added_at) = time::SystemTime::now().duration_since(time::UNIX_EPOCH) else {
+            tracing::error!("Failed to get current time");
+            std::process::exit(1);
+        };
+
+        Self {
+            id: nanoid!(),
+            channel_id: channel.id.clone(),
+            name: channel.name.clone(),
+            is_subscribed: channel.is_subscribed,
+            subscribers_count: channel.subscribers_count,
+            added_at: added_at.as_secs() as i64,
+        }
+    }
+}
+
+#[derive(
+    Queryable,
+    Identifiable,
+    Associations,
+    Insertable,
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    utoipa::ToSchema,
+    TS,
+)]
+#[diesel(table_name = schema::channel_events)]
+#[diesel(belongs_to(Channel, foreign_key = channel_id))]
+#[diesel(check_for_backend(Sqlite))]
+pub struct ChannelEvent {
+    pub id: String,
+    pub channel_id: String,
+    pub kind: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    #[ts(type = "number")]
+    pub added_at: i64,
+}
+
+impl ChannelEvent {
+    pub fn new(
+        channel_id: String,
+        kind: ChannelEventKind,
+        old_value: Option<String>,
+        new_value: Option<String>,
+    ) -> Self {
+        let Ok(added_at) = time::SystemTime::now().duration_since(time::UNIX_EPOCH) else {
+            tracing::error!("Failed to get current time");
+            std::process::exit(1);
+        };
+
+        Self {
+            id: nanoid!(),
+            channel_id,
+            kind: kind.as_str().to_string(),
+            old_value,
+            new_value,
+            added_at: added_at.as_secs() as i64,
+        }
+    }
+}
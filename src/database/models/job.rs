@@ -0,0 +1,89 @@
+use super::prelude::*;
+
+/// Stored in `jobs.kind`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobKind {
+    ChannelAvatar,
+    VideoThumbnail,
+}
+
+impl JobKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ChannelAvatar => "channel_avatar",
+            Self::VideoThumbnail => "video_thumbnail",
+        }
+    }
+
+    pub fn from_str(kind: &str) -> Option<Self> {
+        match kind {
+            "channel_avatar" => Some(Self::ChannelAvatar),
+            "video_thumbnail" => Some(Self::VideoThumbnail),
+            _ => None,
+        }
+    }
+}
+
+/// Stored in `jobs.status`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobStatus {
+    Pending,
+    Running,
+    Done,
+    Failed,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Running => "running",
+            Self::Done => "done",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+#[derive(
+    Queryable, Identifiable, Insertable, Serialize, Deserialize, Debug, Clone, utoipa::ToSchema, TS,
+)]
+#[diesel(table_name = schema::jobs)]
+#[diesel(check_for_backend(Sqlite))]
+pub struct Job {
+    pub id: String,
+    pub kind: String,
+    pub target_id: String,
+    pub url: String,
+    pub status: String,
+    #[ts(type = "number")]
+    pub attempts: i64,
+    pub last_error: Option<String>,
+    #[ts(type = "number")]
+    pub run_at: i64,
+    #[ts(type = "number")]
+    pub updated_at: i64,
+    #[ts(type = "number")]
+    pub added_at: i64,
+}
+
+impl Job {
+    pub fn new(kind: JobKind, target_id: String, url: String) -> Self {
+        let Ok(added_at) = time::SystemTime::now().duration_since(time::UNIX_EPOCH) else {
+            tracing::error!("Failed to get current time");
+            std::process::exit(1);
+        };
+
+        Self {
+            id: nanoid!(),
+            kind: kind.as_str().to_string(),
+            target_id,
+            url,
+            status: JobStatus::Pending.as_str().to_string(),
+            attempts: 0,
+            last_error: None,
+            run_at: added_at.as_secs() as i64,
+            updated_at: added_at.as_secs() as i64,
+            added_at: added_at.as_secs() as i64,
+        }
+    }
+}
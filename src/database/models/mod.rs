@@ -1,12 +1,24 @@
 mod channel;
+mod channel_history;
+mod job;
 mod tag;
 mod video;
+mod video_metric_snapshot;
+mod video_revision;
 mod watch_history;
+mod watch_segment;
+mod watch_session;
 
 pub use channel::*;
+pub use channel_history::*;
+pub use job::*;
 pub use tag::*;
 pub use video::*;
+pub use video_metric_snapshot::*;
+pub use video_revision::*;
 pub use watch_history::*;
+pub use watch_segment::*;
+pub use watch_session::*;
 
 pub mod prelude {
     pub use crate::schema;
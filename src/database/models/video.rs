@@ -10,6 +10,7 @@ pub struct NewVideoParams {
     pub view_count: i64,
     pub comments_count: i64,
     pub published_at: i64,
+    pub thumbnail_url: Option<String>,
 }
 
 #[derive(
@@ -20,6 +21,7 @@ pub struct NewVideoParams {
     Serialize,
     Debug,
     Clone,
+    PartialEq,
     utoipa::ToSchema,
     Deserialize,
     TS,
@@ -48,6 +50,7 @@ pub struct Video {
     pub published_at: i64,
     #[ts(type = "number")]
     pub added_at: i64,
+    pub thumbnail_url: Option<String>,
 }
 
 impl Video {
@@ -72,6 +75,7 @@ impl Video {
             comments_count: p.comments_count,
             published_at: p.published_at,
             added_at: added_at.as_secs() as i64,
+            thumbnail_url: p.thumbnail_url,
         }
     }
 }
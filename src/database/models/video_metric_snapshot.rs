@@ -0,0 +1,48 @@
+use super::{Video, prelude::*};
+
+#[derive(
+    Queryable,
+    Identifiable,
+    Associations,
+    Insertable,
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    utoipa::ToSchema,
+    TS,
+)]
+#[diesel(table_name = schema::video_metric_snapshots)]
+#[diesel(belongs_to(Video, foreign_key = video_id))]
+#[diesel(check_for_backend(Sqlite))]
+pub struct VideoMetricSnapshot {
+    pub id: String,
+    #[serde(skip)]
+    pub video_id: String,
+    #[ts(type = "number")]
+    pub view_count: i64,
+    #[ts(type = "number")]
+    pub likes_count: i64,
+    #[ts(type = "number")]
+    pub comments_count: i64,
+    #[ts(type = "number")]
+    pub added_at: i64,
+}
+
+impl VideoMetricSnapshot {
+    pub fn new(video_id: String, view_count: i64, likes_count: i64, comments_count: i64) -> Self {
+        let Ok(added_at) = time::SystemTime::now().duration_since(time::UNIX_EPOCH) else {
+            tracing::error!("Failed to get current time");
+            std::process::exit(1);
+        };
+
+        Self {
+            id: nanoid!(),
+            video_id,
+            view_count,
+            likes_count,
+            comments_count,
+            added_at: added_at.as_secs() as i64,
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use super::{Video, prelude::*};
+
+#[derive(
+    Queryable,
+    Identifiable,
+    Associations,
+    Insertable,
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    utoipa::ToSchema,
+    TS,
+)]
+#[diesel(table_name = schema::video_revisions)]
+#[diesel(belongs_to(Video, foreign_key = video_id))]
+#[diesel(check_for_backend(Sqlite))]
+pub struct VideoRevision {
+    pub id: String,
+    #[serde(skip)]
+    pub video_id: String,
+    pub title: String,
+    #[serde(skip)]
+    pub thumbnail_file: Option<String>,
+    #[ts(type = "number")]
+    pub added_at: i64,
+}
+
+impl VideoRevision {
+    pub fn new(video_id: String, title: String, thumbnail_file: Option<String>) -> Self {
+        let Ok(added_at) = time::SystemTime::now().duration_since(time::UNIX_EPOCH) else {
+            tracing::error!("Failed to get current time");
+            std::process::exit(1);
+        };
+
+        Self {
+            id: nanoid!(),
+            video_id,
+            title,
+            thumbnail_file,
+            added_at: added_at.as_secs() as i64,
+        }
+    }
+}
@@ -1,5 +1,27 @@
 use super::{Channel, Video, prelude::*};
 
+/// Stored in `watch_history.duration_source`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WatchDurationSource {
+    /// Measured by the browser extension
+    Tracked,
+    /// Derived by an importer, e.g. from the playback position or the time until the next watched
+    /// video
+    Estimated,
+    /// Imported without any duration
+    Unknown,
+}
+
+impl WatchDurationSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Tracked => "tracked",
+            Self::Estimated => "estimated",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
 #[derive(
     Queryable,
     Identifiable,
@@ -30,6 +52,8 @@ pub struct WatchHistory {
     pub session_end_date: i64,
     #[ts(type = "number")]
     pub added_at: i64,
+    pub session_key: Option<String>,
+    pub duration_source: String,
 }
 
 impl WatchHistory {
@@ -39,6 +63,7 @@ impl WatchHistory {
         watch_duration_seconds: i64,
         session_start_date: i64,
         session_end_date: i64,
+        session_key: Option<String>,
     ) -> Self {
         let Ok(added_at) = time::SystemTime::now().duration_since(time::UNIX_EPOCH) else {
             tracing::error!("Failed to get current time");
@@ -53,6 +78,8 @@ impl WatchHistory {
             session_start_date,
             session_end_date,
             added_at: added_at.as_secs() as i64,
+            session_key,
+            duration_source: WatchDurationSource::Tracked.as_str().to_string(),
         }
     }
 }
@@ -0,0 +1,54 @@
+use super::{WatchHistory, prelude::*};
+
+#[derive(
+    Queryable,
+    Identifiable,
+    Associations,
+    Insertable,
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    utoipa::ToSchema,
+    TS,
+)]
+#[diesel(table_name = schema::watch_segments)]
+#[diesel(belongs_to(WatchHistory, foreign_key = watch_history_id))]
+#[diesel(check_for_backend(Sqlite))]
+pub struct WatchSegment {
+    pub id: String,
+    #[serde(skip)]
+    pub watch_history_id: String,
+    #[ts(type = "number")]
+    pub segment_index: i64,
+    #[ts(type = "number")]
+    pub start_seconds: i64,
+    /// Exclusive, the segment covers the seconds before it
+    #[ts(type = "number")]
+    pub end_seconds: i64,
+    #[ts(type = "number")]
+    pub added_at: i64,
+}
+
+impl WatchSegment {
+    pub fn new(
+        watch_history_id: String,
+        segment_index: i64,
+        start_seconds: i64,
+        end_seconds: i64,
+    ) -> Self {
+        let Ok(added_at) = time::SystemTime::now().duration_since(time::UNIX_EPOCH) else {
+            tracing::error!("Failed to get current time");
+            std::process::exit(1);
+        };
+
+        Self {
+            id: nanoid!(),
+            watch_history_id,
+            segment_index,
+            start_seconds,
+            end_seconds,
+            added_at: added_at.as_secs() as i64,
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! In-memory databases for unit tests.
+
+use crate::database::models;
+use crate::ingest::{self, ChannelUpsert, VideoUpsert};
+use diesel::connection::{Instrumentation, InstrumentationEvent};
+use diesel::prelude::*;
+use diesel_migrations::MigrationHarness;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Empty database with every migration applied
+pub fn connection() -> SqliteConnection {
+    let mut conn = SqliteConnection::establish(":memory:").expect("Failed to open the database");
+
+    diesel::sql_query("PRAGMA foreign_keys = ON")
+        .execute(&mut conn)
+        .expect("Failed to enable foreign_keys");
+    conn.run_pending_migrations(crate::MIGRATIONS)
+        .expect("Failed to run the migrations");
+
+    conn
+}
+
+/// Saves `count` channels with one tagged and watched video each
+pub fn seed(conn: &mut SqliteConnection, count: usize) {
+    for i in 0..count {
+        let channel_id = format!("channel-{i}");
+        let video_id = format!("video-{i}");
+
+        ingest::upsert_channel(
+            conn,
+            &ChannelUpsert {
+                id: channel_id.clone(),
+                name: format!("Channel {i}"),
+                url: None,
+                avatar_url: None,
+                is_subscribed: Some(i % 2 == 0),
+                subscribers_count: Some(i as i64),
+                added_at: None,
+                fill_only: false,
+            },
+        )
+        .expect("Failed to save the channel");
+
+        ingest::upsert_video(
+            conn,
+            &VideoUpsert {
+                id: video_id.clone(),
+                channel_id: channel_id.clone(),
+                title: format!("Video {i}"),
+                description: None,
+                thumbnail_url: None,
+                tags: vec![format!("tag-{}", i % 5), "shared".to_string()],
+                duration_seconds: Some(60 + i as i64),
+                published_at: Some(i as i64),
+                likes_count: None,
+                view_count: None,
+                comments_count: None,
+                refresh_thumbnail: false,
+                added_at: None,
+                fill_only: false,
+            },
+        )
+        .expect("Failed to save the video");
+
+        ingest::save_watch_session(
+            conn,
+            models::WatchHistory::new(
+                video_id,
+                channel_id,
+                30,
+                1000 + i as i64,
+                1030 + i as i64,
+                None,
+            ),
+        )
+        .expect("Failed to save the watch");
+    }
+}
+
+/// Number of statements `f` runs on `conn`
+pub fn count_queries<T>(
+    conn: &mut SqliteConnection,
+    f: impl FnOnce(&mut SqliteConnection) -> T,
+) -> (T, usize) {
+    let count = Arc::new(AtomicUsize::new(0));
+    let counter = count.clone();
+
+    conn.set_instrumentation(move |event: InstrumentationEvent<'_>| {
+        if let InstrumentationEvent::StartQuery { .. } = event {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+    });
+
+    let result = f(conn);
+
+    conn.set_instrumentation(None::<Box<dyn Instrumentation>>);
+
+    (result, count.load(Ordering::Relaxed))
+}
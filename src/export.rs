@@ -0,0 +1,526 @@
+//! Exports channels, videos, tags and watch history as JSON, NDJSON or CSV, see [`write`].
+//!
+//! Records use the same shapes as the API responses, the video of a watch history record embeds
+//! the channel of the record like `/api/watch_history` does.
+
+use crate::api_prelude::*;
+use crate::routes::api::WatchHistoryFilters;
+use diesel::prelude::*;
+use std::io::{self, Write};
+use zip::write::{SimpleFileOptions, StreamWriter};
+
+/// Rows loaded per query so a large library is never held in memory at once
+const PAGE_SIZE: i64 = 1000;
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, utoipa::ToSchema, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// A single JSON document with a list per table
+    #[default]
+    Json,
+    /// One record per line
+    Ndjson,
+    /// A zip archive with one CSV file per table
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Ndjson => "application/x-ndjson",
+            Self::Csv => "application/zip",
+        }
+    }
+
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            Self::Json => "chianti-export.json",
+            Self::Ndjson => "chianti-export.ndjson",
+            Self::Csv => "chianti-export.zip",
+        }
+    }
+}
+
+/// The JSON export, read back by the `chianti` importer
+#[derive(Serialize, Deserialize)]
+pub struct ExportDocument {
+    pub channels: Vec<ChannelResponse>,
+    pub videos: Vec<VideoResponse>,
+    pub tags: Vec<models::Tag>,
+    pub watch_history: Vec<WatchHistoryResponse>,
+}
+
+/// A line of the NDJSON export, e.g. `{"channel": {...}}`
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportRecord {
+    Channel(ChannelResponse),
+    Video(VideoResponse),
+    Tag(models::Tag),
+    WatchHistory(WatchHistoryResponse),
+}
+
+#[derive(Clone, Copy)]
+enum Table {
+    Channels,
+    Videos,
+    Tags,
+    WatchHistory,
+}
+
+impl Table {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Channels => "channels",
+            Self::Videos => "videos",
+            Self::Tags => "tags",
+            Self::WatchHistory => "watch_history",
+        }
+    }
+
+    fn csv_header(&self) -> &'static [&'static str] {
+        match self {
+            Self::Channels => &[
+                "id",
+                "name",
+                "url",
+                "is_subscribed",
+                "subscribers_count",
+                "added_at",
+                "avatar_url",
+            ],
+            Self::Videos => &[
+                "id",
+                "channel_id",
+                "url",
+                "title",
+                "description",
+                "watch_counter",
+                "duration_seconds",
+                "likes_count",
+                "view_count",
+                "comments_count",
+                "published_at",
+                "added_at",
+                "thumbnail_url",
+                "tags",
+            ],
+            Self::Tags => &["id", "name", "added_at"],
+            Self::WatchHistory => &[
+                "id",
+                "video_id",
+                "channel_id",
+                "watch_duration_seconds",
+                "session_start_date",
+                "session_end_date",
+                "added_at",
+                "session_key",
+                "duration_source",
+            ],
+        }
+    }
+}
+
+enum Writer<W: Write> {
+    Json { out: W, records: usize },
+    Ndjson(W),
+    Csv(Box<zip::ZipWriter<StreamWriter<W>>>),
+}
+
+impl<W: Write> Writer<W> {
+    fn new(format: ExportFormat, out: W) -> Self {
+        match format {
+            ExportFormat::Json => Self::Json { out, records: 0 },
+            ExportFormat::Ndjson => Self::Ndjson(out),
+            ExportFormat::Csv => Self::Csv(Box::new(zip::ZipWriter::new_stream(out))),
+        }
+    }
+
+    fn begin(&mut self, table: Table) -> io::Result<()> {
+        match self {
+            Self::Json { out, records } => {
+                let open = match table {
+                    Table::Channels => "{",
+                    _ => "],",
+                };
+
+                *records = 0;
+                write!(out, "{}\"{}\":[", open, table.name())
+            }
+            Self::Ndjson(_) => Ok(()),
+            Self::Csv(zip) => {
+                zip.start_file(
+                    format!("{}.csv", table.name()),
+                    SimpleFileOptions::default(),
+                )?;
+                write_csv_row(zip.as_mut(), table.csv_header().iter().copied())
+            }
+        }
+    }
+
+    fn record(&mut self, record: &ExportRecord) -> io::Result<()> {
+        match self {
+            Self::Json { out, records } => {
+                if *records > 0 {
+                    out.write_all(b",")?;
+                }
+
+                *records += 1;
+
+                match record {
+                    ExportRecord::Channel(channel) => serde_json::to_writer(&mut *out, channel),
+                    ExportRecord::Video(video) => serde_json::to_writer(&mut *out, video),
+                    ExportRecord::Tag(tag) => serde_json::to_writer(&mut *out, tag),
+                    ExportRecord::WatchHistory(watch_history) => {
+                        serde_json::to_writer(&mut *out, watch_history)
+                    }
+                }?;
+
+                Ok(())
+            }
+            Self::Ndjson(out) => {
+                serde_json::to_writer(&mut *out, record)?;
+                out.write_all(b"\n")
+            }
+            Self::Csv(zip) => {
+                write_csv_row(zip.as_mut(), csv_values(record).iter().map(String::as_str))
+            }
+        }
+    }
+
+    fn finish(self) -> io::Result<()> {
+        let mut out = match self {
+            Self::Json { mut out, .. } => {
+                out.write_all(b"]}")?;
+                out
+            }
+            Self::Ndjson(out) => out,
+            Self::Csv(zip) => zip.finish()?.into_inner(),
+        };
+
+        out.flush()
+    }
+}
+
+fn csv_values(record: &ExportRecord) -> Vec<String> {
+    match record {
+        ExportRecord::Channel(ChannelResponse { channel, .. }) => vec![
+            channel.id.clone(),
+            channel.name.clone(),
+            channel.url.clone(),
+            channel.is_subscribed.to_string(),
+            channel.subscribers_count.to_string(),
+            channel.added_at.to_string(),
+            channel.avatar_url.clone().unwrap_or_default(),
+        ],
+        ExportRecord::Video(VideoResponse { video, tags, .. }) => vec![
+            video.id.clone(),
+            video.channel_id.clone(),
+            video.url.clone(),
+            video.title.clone(),
+            video.description.clone(),
+            video.watch_counter.to_string(),
+            video.duration_seconds.to_string(),
+            video.likes_count.to_string(),
+            video.view_count.to_string(),
+            video.comments_count.to_string(),
+            video.published_at.to_string(),
+            video.added_at.to_string(),
+            video.thumbnail_url.clone().unwrap_or_default(),
+            tags.join("|"),
+        ],
+        ExportRecord::Tag(tag) => vec![tag.id.clone(), tag.name.clone(), tag.added_at.to_string()],
+        ExportRecord::WatchHistory(WatchHistoryResponse { watch_history, .. }) => vec![
+            watch_history.id.clone(),
+            watch_history.video_id.clone(),
+            watch_history.channel_id.clone(),
+            watch_history.watch_duration_seconds.to_string(),
+            watch_history.session_start_date.to_string(),
+            watch_history.session_end_date.to_string(),
+            watch_history.added_at.to_string(),
+            watch_history.session_key.clone().unwrap_or_default(),
+            watch_history.duration_source.clone(),
+        ],
+    }
+}
+
+/// Writes a RFC 4180 row, values with separators, quotes or line breaks are quoted
+fn write_csv_row<'a>(
+    out: &mut impl Write,
+    values: impl Iterator<Item = &'a str>,
+) -> io::Result<()> {
+    for (i, value) in values.enumerate() {
+        if i > 0 {
+            out.write_all(b",")?;
+        }
+
+        if value.contains([',', '"', '\n', '\r']) {
+            write!(out, "\"{}\"", value.replace('"', "\"\""))?;
+        } else {
+            out.write_all(value.as_bytes())?;
+        }
+    }
+
+    out.write_all(b"\r\n")
+}
+
+/// Writes an export of the records matching `filters` to `out`.
+///
+/// Without filters everything is exported, otherwise the channels, videos and tags of the
+/// matching watch history records. All tables are read in one transaction, so the export is a
+/// single snapshot of the database even while the server keeps saving records.
+pub fn write(
+    conn: &mut SqliteConnection,
+    format: ExportFormat,
+    filters: &WatchHistoryFilters,
+    out: impl Write,
+) -> Result<(), String> {
+    conn.transaction(|conn| Ok(write_snapshot(conn, format, filters, out)))
+        .map_err(|e: diesel::result::Error| format!("Failed to read the database: {}", e))?
+}
+
+fn write_snapshot(
+    conn: &mut SqliteConnection,
+    format: ExportFormat,
+    filters: &WatchHistoryFilters,
+    out: impl Write,
+) -> Result<(), String> {
+    let mut writer = Writer::new(format, out);
+    let io_error = |e: io::Error| format!("Failed to write export: {}", e);
+
+    writer.begin(Table::Channels).map_err(io_error)?;
+    paged(
+        |after| load_channels(conn, filters, after),
+        |channel| writer.record(&ExportRecord::Channel(ChannelResponse::new(channel))),
+    )?;
+
+    writer.begin(Table::Videos).map_err(io_error)?;
+    paged(
+        |after| {
+            let videos = load_videos(conn, filters, after)?;
+            let mut tags = load_video_tags(conn, videos.iter().map(|(_, (video, _))| &video.id))?;
+
+            Ok(videos
+                .into_iter()
+                .map(|(key, (video, channel))| {
+                    let tags = tags.remove(&video.id).unwrap_or_default();
+                    let video =
+                        VideoResponse::new(video, tags, Some(ChannelResponse::new(channel)));
+
+                    (key, video)
+                })
+                .collect())
+        },
+        |video| writer.record(&ExportRecord::Video(video)),
+    )?;
+
+    writer.begin(Table::Tags).map_err(io_error)?;
+    paged(
+        |after| load_tags(conn, filters, after),
+        |tag| writer.record(&ExportRecord::Tag(tag)),
+    )?;
+
+    writer.begin(Table::WatchHistory).map_err(io_error)?;
+    paged(
+        |after| {
+            let watch_history = load_watch_history(conn, filters, after)?;
+            let tags = load_video_tags(
+                conn,
+                watch_history.iter().map(|(_, (_, _, video))| &video.id),
+            )?;
+
+            Ok(watch_history
+                .into_iter()
+                .map(|(key, (watch_history, channel, video))| {
+                    let tags = tags.get(&video.id).cloned().unwrap_or_default();
+                    let channel = ChannelResponse::new(channel);
+                    let watch_history = WatchHistoryResponse::new(
+                        watch_history,
+                        VideoResponse::new(video, tags, Some(channel)),
+                    );
+
+                    (key, watch_history)
+                })
+                .collect::<Vec<_>>())
+        },
+        |watch_history| writer.record(&ExportRecord::WatchHistory(watch_history)),
+    )?;
+
+    writer.finish().map_err(io_error)
+}
+
+/// Calls `f` for every row of the pages returned by `load`.
+///
+/// Rows come with the sort key they are ordered by, `load` gets the key of the last row of the
+/// previous page and returns the rows after it.
+fn paged<K, T>(
+    mut load: impl FnMut(Option<K>) -> QueryResult<Vec<(K, T)>>,
+    mut f: impl FnMut(T) -> io::Result<()>,
+) -> Result<(), String> {
+    let mut after = None;
+
+    loop {
+        let page =
+            load(after.take()).map_err(|e| format!("Failed to load export records: {}", e))?;
+        let len = page.len() as i64;
+
+        for (key, row) in page {
+            after = Some(key);
+            f(row).map_err(|e| format!("Failed to write export: {}", e))?;
+        }
+
+        if len < PAGE_SIZE {
+            return Ok(());
+        }
+    }
+}
+
+fn load_channels(
+    conn: &mut SqliteConnection,
+    filters: &WatchHistoryFilters,
+    after: Option<i64>,
+) -> QueryResult<Vec<(i64, models::Channel)>> {
+    use schema::channels::dsl as channels_dsl;
+
+    let mut query = channels_dsl::channels
+        .select((rowid("channels"), schema::channels::all_columns))
+        .order(rowid("channels"))
+        .limit(PAGE_SIZE)
+        .into_boxed();
+
+    if let Some(after) = after {
+        query = query.filter(rowid("channels").gt(after));
+    }
+
+    if !filters.is_empty() {
+        query = query.filter(channels_dsl::id.eq_any(filter_watch_history!(
+            watch_history_dsl::watch_history
+                .select(watch_history_dsl::channel_id)
+                .into_boxed(),
+            filters
+        )));
+    }
+
+    query.load(conn)
+}
+
+fn load_videos(
+    conn: &mut SqliteConnection,
+    filters: &WatchHistoryFilters,
+    after: Option<i64>,
+) -> QueryResult<Vec<(i64, (models::Video, models::Channel))>> {
+    use schema::channels::dsl as channels_dsl;
+    use schema::videos::dsl as videos_dsl;
+
+    let mut query = videos_dsl::videos
+        .inner_join(channels_dsl::channels)
+        .select((
+            rowid("videos"),
+            (schema::videos::all_columns, schema::channels::all_columns),
+        ))
+        .order(rowid("videos"))
+        .limit(PAGE_SIZE)
+        .into_boxed();
+
+    if let Some(after) = after {
+        query = query.filter(rowid("videos").gt(after));
+    }
+
+    if !filters.is_empty() {
+        query = query.filter(videos_dsl::id.eq_any(filter_watch_history!(
+            watch_history_dsl::watch_history
+                .select(watch_history_dsl::video_id)
+                .into_boxed(),
+            filters
+        )));
+    }
+
+    query.load(conn)
+}
+
+fn load_tags(
+    conn: &mut SqliteConnection,
+    filters: &WatchHistoryFilters,
+    after: Option<i64>,
+) -> QueryResult<Vec<(i64, models::Tag)>> {
+    use schema::tags::dsl as tags_dsl;
+    use schema::video_tags::dsl as video_tags_dsl;
+
+    let mut query = tags_dsl::tags
+        .select((rowid("tags"), schema::tags::all_columns))
+        .order(rowid("tags"))
+        .limit(PAGE_SIZE)
+        .into_boxed();
+
+    if let Some(after) = after {
+        query = query.filter(rowid("tags").gt(after));
+    }
+
+    if !filters.is_empty() {
+        query = query.filter(
+            tags_dsl::id.eq_any(
+                video_tags_dsl::video_tags
+                    .select(video_tags_dsl::tag_id)
+                    .filter(video_tags_dsl::video_id.eq_any(filter_watch_history!(
+                        watch_history_dsl::watch_history
+                            .select(watch_history_dsl::video_id)
+                            .into_boxed(),
+                        filters
+                    ))),
+            ),
+        );
+    }
+
+    query.load(conn)
+}
+
+/// A watch history record with its channel and video, after its `(session_start_date, rowid)`
+/// sort key
+type WatchHistoryRow = (
+    (i64, i64),
+    (models::WatchHistory, models::Channel, models::Video),
+);
+
+fn load_watch_history(
+    conn: &mut SqliteConnection,
+    filters: &WatchHistoryFilters,
+    after: Option<(i64, i64)>,
+) -> QueryResult<Vec<WatchHistoryRow>> {
+    use schema::channels::dsl as channels_dsl;
+    use schema::videos::dsl as videos_dsl;
+    use schema::watch_history::dsl as watch_history_dsl;
+
+    let mut query = watch_history_dsl::watch_history
+        .inner_join(channels_dsl::channels)
+        .inner_join(videos_dsl::videos)
+        .select((
+            (
+                watch_history_dsl::session_start_date,
+                rowid("watch_history"),
+            ),
+            (
+                schema::watch_history::all_columns,
+                schema::channels::all_columns,
+                schema::videos::all_columns,
+            ),
+        ))
+        .order((
+            watch_history_dsl::session_start_date.asc(),
+            rowid("watch_history").asc(),
+        ))
+        .limit(PAGE_SIZE)
+        .into_boxed();
+
+    if let Some((session_start_date, watch_history_rowid)) = after {
+        query = query.filter(
+            watch_history_dsl::session_start_date
+                .gt(session_start_date)
+                .or(watch_history_dsl::session_start_date
+                    .eq(session_start_date)
+                    .and(rowid("watch_history").gt(watch_history_rowid))),
+        );
+    }
+
+    filter_watch_history!(query, filters).load(conn)
+}
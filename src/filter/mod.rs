@@ -0,0 +1,506 @@
+//! The `filter` parameter of `/api/videos`, `/api/channels` and `/api/watch_history`.
+//!
+//! A filter is a boolean expression of comparisons, for example
+//! `duration_seconds>600 and (channel.is_subscribed=true or tag:music)`.
+//!
+//! - `field op value` compares a field, `op` is one of `=`, `!=`, `>`, `>=`, `<`, `<=` or `~`
+//!   (text contains the value)
+//! - `tag:name` matches videos that have the tag, quote names with spaces: `tag:"lo fi"`
+//! - `and`, `or`, `not` and parentheses combine them, `and` binds tighter than `or`
+//! - values are integers, `true`, `false`, `"quoted text"` or a single word of text
+//!
+//! The expression is parsed by [`parser`] and compiled into a Diesel condition that only allows the
+//! fields of the listed resource, see [`videos`], [`channels`] and [`watch_history`]. Errors point
+//! at the token that caused them.
+
+mod parser;
+
+use crate::schema::{channels, tags, video_tags, videos, watch_history};
+use axum::http::StatusCode;
+use diesel::helper_types::InnerJoinQuerySource;
+use diesel::prelude::*;
+use diesel::sql_types::Bool;
+use diesel::sqlite::Sqlite;
+use parser::{Comparison, Expr, Op, Value};
+use std::ops::Range;
+
+pub type Condition<QS> = Box<dyn BoxableExpression<QS, Sqlite, SqlType = Bool>>;
+
+pub type VideosSource = InnerJoinQuerySource<videos::table, channels::table>;
+pub type WatchHistorySource = InnerJoinQuerySource<
+    InnerJoinQuerySource<watch_history::table, channels::table>,
+    videos::table,
+>;
+
+const VIDEO_FIELDS: [&str; 10] = [
+    "id",
+    "title",
+    "description",
+    "watch_counter",
+    "duration_seconds",
+    "likes_count",
+    "view_count",
+    "comments_count",
+    "published_at",
+    "added_at",
+];
+
+const CHANNEL_FIELDS: [&str; 5] = [
+    "id",
+    "name",
+    "is_subscribed",
+    "subscribers_count",
+    "added_at",
+];
+
+const WATCH_HISTORY_FIELDS: [&str; 6] = [
+    "id",
+    "watch_duration_seconds",
+    "session_start_date",
+    "session_end_date",
+    "added_at",
+    "duration_source",
+];
+
+/// Problem with a filter and the byte range of the filter that caused it
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterError {
+    message: String,
+    span: Range<usize>,
+}
+
+impl FilterError {
+    fn new(message: impl Into<String>, span: Range<usize>) -> Self {
+        Self {
+            message: message.into(),
+            span,
+        }
+    }
+
+    /// The message followed by the filter with the offending token underlined
+    pub fn report(&self, input: &str) -> String {
+        let start = input[..self.span.start].chars().count();
+        let width = input[self.span.clone()].chars().count().max(1);
+
+        format!(
+            "Invalid filter: {}\n{}\n{}{}",
+            self.message,
+            input,
+            " ".repeat(start),
+            "^".repeat(width)
+        )
+    }
+}
+
+/// Compares `$column` with `$value`, the value must already have the type of the column
+macro_rules! ordered {
+    ($column:expr, $comparison:expr, $value:expr) => {
+        match $comparison.op.node {
+            Op::Eq => Box::new($column.eq($value)) as Condition<_>,
+            Op::Ne => Box::new($column.ne($value)),
+            Op::Gt => Box::new($column.gt($value)),
+            Op::Ge => Box::new($column.ge($value)),
+            Op::Lt => Box::new($column.lt($value)),
+            Op::Le => Box::new($column.le($value)),
+            Op::Contains => {
+                return Err(FilterError::new(
+                    "`~` only compares text fields",
+                    $comparison.op.span.clone(),
+                ));
+            }
+        }
+    };
+}
+
+/// Compares a column of the given type with the value of `$comparison`
+macro_rules! compare {
+    (integer, $column:expr, $comparison:expr) => {{
+        let value = integer($comparison)?;
+        ordered!($column, $comparison, value)
+    }};
+    (text, $column:expr, $comparison:expr) => {{
+        let value = text($comparison)?;
+
+        match $comparison.op.node {
+            Op::Contains => Box::new($column.like(contains_pattern(&value)).escape('\\')),
+            _ => ordered!($column, $comparison, value),
+        }
+    }};
+    (bool, $column:expr, $comparison:expr) => {{
+        let value = boolean($comparison)?;
+
+        match $comparison.op.node {
+            Op::Eq => Box::new($column.eq(value)) as Condition<_>,
+            Op::Ne => Box::new($column.ne(value)),
+            _ => {
+                return Err(FilterError::new(
+                    "Only `=` and `!=` compare true or false",
+                    $comparison.op.span.clone(),
+                ));
+            }
+        }
+    }};
+}
+
+/// Condition on a field of [`VIDEO_FIELDS`], `None` when `$name` is not one of them
+macro_rules! video_field {
+    ($name:expr, $comparison:expr) => {
+        match $name {
+            "id" => Some(compare!(text, videos::id, $comparison)),
+            "title" => Some(compare!(text, videos::title, $comparison)),
+            "description" => Some(compare!(text, videos::description, $comparison)),
+            "watch_counter" => Some(compare!(integer, videos::watch_counter, $comparison)),
+            "duration_seconds" => Some(compare!(integer, videos::duration_seconds, $comparison)),
+            "likes_count" => Some(compare!(integer, videos::likes_count, $comparison)),
+            "view_count" => Some(compare!(integer, videos::view_count, $comparison)),
+            "comments_count" => Some(compare!(integer, videos::comments_count, $comparison)),
+            "published_at" => Some(compare!(integer, videos::published_at, $comparison)),
+            "added_at" => Some(compare!(integer, videos::added_at, $comparison)),
+            _ => None,
+        }
+    };
+}
+
+/// Condition on a field of [`CHANNEL_FIELDS`], `None` when `$name` is not one of them
+macro_rules! channel_field {
+    ($name:expr, $comparison:expr) => {
+        match $name {
+            "id" => Some(compare!(text, channels::id, $comparison)),
+            "name" => Some(compare!(text, channels::name, $comparison)),
+            "is_subscribed" => Some(compare!(bool, channels::is_subscribed, $comparison)),
+            "subscribers_count" => {
+                Some(compare!(integer, channels::subscribers_count, $comparison))
+            }
+            "added_at" => Some(compare!(integer, channels::added_at, $comparison)),
+            _ => None,
+        }
+    };
+}
+
+/// Condition on a field of [`WATCH_HISTORY_FIELDS`], `None` when `$name` is not one of them
+macro_rules! watch_history_field {
+    ($name:expr, $comparison:expr) => {
+        match $name {
+            "id" => Some(compare!(text, watch_history::id, $comparison)),
+            "watch_duration_seconds" => Some(compare!(
+                integer,
+                watch_history::watch_duration_seconds,
+                $comparison
+            )),
+            "session_start_date" => Some(compare!(
+                integer,
+                watch_history::session_start_date,
+                $comparison
+            )),
+            "session_end_date" => Some(compare!(
+                integer,
+                watch_history::session_end_date,
+                $comparison
+            )),
+            "added_at" => Some(compare!(integer, watch_history::added_at, $comparison)),
+            "duration_source" => Some(compare!(text, watch_history::duration_source, $comparison)),
+            _ => None,
+        }
+    };
+}
+
+fn integer(comparison: &Comparison) -> Result<i64, FilterError> {
+    match &comparison.value.node {
+        Value::Integer(integer, _) => Ok(*integer),
+        _ => Err(FilterError::new(
+            format!("`{}` compares integers", comparison.field.node),
+            comparison.value.span.clone(),
+        )),
+    }
+}
+
+fn text(comparison: &Comparison) -> Result<String, FilterError> {
+    match &comparison.value.node {
+        Value::Text(text) => Ok(text.clone()),
+        // Ids and titles can look like numbers
+        Value::Integer(_, digits) => Ok(digits.clone()),
+        Value::Bool(_) => Err(FilterError::new(
+            format!("`{}` compares text", comparison.field.node),
+            comparison.value.span.clone(),
+        )),
+    }
+}
+
+fn boolean(comparison: &Comparison) -> Result<bool, FilterError> {
+    match &comparison.value.node {
+        Value::Bool(boolean) => Ok(*boolean),
+        _ => Err(FilterError::new(
+            format!("`{}` is true or false", comparison.field.node),
+            comparison.value.span.clone(),
+        )),
+    }
+}
+
+/// `LIKE` pattern of text that contains `value`, `\` escapes the wildcards of `value`
+fn contains_pattern(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+
+    format!("%{escaped}%")
+}
+
+/// Ids of the videos that have the tag
+fn tagged(name: String) -> video_tags::BoxedQuery<'static, Sqlite, diesel::sql_types::Text> {
+    video_tags::table
+        .filter(video_tags::tag_id.eq_any(tags::table.filter(tags::name.eq(name)).select(tags::id)))
+        .select(video_tags::video_id)
+        .into_boxed()
+}
+
+fn unknown_field(comparison: &Comparison, fields: &[String]) -> FilterError {
+    FilterError::new(
+        format!(
+            "Unknown field `{}`, the fields are {}",
+            comparison.field.node,
+            fields.join(", ")
+        ),
+        comparison.field.span.clone(),
+    )
+}
+
+/// Fields of a nested resource, `channel.` and `name` give `channel.name`
+fn prefixed<'a>(prefix: &'a str, fields: &'a [&'a str]) -> impl Iterator<Item = String> + 'a {
+    fields.iter().map(move |field| format!("{prefix}{field}"))
+}
+
+/// Builds the condition of `expr` from the conditions of its comparisons and tags
+fn compile<QS>(
+    expr: &Expr,
+    comparison: &dyn Fn(&Comparison) -> Result<Condition<QS>, FilterError>,
+    tag: &dyn Fn(String) -> Condition<QS>,
+) -> Result<Condition<QS>, FilterError>
+where
+    QS: 'static,
+    Condition<QS>: BoxableExpression<QS, Sqlite, SqlType = Bool>,
+    diesel::dsl::And<Condition<QS>, Condition<QS>>:
+        BoxableExpression<QS, Sqlite, SqlType = Bool> + 'static,
+    diesel::dsl::Or<Condition<QS>, Condition<QS>>:
+        BoxableExpression<QS, Sqlite, SqlType = Bool> + 'static,
+    diesel::dsl::not<Condition<QS>>: BoxableExpression<QS, Sqlite, SqlType = Bool> + 'static,
+{
+    Ok(match expr {
+        Expr::And(left, right) => {
+            Box::new(compile(left, comparison, tag)?.and(compile(right, comparison, tag)?))
+        }
+        Expr::Or(left, right) => {
+            Box::new(compile(left, comparison, tag)?.or(compile(right, comparison, tag)?))
+        }
+        Expr::Not(inner) => Box::new(diesel::dsl::not(compile(inner, comparison, tag)?)),
+        Expr::Compare(cmp) => comparison(cmp)?,
+        Expr::Tag(name) => tag(name.node.clone()),
+    })
+}
+
+/// Parses and compiles `input` into a `400 Bad Request` that shows the error in the filter
+fn build<QS>(
+    input: &str,
+    comparison: &dyn Fn(&Comparison) -> Result<Condition<QS>, FilterError>,
+    tag: &dyn Fn(String) -> Condition<QS>,
+) -> Result<Condition<QS>, (StatusCode, String)>
+where
+    QS: 'static,
+    Condition<QS>: BoxableExpression<QS, Sqlite, SqlType = Bool>,
+    diesel::dsl::And<Condition<QS>, Condition<QS>>:
+        BoxableExpression<QS, Sqlite, SqlType = Bool> + 'static,
+    diesel::dsl::Or<Condition<QS>, Condition<QS>>:
+        BoxableExpression<QS, Sqlite, SqlType = Bool> + 'static,
+    diesel::dsl::not<Condition<QS>>: BoxableExpression<QS, Sqlite, SqlType = Bool> + 'static,
+{
+    parser::parse(input)
+        .and_then(|expr| compile(&expr, comparison, tag))
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.report(input)))
+}
+
+/// Condition on videos and their channel, channel fields are prefixed with `channel.`
+pub fn videos(input: &str) -> Result<Condition<VideosSource>, (StatusCode, String)> {
+    build(
+        input,
+        &|comparison| {
+            let name = comparison.field.node.as_str();
+
+            let condition = match name.strip_prefix("channel.") {
+                Some(name) => channel_field!(name, comparison),
+                None => video_field!(name, comparison),
+            };
+
+            condition.ok_or_else(|| {
+                let fields = prefixed("", &VIDEO_FIELDS)
+                    .chain(prefixed("channel.", &CHANNEL_FIELDS))
+                    .collect::<Vec<String>>();
+
+                unknown_field(comparison, &fields)
+            })
+        },
+        &|name| Box::new(videos::id.eq_any(tagged(name))),
+    )
+}
+
+/// Condition on channels, a tag matches channels with at least one video that has the tag
+pub fn channels(input: &str) -> Result<Condition<channels::table>, (StatusCode, String)> {
+    build(
+        input,
+        &|comparison| {
+            channel_field!(comparison.field.node.as_str(), comparison).ok_or_else(|| {
+                unknown_field(
+                    comparison,
+                    &prefixed("", &CHANNEL_FIELDS).collect::<Vec<String>>(),
+                )
+            })
+        },
+        &|name| {
+            Box::new(
+                channels::id.eq_any(
+                    videos::table
+                        .filter(videos::id.eq_any(tagged(name)))
+                        .select(videos::channel_id),
+                ),
+            )
+        },
+    )
+}
+
+/// Condition on watch history records, their video and their channel. Video fields are prefixed
+/// with `video.` and channel fields with `channel.`.
+pub fn watch_history(input: &str) -> Result<Condition<WatchHistorySource>, (StatusCode, String)> {
+    build(
+        input,
+        &|comparison| {
+            let name = comparison.field.node.as_str();
+
+            let condition = if let Some(name) = name.strip_prefix("video.") {
+                video_field!(name, comparison)
+            } else if let Some(name) = name.strip_prefix("channel.") {
+                channel_field!(name, comparison)
+            } else {
+                watch_history_field!(name, comparison)
+            };
+
+            condition.ok_or_else(|| {
+                let fields = prefixed("", &WATCH_HISTORY_FIELDS)
+                    .chain(prefixed("video.", &VIDEO_FIELDS))
+                    .chain(prefixed("channel.", &CHANNEL_FIELDS))
+                    .collect::<Vec<String>>();
+
+                unknown_field(comparison, &fields)
+            })
+        },
+        &|name| Box::new(watch_history::video_id.eq_any(tagged(name))),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// SQL and binds of a compiled condition
+    fn sql<QS>(condition: Result<Condition<QS>, (StatusCode, String)>) -> String {
+        let condition = condition.unwrap();
+
+        diesel::debug_query::<Sqlite, _>(&condition).to_string()
+    }
+
+    /// Message of a rejected filter
+    fn error<QS>(condition: Result<Condition<QS>, (StatusCode, String)>) -> String {
+        match condition {
+            Ok(_) => panic!("Expected the filter to be rejected"),
+            Err((status, message)) => {
+                assert_eq!(status, StatusCode::BAD_REQUEST);
+                message
+            }
+        }
+    }
+
+    #[test]
+    fn report_underlines_the_token() {
+        let input = "title~\"ä\" and size>3";
+        let message = error(videos(input));
+
+        assert_eq!(
+            message,
+            format!(
+                "Invalid filter: Unknown field `size`, the fields are {}\n{}\n{}^^^^",
+                prefixed("", &VIDEO_FIELDS)
+                    .chain(prefixed("channel.", &CHANNEL_FIELDS))
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                input,
+                " ".repeat(14)
+            )
+        );
+    }
+
+    #[test]
+    fn report_marks_the_end_of_the_filter() {
+        let message = error(channels("name="));
+
+        assert!(message.ends_with("name=\n     ^"), "{}", message);
+    }
+
+    #[test]
+    fn fields_depend_on_the_resource() {
+        assert!(videos("channel.name=x and duration_seconds>1").is_ok());
+        assert!(channels("subscribers_count>1").is_ok());
+        assert!(watch_history("video.title~x and channel.is_subscribed=true").is_ok());
+
+        assert!(error(videos("watch_duration_seconds>1")).contains("Unknown field"));
+        assert!(error(videos("video.title=x")).contains("Unknown field"));
+        assert!(error(channels("channel.name=x")).contains("Unknown field"));
+        assert!(error(channels("title=x")).contains("Unknown field"));
+        assert!(error(watch_history("title=x")).contains("Unknown field"));
+    }
+
+    #[test]
+    fn values_must_match_the_field_type() {
+        assert!(error(videos("duration_seconds=long")).contains("compares integers"));
+        assert!(error(videos("title=true")).contains("compares text"));
+        assert!(error(channels("is_subscribed=1")).contains("is true or false"));
+        assert!(error(channels("is_subscribed>true")).contains("Only `=` and `!=`"));
+        assert!(error(videos("view_count~1")).contains("only compares text"));
+    }
+
+    #[test]
+    fn text_fields_keep_leading_zeros() {
+        assert!(sql(videos("id=007")).contains(r#"binds: ["007"]"#));
+        assert!(sql(videos("title~007")).contains(r#""%007%""#));
+        assert!(sql(videos("duration_seconds=007")).contains("binds: [7]"));
+        assert!(sql(videos("tag:007")).contains(r#""007""#));
+    }
+
+    #[test]
+    fn tags_filter_through_video_tags() {
+        let videos_sql = sql(videos("tag:music"));
+        let channels_sql = sql(channels("tag:music"));
+        let watch_history_sql = sql(watch_history("not tag:music"));
+
+        assert!(videos_sql.contains("`video_tags`"), "{}", videos_sql);
+        assert!(
+            channels_sql.contains("`videos`.`channel_id`"),
+            "{}",
+            channels_sql
+        );
+        assert!(
+            watch_history_sql.trim_start().starts_with("NOT"),
+            "{}",
+            watch_history_sql
+        );
+    }
+
+    #[test]
+    fn contains_escapes_wildcards() {
+        assert_eq!(contains_pattern(r"50%_\"), r"%50\%\_\\%");
+    }
+
+    #[test]
+    fn too_many_terms_are_rejected() {
+        let input = vec!["tag:a"; 65].join(" or ");
+
+        assert!(error(videos(&input)).contains("more than 64 comparisons and tags"));
+    }
+}
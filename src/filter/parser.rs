@@ -0,0 +1,530 @@
+use super::FilterError;
+use std::ops::Range;
+
+/// Parentheses and `not`s nested deeper than this are rejected instead of overflowing the stack
+const MAX_DEPTH: usize = 32;
+
+/// Filters with more comparisons and tags than this are rejected, every one of them nests the
+/// compiled condition one level deeper
+const MAX_TERMS: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    /// Text contains the value
+    Contains,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// The integer and its digits as written, text fields compare the digits so `007` keeps its
+    /// leading zeros
+    Integer(i64, String),
+    Text(String),
+    Bool(bool),
+}
+
+/// Field or value with the byte range it was parsed from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Range<usize>,
+}
+
+/// `field op value`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub field: Spanned<String>,
+    pub op: Spanned<Op>,
+    pub value: Spanned<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Comparison),
+    /// `tag:name`
+    Tag(Spanned<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// Field names, keywords and unquoted values
+    Word(String),
+    /// The integer and its digits as written
+    Integer(i64, String),
+    /// Quoted value
+    Text(String),
+    Op(Op),
+    Colon,
+    LParen,
+    RParen,
+    End,
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '.' | '-')
+}
+
+fn tokenize(input: &str) -> Result<Vec<Spanned<Token>>, FilterError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let token = match c {
+            '(' | ')' | ':' | '=' | '~' => {
+                chars.next();
+
+                match c {
+                    '(' => Token::LParen,
+                    ')' => Token::RParen,
+                    ':' => Token::Colon,
+                    '=' => Token::Op(Op::Eq),
+                    _ => Token::Op(Op::Contains),
+                }
+            }
+            '!' | '>' | '<' => {
+                chars.next();
+                let followed_by_eq = chars.next_if(|&(_, next)| next == '=').is_some();
+
+                match (c, followed_by_eq) {
+                    ('!', true) => Token::Op(Op::Ne),
+                    ('>', true) => Token::Op(Op::Ge),
+                    ('>', false) => Token::Op(Op::Gt),
+                    ('<', true) => Token::Op(Op::Le),
+                    ('<', false) => Token::Op(Op::Lt),
+                    _ => return Err(FilterError::new("Expected `!=`", start..start + 1)),
+                }
+            }
+            '"' => {
+                chars.next();
+                let mut text = String::new();
+
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, escaped)) => text.push(escaped),
+                            None => break,
+                        },
+                        Some((_, c)) => text.push(c),
+                        None => {
+                            return Err(FilterError::new(
+                                "Quoted value is never closed",
+                                start..input.len(),
+                            ));
+                        }
+                    }
+                }
+
+                Token::Text(text)
+            }
+            c if is_word_char(c) => {
+                let mut word = String::new();
+
+                while let Some((_, c)) = chars.next_if(|&(_, c)| is_word_char(c)) {
+                    word.push(c);
+                }
+
+                match word.parse::<i64>() {
+                    Ok(integer) => Token::Integer(integer, word),
+                    Err(_) => Token::Word(word),
+                }
+            }
+            c => {
+                return Err(FilterError::new(
+                    format!("Unexpected character `{}`", c),
+                    start..start + c.len_utf8(),
+                ));
+            }
+        };
+
+        let end = chars.peek().map_or(input.len(), |&(end, _)| end);
+        tokens.push(Spanned {
+            node: token,
+            span: start..end,
+        });
+    }
+
+    tokens.push(Spanned {
+        node: Token::End,
+        span: input.len()..input.len(),
+    });
+
+    Ok(tokens)
+}
+
+/// Parses a filter expression.
+///
+/// ```text
+/// expr       = and ("or" and)*
+/// and        = not ("and" not)*
+/// not        = "not" not | "(" expr ")" | "tag" ":" value | field op value
+/// op         = "=" | "!=" | ">" | ">=" | "<" | "<=" | "~"
+/// value      = integer | "quoted text" | true | false | word
+/// ```
+pub fn parse(input: &str) -> Result<Expr, FilterError> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        position: 0,
+        depth: 0,
+        terms: 0,
+    };
+
+    let expr = parser.or()?;
+    let token = parser.peek();
+
+    if token.node != Token::End {
+        let message = match token.node {
+            Token::RParen => "`)` has no matching `(`",
+            _ => "Expected `and`, `or` or the end of the filter",
+        };
+
+        return Err(FilterError::new(message, token.span.clone()));
+    }
+
+    Ok(expr)
+}
+
+struct Parser {
+    tokens: Vec<Spanned<Token>>,
+    position: usize,
+    depth: usize,
+    /// Comparisons and tags parsed so far
+    terms: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Spanned<Token> {
+        &self.tokens[self.position]
+    }
+
+    fn next(&mut self) -> Spanned<Token> {
+        let token = self.tokens[self.position].clone();
+
+        if token.node != Token::End {
+            self.position += 1;
+        }
+
+        token
+    }
+
+    fn keyword(&self, keyword: &str) -> bool {
+        matches!(&self.peek().node, Token::Word(word) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn or(&mut self) -> Result<Expr, FilterError> {
+        let mut expr = self.and()?;
+
+        while self.keyword("or") {
+            self.next();
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, FilterError> {
+        let mut expr = self.not()?;
+
+        while self.keyword("and") {
+            self.next();
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, FilterError> {
+        self.depth += 1;
+
+        if self.depth > MAX_DEPTH {
+            return Err(FilterError::new(
+                "Filter is nested too deeply",
+                self.peek().span.clone(),
+            ));
+        }
+
+        let expr = if self.keyword("not") {
+            self.next();
+            Expr::Not(Box::new(self.not()?))
+        } else {
+            self.primary()?
+        };
+
+        self.depth -= 1;
+
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr, FilterError> {
+        let token = self.next();
+
+        match token.node {
+            Token::LParen => {
+                let expr = self.or()?;
+                let close = self.next();
+
+                if close.node != Token::RParen {
+                    return Err(FilterError::new(
+                        "Expected `)` to close the `(`",
+                        token.span.start..close.span.end,
+                    ));
+                }
+
+                Ok(expr)
+            }
+            Token::Word(field) => {
+                self.terms += 1;
+
+                if self.terms > MAX_TERMS {
+                    return Err(FilterError::new(
+                        format!("Filter has more than {} comparisons and tags", MAX_TERMS),
+                        token.span,
+                    ));
+                }
+
+                let field = Spanned {
+                    node: field,
+                    span: token.span,
+                };
+
+                let op = self.next();
+
+                match op.node {
+                    Token::Colon if field.node == "tag" => {
+                        let value = self.value()?;
+
+                        let name = match value.node {
+                            Value::Text(name) => name,
+                            Value::Integer(_, digits) => digits,
+                            Value::Bool(boolean) => boolean.to_string(),
+                        };
+
+                        Ok(Expr::Tag(Spanned {
+                            node: name,
+                            span: value.span,
+                        }))
+                    }
+                    Token::Colon => Err(FilterError::new(
+                        format!("Only `tag:` takes a `:`, compare `{}` with `=`", field.node),
+                        field.span.start..op.span.end,
+                    )),
+                    Token::Op(node) => Ok(Expr::Compare(Comparison {
+                        field,
+                        op: Spanned {
+                            node,
+                            span: op.span,
+                        },
+                        value: self.value()?,
+                    })),
+                    _ => Err(FilterError::new(
+                        format!(
+                            "Expected a comparison like `=`, `>` or `~` after `{}`",
+                            field.node
+                        ),
+                        op.span,
+                    )),
+                }
+            }
+            _ => Err(FilterError::new(
+                "Expected a field, `tag:`, `not` or `(`",
+                token.span,
+            )),
+        }
+    }
+
+    fn value(&mut self) -> Result<Spanned<Value>, FilterError> {
+        let token = self.next();
+
+        let node = match token.node {
+            Token::Integer(integer, digits) => Value::Integer(integer, digits),
+            Token::Text(text) => Value::Text(text),
+            Token::Word(word) if word == "true" => Value::Bool(true),
+            Token::Word(word) if word == "false" => Value::Bool(false),
+            Token::Word(word) => Value::Text(word),
+            _ => return Err(FilterError::new("Expected a value", token.span)),
+        };
+
+        Ok(Spanned {
+            node,
+            span: token.span,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Comparison of `field` with `=` and `value`, spans are not compared by [`shape`]
+    fn compare(field: &str, value: Value) -> Expr {
+        Expr::Compare(Comparison {
+            field: Spanned {
+                node: field.to_string(),
+                span: 0..0,
+            },
+            op: Spanned {
+                node: Op::Eq,
+                span: 0..0,
+            },
+            value: Spanned {
+                node: value,
+                span: 0..0,
+            },
+        })
+    }
+
+    /// `expr` with every span cleared
+    fn shape(expr: Expr) -> Expr {
+        match expr {
+            Expr::And(left, right) => Expr::And(Box::new(shape(*left)), Box::new(shape(*right))),
+            Expr::Or(left, right) => Expr::Or(Box::new(shape(*left)), Box::new(shape(*right))),
+            Expr::Not(inner) => Expr::Not(Box::new(shape(*inner))),
+            Expr::Compare(comparison) => compare(&comparison.field.node, comparison.value.node),
+            Expr::Tag(name) => Expr::Tag(Spanned {
+                node: name.node,
+                span: 0..0,
+            }),
+        }
+    }
+
+    fn integer(value: i64) -> Value {
+        Value::Integer(value, value.to_string())
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let expr = shape(parse("a=1 or b=2 and c=3").unwrap());
+
+        assert_eq!(
+            expr,
+            Expr::Or(
+                Box::new(compare("a", integer(1))),
+                Box::new(Expr::And(
+                    Box::new(compare("b", integer(2))),
+                    Box::new(compare("c", integer(3)))
+                ))
+            )
+        );
+    }
+
+    #[test]
+    fn not_binds_tighter_than_and() {
+        let expr = shape(parse("not a=1 and b=2").unwrap());
+
+        assert_eq!(
+            expr,
+            Expr::And(
+                Box::new(Expr::Not(Box::new(compare("a", integer(1))))),
+                Box::new(compare("b", integer(2)))
+            )
+        );
+    }
+
+    #[test]
+    fn parentheses_group_first() {
+        let expr = shape(parse("(a=1 or b=2) and not (c=3)").unwrap());
+
+        assert_eq!(
+            expr,
+            Expr::And(
+                Box::new(Expr::Or(
+                    Box::new(compare("a", integer(1))),
+                    Box::new(compare("b", integer(2)))
+                )),
+                Box::new(Expr::Not(Box::new(compare("c", integer(3)))))
+            )
+        );
+    }
+
+    #[test]
+    fn keywords_ignore_case() {
+        assert_eq!(
+            shape(parse("a=1 AND b=2").unwrap()),
+            shape(parse("a=1 and b=2").unwrap())
+        );
+    }
+
+    #[test]
+    fn values() {
+        let value = |input: &str| match parse(input).unwrap() {
+            Expr::Compare(comparison) => comparison.value.node,
+            expr => panic!("Expected a comparison, got {:?}", expr),
+        };
+
+        assert_eq!(value("a=-5"), integer(-5));
+        assert_eq!(value("a=true"), Value::Bool(true));
+        assert_eq!(value("a=word"), Value::Text("word".to_string()));
+        assert_eq!(
+            value(r#"a="quoted \"text\"""#),
+            Value::Text("quoted \"text\"".to_string())
+        );
+        assert_eq!(value("a=007"), Value::Integer(7, "007".to_string()));
+    }
+
+    #[test]
+    fn tags_keep_their_text() {
+        let tag = |input: &str| match parse(input).unwrap() {
+            Expr::Tag(name) => name,
+            expr => panic!("Expected a tag, got {:?}", expr),
+        };
+
+        assert_eq!(tag("tag:music").node, "music");
+        assert_eq!(tag(r#"tag:"lo fi""#).node, "lo fi");
+        assert_eq!(tag("tag:007").node, "007");
+        assert_eq!(tag("tag:true").node, "true");
+        assert_eq!(tag("tag:music").span, 4..9);
+    }
+
+    #[test]
+    fn spans_point_at_the_token() {
+        let span = |input: &str| parse(input).unwrap_err().span;
+
+        assert_eq!(span("a=1 b=2"), 4..5);
+        assert_eq!(span("a=1 and"), 7..7);
+        assert_eq!(span("a 1"), 2..3);
+        assert_eq!(span("a=1)"), 3..4);
+        assert_eq!(span("(a=1"), 0..4);
+        assert_eq!(span(r#"a="open"#), 2..7);
+        assert_eq!(span("a=1 & b=2"), 4..5);
+        assert_eq!(span("a=!"), 2..3);
+        assert_eq!(span("name:x"), 0..5);
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        let nested = |count: usize| format!("{}a=1{}", "(".repeat(count), ")".repeat(count));
+
+        // The comparison inside the parentheses is one level deeper
+        assert!(parse(&nested(MAX_DEPTH - 1)).is_ok());
+        assert!(parse(&nested(MAX_DEPTH)).is_err());
+        assert!(parse(&format!("{}a=1", "not ".repeat(MAX_DEPTH))).is_err());
+    }
+
+    #[test]
+    fn terms_are_limited() {
+        let terms = |count: usize| vec!["a=1"; count].join(" or ");
+
+        assert!(parse(&terms(MAX_TERMS)).is_ok());
+
+        let input = terms(MAX_TERMS + 1);
+        let error = parse(&input).unwrap_err();
+        let last = input.rfind("a=1").unwrap();
+
+        assert_eq!(error.span, last..last + 1);
+        assert!(error.message.contains("comparisons and tags"));
+    }
+}
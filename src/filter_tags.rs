@@ -0,0 +1,59 @@
+// Tag filters
+//
+// Applies the `TagFilters` of `/api/videos`, `/api/channels` and `/api/watch_history` to any boxed
+// query with a video id column.
+//
+// Example:
+//
+// query = filter_tags!(query, videos_dsl::id, tag_filters);
+//
+
+#[macro_export]
+macro_rules! filter_tags {
+    ($query:expr, $video_id:expr, $filters:expr) => {{
+        use $crate::schema::tags::dsl as tags_dsl;
+        use $crate::schema::video_tags::dsl as video_tags_dsl;
+
+        let filters: &$crate::api_prelude::TagFilters = &$filters;
+        let mut query = $query;
+
+        if let Some(tags) = filters.tags.as_ref().filter(|tags| !tags.is_empty()) {
+            let tagged = video_tags_dsl::video_tags
+                .inner_join(tags_dsl::tags)
+                .filter(tags_dsl::name.eq_any(tags.clone()))
+                .select(video_tags_dsl::video_id);
+
+            query = match filters.tags_mode.unwrap_or_default() {
+                $crate::api_prelude::TagsMode::Any => query.filter($video_id.eq_any(tagged)),
+                // Tag names are unique and a video has each tag once, so a video that matched
+                // one row per requested name has every tag
+                $crate::api_prelude::TagsMode::All => {
+                    let mut names = tags.iter().collect::<Vec<&String>>();
+                    names.sort_unstable();
+                    names.dedup();
+
+                    query.filter(
+                        $video_id.eq_any(
+                            tagged
+                                .group_by(video_tags_dsl::video_id)
+                                .having(diesel::dsl::count_star().eq(names.len() as i64)),
+                        ),
+                    )
+                }
+            };
+        }
+
+        if let Some(exclude_tags) = &filters.exclude_tags {
+            query = query.filter(
+                $video_id.ne_all(
+                    video_tags_dsl::video_tags
+                        .inner_join(tags_dsl::tags)
+                        .filter(tags_dsl::name.eq_any(exclude_tags.clone()))
+                        .select(video_tags_dsl::video_id),
+                ),
+            );
+        }
+
+        query
+    }};
+}
@@ -0,0 +1,74 @@
+// Watch history filters
+//
+// Applies the `WatchHistoryFilters` of `/api/watch_history` and `/api/export` to any query that
+// includes the `watch_history` table.
+//
+// Example:
+//
+// query = filter_watch_history!(query, filters);
+//
+
+#[macro_export]
+macro_rules! filter_watch_history {
+    ($query:expr, $filters:expr) => {{
+        use $crate::schema::watch_history::dsl as watch_history_dsl;
+
+        let filters: &$crate::routes::api::WatchHistoryFilters = &$filters;
+        let mut query = $query;
+
+        if let Some(video_id) = &filters.video_id {
+            query = query.filter(watch_history_dsl::video_id.eq(video_id.clone()));
+        }
+
+        if let Some(channel_id) = &filters.channel_id {
+            query = query.filter(watch_history_dsl::channel_id.eq(channel_id.clone()));
+        }
+
+        if let Some(watch_duration_seconds) = filters.watch_duration_seconds {
+            query =
+                query.filter(watch_history_dsl::watch_duration_seconds.eq(watch_duration_seconds));
+        }
+
+        if let Some(min_watch_duration_seconds) = filters.min_watch_duration_seconds {
+            query = query
+                .filter(watch_history_dsl::watch_duration_seconds.gt(min_watch_duration_seconds));
+        }
+
+        if let Some(max_watch_duration_seconds) = filters.max_watch_duration_seconds {
+            query = query
+                .filter(watch_history_dsl::watch_duration_seconds.lt(max_watch_duration_seconds));
+        }
+
+        if let Some(watched_at) = filters.watched_at {
+            query = query.filter(watch_history_dsl::session_start_date.eq(watched_at));
+        }
+
+        if let Some(watched_before) = filters.watched_before {
+            query = query.filter(watch_history_dsl::session_start_date.lt(watched_before));
+        }
+
+        if let Some(watched_after) = filters.watched_after {
+            query = query.filter(watch_history_dsl::session_start_date.gt(watched_after));
+        }
+
+        if let Some(watched_year) = filters.watched_year {
+            query = query.filter(
+                year_unix!(watch_history_dsl::session_start_date).eq(watched_year.to_string()),
+            );
+        }
+
+        if let Some(watched_month) = filters.watched_month {
+            query = query.filter(
+                month_unix!(watch_history_dsl::session_start_date).eq(watched_month.to_string()),
+            );
+        }
+
+        if let Some(watched_day) = filters.watched_day {
+            query = query.filter(
+                day_unix!(watch_history_dsl::session_start_date).eq(watched_day.to_string()),
+            );
+        }
+
+        query
+    }};
+}
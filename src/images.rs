@@ -0,0 +1,417 @@
+use crate::api_prelude::rowid;
+use crate::database::models;
+use crate::jobs;
+use crate::schema;
+use crate::state::AppState;
+use crate::utils;
+use diesel::dsl::{insert_into, update};
+use diesel::prelude::*;
+use std::time::Duration;
+use tokio::task::JoinSet;
+
+/// `timeout` applies to every single image download
+pub fn build_http_client(timeout: Duration) -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(timeout)
+        .build()
+        .expect("Failed to build http client")
+}
+
+/// Downloads an image and converts it to webp, the conversion runs on the blocking thread pool.
+pub async fn fetch_image_as_webp(client: &reqwest::Client, url: &str) -> Result<Vec<u8>, String> {
+    let res = client.get(url).send().await.map_err(|e| e.to_string())?;
+
+    if res.status() != reqwest::StatusCode::OK {
+        return Err(format!("Unexpected status code {}", res.status()));
+    }
+
+    let image = res.bytes().await.map_err(|e| e.to_string())?;
+
+    tokio::task::spawn_blocking(move || {
+        let mut encoded = Vec::new();
+
+        image::load_from_memory(&image)
+            .map_err(|e| e.to_string())?
+            .write_to(
+                &mut std::io::Cursor::new(&mut encoded),
+                image::ImageFormat::WebP,
+            )
+            .map_err(|e| e.to_string())?;
+
+        Ok(encoded)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+pub async fn save_channel_avatar(
+    state: &AppState,
+    channel_id: &String,
+    image: Vec<u8>,
+) -> Result<(), String> {
+    let avater_file_path = state
+        .channel_avaters_dir
+        .join(utils::build_avater_cache_image_filename(channel_id));
+
+    tokio::fs::write(&avater_file_path, image)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Replaces the cached thumbnail of a video and keeps a copy of every distinct thumbnail.
+///
+/// A changed thumbnail starts a new revision with the current title, the first thumbnail of a
+/// revision is attached to it.
+pub async fn save_video_thumbnail(
+    state: &AppState,
+    video_id: &String,
+    image: Vec<u8>,
+) -> Result<(), String> {
+    use schema::video_revisions::dsl as video_revisions_dsl;
+
+    let thumbnail_file_path = state
+        .video_thumbnails_dir
+        .join(utils::build_thumbnail_cache_image_filename(video_id));
+
+    let latest = {
+        let video_id = video_id.clone();
+
+        state
+            .db(move |conn| {
+                video_revisions_dsl::video_revisions
+                    .filter(video_revisions_dsl::video_id.eq(video_id))
+                    .order((
+                        video_revisions_dsl::added_at.desc(),
+                        rowid("video_revisions").desc(),
+                    ))
+                    .first::<models::VideoRevision>(conn)
+                    .optional()
+                    .map_err(|e| e.to_string())
+            })
+            .await?
+    };
+
+    // A thumbnail that went missing on disk is compared with the one of the latest revision
+    let previous = match tokio::fs::read(&thumbnail_file_path).await {
+        Ok(current) => Some(current),
+        Err(_) => match latest.as_ref().and_then(|l| l.thumbnail_file.as_ref()) {
+            Some(file) => tokio::fs::read(state.video_thumbnail_revisions_dir.join(file))
+                .await
+                .ok(),
+            None => None,
+        },
+    };
+
+    let changed = previous.is_none_or(|previous| previous != image);
+    let restored = !changed && !thumbnail_file_path.exists();
+
+    let needs_revision_file = match &latest {
+        Some(latest) => changed || latest.thumbnail_file.is_none(),
+        None => false,
+    };
+
+    if changed || restored {
+        tokio::fs::write(&thumbnail_file_path, &image)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    let Some(latest) = latest.filter(|_| needs_revision_file) else {
+        return Ok(());
+    };
+
+    let thumbnail_file =
+        utils::build_thumbnail_revision_image_filename(video_id, &nanoid::nanoid!());
+    let revision_file_path = state.video_thumbnail_revisions_dir.join(&thumbnail_file);
+
+    if let Some(parent) = revision_file_path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    tokio::fs::write(&revision_file_path, &image)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if latest.thumbnail_file.is_none() {
+        state
+            .db(move |conn| {
+                update(video_revisions_dsl::video_revisions.find(&latest.id))
+                    .set(video_revisions_dsl::thumbnail_file.eq(&thumbnail_file))
+                    .execute(conn)
+                    .map_err(|e| e.to_string())
+            })
+            .await?;
+    } else {
+        tracing::info!("Thumbnail of video {} changed", video_id);
+
+        let revision =
+            models::VideoRevision::new(video_id.clone(), latest.title, Some(thumbnail_file));
+
+        state
+            .db(move |conn| {
+                insert_into(video_revisions_dsl::video_revisions)
+                    .values(&revision)
+                    .execute(conn)
+                    .map_err(|e| e.to_string())
+            })
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Source url of the avatar of a channel
+async fn avatar_url(state: &AppState, channel_id: &str) -> Result<String, String> {
+    use schema::channels::dsl as channels_dsl;
+
+    let id = channel_id.to_string();
+
+    state
+        .db(move |conn| {
+            channels_dsl::channels
+                .find(id)
+                .select(channels_dsl::avatar_url)
+                .first::<Option<String>>(conn)
+                .optional()
+                .map_err(|e| e.to_string())
+        })
+        .await?
+        .flatten()
+        .ok_or("No avatar url stored for channel".to_string())
+}
+
+/// Source url of the thumbnail of a video
+async fn thumbnail_url(state: &AppState, video_id: &str) -> Result<String, String> {
+    use schema::videos::dsl as videos_dsl;
+
+    let id = video_id.to_string();
+
+    state
+        .db(move |conn| {
+            videos_dsl::videos
+                .find(id)
+                .select(videos_dsl::thumbnail_url)
+                .first::<Option<String>>(conn)
+                .optional()
+                .map_err(|e| e.to_string())
+        })
+        .await?
+        .flatten()
+        .ok_or("No thumbnail url stored for video".to_string())
+}
+
+/// Downloads the avatar of a channel again from its stored source url.
+pub async fn repair_channel_avatar(state: &AppState, channel_id: &String) -> Result<(), String> {
+    let avatar_url = avatar_url(state, channel_id).await?;
+
+    let image = fetch_image_as_webp(&state.http_client, &avatar_url).await?;
+    save_channel_avatar(state, channel_id, image).await
+}
+
+/// Downloads the thumbnail of a video again from its stored source url.
+pub async fn repair_video_thumbnail(state: &AppState, video_id: &String) -> Result<(), String> {
+    let thumbnail_url = thumbnail_url(state, video_id).await?;
+
+    let image = fetch_image_as_webp(&state.http_client, &thumbnail_url).await?;
+    save_video_thumbnail(state, video_id, image).await
+}
+
+/// Downloads the missing avatar of a channel for a request, see [`fetch_for_request`].
+pub async fn fetch_missing_channel_avatar(
+    state: &AppState,
+    channel_id: &String,
+) -> Result<(), String> {
+    let avatar_url = avatar_url(state, channel_id).await?;
+
+    let image = fetch_for_request(
+        state,
+        models::JobKind::ChannelAvatar,
+        channel_id,
+        avatar_url,
+    )
+    .await?;
+    save_channel_avatar(state, channel_id, image).await
+}
+
+/// Downloads the missing thumbnail of a video for a request, see [`fetch_for_request`].
+pub async fn fetch_missing_video_thumbnail(
+    state: &AppState,
+    video_id: &String,
+) -> Result<(), String> {
+    let thumbnail_url = thumbnail_url(state, video_id).await?;
+
+    let image = fetch_for_request(
+        state,
+        models::JobKind::VideoThumbnail,
+        video_id,
+        thumbnail_url,
+    )
+    .await?;
+    save_video_thumbnail(state, video_id, image).await
+}
+
+/// Downloads an image that a request found missing, unless the job queue already handles it.
+///
+/// Requests leave an image to its job while the job is pending or waiting for a retry, and give
+/// up on a url that a job already failed on, so a broken url is not fetched on every page load.
+/// A failed download is queued as a job that already used its first attempt, later requests wait
+/// for its retries.
+async fn fetch_for_request(
+    state: &AppState,
+    kind: models::JobKind,
+    target_id: &str,
+    url: String,
+) -> Result<Vec<u8>, String> {
+    let allowed = {
+        let (target_id, url) = (target_id.to_string(), url.clone());
+
+        state
+            .db(move |conn| {
+                jobs::allows_fetch(conn, kind, &target_id, &url).map_err(|e| e.to_string())
+            })
+            .await?
+    };
+
+    if !allowed {
+        return Err("Download is left to the job queue".to_string());
+    }
+
+    match fetch_image_as_webp(&state.http_client, &url).await {
+        Ok(image) => Ok(image),
+        Err(e) => {
+            let (target_id, error) = (target_id.to_string(), e.clone());
+
+            state
+                .db(move |conn| {
+                    jobs::enqueue_retry(conn, kind, &target_id, &url, error)
+                        .map_err(|e| e.to_string())
+                })
+                .await?;
+
+            Err(e)
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct BackfillSummary {
+    pub repaired: usize,
+    pub failed: usize,
+    /// Missing images left to the pending or retrying job that downloads them
+    pub queued: usize,
+}
+
+enum MissingImage {
+    ChannelAvatar(String),
+    VideoThumbnail(String),
+}
+
+/// Downloads every channel avatar and video thumbnail that is missing on disk, at most
+/// `concurrency` downloads run at the same time. Images with a pending or retrying job are left
+/// to the job queue.
+pub async fn backfill(state: &AppState, concurrency: usize) -> Result<BackfillSummary, String> {
+    use schema::channels::dsl as channels_dsl;
+    use schema::videos::dsl as videos_dsl;
+
+    let (channel_ids, video_ids, queued_avatars, queued_thumbnails) = state
+        .db(|conn| {
+            let channel_ids = channels_dsl::channels
+                .filter(channels_dsl::avatar_url.is_not_null())
+                .select(channels_dsl::id)
+                .load::<String>(conn)
+                .map_err(|e| e.to_string())?;
+
+            let video_ids = videos_dsl::videos
+                .filter(videos_dsl::thumbnail_url.is_not_null())
+                .select(videos_dsl::id)
+                .load::<String>(conn)
+                .map_err(|e| e.to_string())?;
+
+            let queued_avatars = jobs::queued_targets(conn, models::JobKind::ChannelAvatar)
+                .map_err(|e| e.to_string())?;
+            let queued_thumbnails = jobs::queued_targets(conn, models::JobKind::VideoThumbnail)
+                .map_err(|e| e.to_string())?;
+
+            Ok::<_, String>((channel_ids, video_ids, queued_avatars, queued_thumbnails))
+        })
+        .await?;
+
+    let missing = channel_ids
+        .into_iter()
+        .filter(|id| {
+            !state
+                .channel_avaters_dir
+                .join(utils::build_avater_cache_image_filename(id))
+                .exists()
+        })
+        .map(MissingImage::ChannelAvatar)
+        .chain(
+            video_ids
+                .into_iter()
+                .filter(|id| {
+                    !state
+                        .video_thumbnails_dir
+                        .join(utils::build_thumbnail_cache_image_filename(id))
+                        .exists()
+                })
+                .map(MissingImage::VideoThumbnail),
+        );
+
+    let mut summary = BackfillSummary::default();
+    let mut tasks = JoinSet::new();
+
+    for image in missing {
+        let queued = match &image {
+            MissingImage::ChannelAvatar(id) => queued_avatars.contains(id),
+            MissingImage::VideoThumbnail(id) => queued_thumbnails.contains(id),
+        };
+
+        if queued {
+            summary.queued += 1;
+            continue;
+        }
+
+        if tasks.len() >= concurrency.max(1) {
+            tally(&mut summary, tasks.join_next().await);
+        }
+
+        let state = state.clone();
+
+        tasks.spawn(async move {
+            match &image {
+                MissingImage::ChannelAvatar(id) => repair_channel_avatar(&state, id)
+                    .await
+                    .map_err(|e| format!("Failed to repair avatar of channel {}: {}", id, e)),
+                MissingImage::VideoThumbnail(id) => repair_video_thumbnail(&state, id)
+                    .await
+                    .map_err(|e| format!("Failed to repair thumbnail of video {}: {}", id, e)),
+            }
+        });
+    }
+
+    while let Some(result) = tasks.join_next().await {
+        tally(&mut summary, Some(result));
+    }
+
+    Ok(summary)
+}
+
+fn tally(
+    summary: &mut BackfillSummary,
+    result: Option<Result<Result<(), String>, tokio::task::JoinError>>,
+) {
+    match result {
+        Some(Ok(Ok(()))) => summary.repaired += 1,
+        Some(Ok(Err(e))) => {
+            tracing::error!("{}", e);
+            summary.failed += 1;
+        }
+        Some(Err(e)) => {
+            tracing::error!("Image repair task failed: {}", e);
+            summary.failed += 1;
+        }
+        None => {}
+    }
+}
@@ -0,0 +1,140 @@
+//! Chianti `json` and `ndjson` exports, see [`crate::export`].
+//!
+//! Ids and dates of the exported records are kept so an export imported into an empty database
+//! reproduces it. Metric snapshots, revisions and images are not part of the export.
+
+use super::{ImportBatch, Importer};
+use crate::api_prelude::{VideoResponse, WatchHistoryResponse};
+use crate::database::models;
+use crate::export::{ExportDocument, ExportRecord};
+use crate::ingest::{ChannelUpsert, VideoUpsert};
+use std::collections::HashMap;
+
+pub struct Chianti;
+
+impl Importer for Chianti {
+    fn name(&self) -> &'static str {
+        "chianti"
+    }
+
+    fn accepts(&self, file_name: &str) -> bool {
+        file_name.ends_with(".json") || file_name.ends_with(".ndjson")
+    }
+
+    fn parse(&self, data: &[u8]) -> Result<ImportBatch, String> {
+        let document = match serde_json::from_slice::<ExportDocument>(data) {
+            Ok(document) => document,
+            Err(e) => parse_ndjson(data).map_err(|_| format!("Not a chianti export: {}", e))?,
+        };
+
+        Ok(batch(document))
+    }
+}
+
+fn parse_ndjson(data: &[u8]) -> Result<ExportDocument, serde_json::Error> {
+    let mut document = ExportDocument {
+        channels: Vec::new(),
+        videos: Vec::new(),
+        tags: Vec::new(),
+        watch_history: Vec::new(),
+    };
+
+    for line in data.split(|&b| b == b'\n') {
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+
+        match serde_json::from_slice::<ExportRecord>(line)? {
+            ExportRecord::Channel(channel) => document.channels.push(channel),
+            ExportRecord::Video(video) => document.videos.push(video),
+            ExportRecord::Tag(tag) => document.tags.push(tag),
+            ExportRecord::WatchHistory(watch_history) => document.watch_history.push(watch_history),
+        }
+    }
+
+    Ok(document)
+}
+
+fn batch(document: ExportDocument) -> ImportBatch {
+    let mut batch = ImportBatch {
+        tags: document.tags,
+        ..Default::default()
+    };
+
+    for channel in document.channels {
+        batch.channels.push(channel_upsert(channel.channel));
+    }
+
+    let mut video_channels = HashMap::new();
+
+    for VideoResponse {
+        video,
+        tags,
+        channel,
+        ..
+    } in document.videos
+    {
+        let Some(channel) = channel else {
+            batch.skipped += 1;
+            continue;
+        };
+
+        let channel_id = channel.channel.id.clone();
+        batch.channels.push(channel_upsert(channel.channel));
+        video_channels.insert(video.id.clone(), channel_id.clone());
+
+        batch.videos.push(VideoUpsert {
+            id: video.id,
+            channel_id,
+            title: video.title,
+            description: Some(video.description),
+            thumbnail_url: video.thumbnail_url,
+            tags,
+            duration_seconds: Some(video.duration_seconds),
+            published_at: Some(video.published_at),
+            likes_count: Some(video.likes_count),
+            view_count: Some(video.view_count),
+            comments_count: Some(video.comments_count),
+            refresh_thumbnail: false,
+            added_at: Some(video.added_at),
+            fill_only: false,
+        });
+    }
+
+    for WatchHistoryResponse {
+        mut watch_history,
+        video,
+    } in document.watch_history
+    {
+        let channel_id = video
+            .channel
+            .map(|channel| channel.channel.id)
+            .or_else(|| video_channels.get(&video.video.id).cloned());
+
+        let Some(channel_id) = channel_id else {
+            batch.skipped += 1;
+            continue;
+        };
+
+        watch_history.video_id = video.video.id;
+        watch_history.channel_id = channel_id;
+        batch.history.push(watch_history);
+    }
+
+    batch.dedup();
+
+    batch
+}
+
+fn channel_upsert(channel: models::Channel) -> ChannelUpsert {
+    ChannelUpsert {
+        id: channel.id,
+        name: channel.name,
+        url: Some(channel.url),
+        avatar_url: channel.avatar_url,
+        is_subscribed: Some(channel.is_subscribed),
+        subscribers_count: Some(channel.subscribers_count),
+        added_at: Some(channel.added_at),
+        fill_only: false,
+    }
+}
@@ -0,0 +1,173 @@
+//! FreeTube `history.db` and `profiles.db` files.
+//!
+//! Both are NeDB databases, an append only log with one JSON document per line where a later line
+//! replaces the document with the same `_id`.
+
+use super::{ImportBatch, ImportedDuration, ImportedWatch, Importer};
+use crate::ingest::{ChannelUpsert, VideoUpsert};
+use crate::validation;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HistoryEntry {
+    video_id: String,
+    title: String,
+    author: String,
+    author_id: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    view_count: Option<i64>,
+    #[serde(default)]
+    length_seconds: Option<i64>,
+    #[serde(default)]
+    watch_progress: Option<f64>,
+    /// Milliseconds
+    time_watched: i64,
+    /// Milliseconds
+    #[serde(default)]
+    published: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct Profile {
+    subscriptions: Vec<Subscription>,
+}
+
+#[derive(Deserialize)]
+struct Subscription {
+    id: String,
+    name: String,
+    #[serde(default)]
+    thumbnail: Option<String>,
+}
+
+pub struct FreeTube;
+
+impl Importer for FreeTube {
+    fn name(&self) -> &'static str {
+        "freetube"
+    }
+
+    /// Accepts `history.db`, `profiles.db` or both concatenated
+    fn parse(&self, data: &[u8]) -> Result<ImportBatch, String> {
+        let contents = std::str::from_utf8(data).map_err(|e| e.to_string())?;
+
+        let mut documents: HashMap<String, serde_json::Value> = HashMap::new();
+        let mut order = Vec::new();
+
+        for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+            let document = serde_json::from_str::<serde_json::Value>(line)
+                .map_err(|e| format!("Not a FreeTube database: {}", e))?;
+
+            let Some(id) = document.get("_id").and_then(|id| id.as_str()) else {
+                continue;
+            };
+
+            if document.get("$$deleted").is_some() {
+                documents.remove(id);
+                continue;
+            }
+
+            if !documents.contains_key(id) {
+                order.push(id.to_string());
+            }
+
+            documents.insert(id.to_string(), document);
+        }
+
+        let mut batch = ImportBatch::default();
+        let mut history = Vec::new();
+
+        for document in order.iter().filter_map(|id| documents.remove(id)) {
+            if document.get("subscriptions").is_some() {
+                let profile = serde_json::from_value::<Profile>(document)
+                    .map_err(|e| format!("Invalid FreeTube profile: {}", e))?;
+
+                for subscription in profile.subscriptions {
+                    if !validation::is_channel_id(&subscription.id) {
+                        batch.skipped += 1;
+                        continue;
+                    }
+
+                    batch.channels.push(ChannelUpsert {
+                        url: Some(format!(
+                            "https://www.youtube.com/channel/{}",
+                            subscription.id
+                        )),
+                        id: subscription.id,
+                        name: subscription.name,
+                        avatar_url: subscription.thumbnail,
+                        is_subscribed: Some(true),
+                        subscribers_count: None,
+                        added_at: None,
+                        fill_only: false,
+                    });
+                }
+            } else if document.get("videoId").is_some() {
+                match serde_json::from_value::<HistoryEntry>(document) {
+                    Ok(entry)
+                        if validation::is_video_id(&entry.video_id)
+                            && validation::is_channel_id(&entry.author_id) =>
+                    {
+                        history.push(entry)
+                    }
+                    _ => batch.skipped += 1,
+                }
+            }
+        }
+
+        history.sort_by_key(|entry| std::cmp::Reverse(entry.time_watched));
+
+        for entry in history {
+            batch.channels.push(ChannelUpsert {
+                id: entry.author_id.clone(),
+                name: entry.author,
+                url: None,
+                avatar_url: None,
+                is_subscribed: None,
+                subscribers_count: None,
+                added_at: None,
+                fill_only: true,
+            });
+
+            batch.videos.push(VideoUpsert {
+                thumbnail_url: Some(format!(
+                    "https://i.ytimg.com/vi/{}/hqdefault.jpg",
+                    entry.video_id
+                )),
+                id: entry.video_id.clone(),
+                channel_id: entry.author_id.clone(),
+                title: entry.title,
+                description: entry.description.filter(|d| !d.is_empty()),
+                tags: Vec::new(),
+                duration_seconds: entry.length_seconds.filter(|&s| s > 0),
+                published_at: entry.published.filter(|&p| p > 0).map(|p| p / 1000),
+                likes_count: None,
+                view_count: entry.view_count,
+                comments_count: None,
+                refresh_thumbnail: false,
+                added_at: None,
+                fill_only: true,
+            });
+
+            let watch_duration = match entry.watch_progress {
+                Some(progress) if progress > 0.0 => ImportedDuration::Reported(progress as i64),
+                _ => ImportedDuration::FromVideoLength,
+            };
+
+            batch.watches.push(ImportedWatch {
+                video_id: entry.video_id,
+                channel_id: entry.author_id,
+                watched_at: entry.time_watched / 1000,
+                watch_duration,
+            });
+        }
+
+        batch.dedup();
+
+        Ok(batch)
+    }
+}
@@ -0,0 +1,41 @@
+//! Invidious JSON export (`Settings > Import/export > Export data as JSON`).
+//!
+//! The export only has channel ids for subscriptions and video ids without dates for the watch
+//! history, so only channels that are already saved are marked as subscribed and the watch
+//! history is reported as skipped.
+
+use super::{ImportBatch, Importer};
+use crate::validation;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Export {
+    #[serde(default)]
+    subscriptions: Vec<String>,
+    #[serde(default)]
+    watch_history: Vec<String>,
+}
+
+pub struct Invidious;
+
+impl Importer for Invidious {
+    fn name(&self) -> &'static str {
+        "invidious"
+    }
+
+    fn parse(&self, data: &[u8]) -> Result<ImportBatch, String> {
+        let export = serde_json::from_slice::<Export>(data)
+            .map_err(|e| format!("Not an Invidious export: {}", e))?;
+
+        let (subscriptions, invalid): (Vec<String>, Vec<String>) = export
+            .subscriptions
+            .into_iter()
+            .partition(|id| validation::is_channel_id(id));
+
+        Ok(ImportBatch {
+            subscriptions,
+            skipped: invalid.len() + export.watch_history.len(),
+            ..Default::default()
+        })
+    }
+}
@@ -0,0 +1,447 @@
+mod chianti;
+mod freetube;
+mod invidious;
+mod newpipe;
+mod takeout;
+mod ytdlp;
+
+use crate::database::models;
+use crate::ingest::{self, ChannelUpsert, Outcome, VideoUpsert};
+use crate::schema;
+use crate::validation;
+use diesel::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+use ts_rs::TS;
+
+/// Ids bound to one query, large exports stay below the bound parameter limit of SQLite
+const IDS_PER_QUERY: usize = 10_000;
+
+/// Reads the export file of another service
+pub trait Importer: Sync {
+    /// Name used by `chianti import <name>` and `/api/import/{name}`
+    fn name(&self) -> &'static str;
+
+    fn parse(&self, data: &[u8]) -> Result<ImportBatch, String>;
+
+    /// Whether a file of an imported directory is read, every file by default
+    fn accepts(&self, _file_name: &str) -> bool {
+        true
+    }
+}
+
+pub static IMPORTERS: &[&dyn Importer] = &[
+    &takeout::Takeout,
+    &freetube::FreeTube,
+    &newpipe::NewPipe,
+    &invidious::Invidious,
+    &ytdlp::YtDlp,
+    &chianti::Chianti,
+];
+
+pub fn find(name: &str) -> Option<&'static dyn Importer> {
+    IMPORTERS
+        .iter()
+        .find(|importer| importer.name() == name)
+        .copied()
+}
+
+pub fn names() -> Vec<&'static str> {
+    IMPORTERS.iter().map(|importer| importer.name()).collect()
+}
+
+/// A watched video from an export
+pub struct ImportedWatch {
+    pub video_id: String,
+    pub channel_id: String,
+    pub watched_at: i64,
+    pub watch_duration: ImportedDuration,
+}
+
+pub enum ImportedDuration {
+    /// Watch time reported by the export, e.g. the playback position
+    Reported(i64),
+    /// Not in the export, estimated from the video length by [`estimate_watch_durations`]
+    FromVideoLength,
+}
+
+/// Everything read from an export, channels are saved before videos and videos before watches.
+///
+/// Importers add entries most recent first.
+#[derive(Default)]
+pub struct ImportBatch {
+    pub channels: Vec<ChannelUpsert>,
+    pub videos: Vec<VideoUpsert>,
+    pub watches: Vec<ImportedWatch>,
+    /// Watch history rows that are saved as they are, e.g. from a chianti export
+    pub history: Vec<models::WatchHistory>,
+    /// Tags that are saved as they are unless a tag with the same name exists, tags of videos
+    /// that are not saved yet are created by [`ingest::upsert_video`]
+    pub tags: Vec<models::Tag>,
+    /// Ids of subscribed channels that the export has no other info about, only channels that
+    /// are already saved are marked as subscribed
+    pub subscriptions: Vec<String>,
+    /// Entries of the export that could not be imported
+    pub skipped: usize,
+}
+
+impl ImportBatch {
+    pub fn merge(&mut self, other: ImportBatch) {
+        self.channels.extend(other.channels);
+        self.videos.extend(other.videos);
+        self.watches.extend(other.watches);
+        self.history.extend(other.history);
+        self.tags.extend(other.tags);
+        self.subscriptions.extend(other.subscriptions);
+        self.skipped += other.skipped;
+    }
+
+    /// Merges the channels and videos with the same id, the values of the first entry win.
+    ///
+    /// Entries that are not `fill_only` hold current values and win over `fill_only` entries,
+    /// e.g. a subscription over the channel of a watched video.
+    pub fn dedup(&mut self) {
+        let mut channels: Vec<ChannelUpsert> = Vec::new();
+        let mut index = HashMap::new();
+
+        for mut channel in self.channels.drain(..) {
+            match index.get(&channel.id) {
+                Some(&i) => {
+                    let first: &mut ChannelUpsert = &mut channels[i];
+
+                    if first.fill_only && !channel.fill_only {
+                        std::mem::swap(first, &mut channel);
+                    }
+
+                    first.url = first.url.take().or(channel.url);
+                    first.avatar_url = first.avatar_url.take().or(channel.avatar_url);
+                    first.is_subscribed = first.is_subscribed.or(channel.is_subscribed);
+                    first.subscribers_count = first.subscribers_count.or(channel.subscribers_count);
+                    first.added_at = first.added_at.or(channel.added_at);
+                }
+                None => {
+                    index.insert(channel.id.clone(), channels.len());
+                    channels.push(channel);
+                }
+            }
+        }
+
+        let mut videos: Vec<VideoUpsert> = Vec::new();
+        let mut index = HashMap::new();
+
+        for mut video in self.videos.drain(..) {
+            match index.get(&video.id) {
+                Some(&i) => {
+                    let first: &mut VideoUpsert = &mut videos[i];
+
+                    if first.fill_only && !video.fill_only {
+                        std::mem::swap(first, &mut video);
+                    }
+
+                    first.description = first.description.take().or(video.description);
+                    first.thumbnail_url = first.thumbnail_url.take().or(video.thumbnail_url);
+                    first.duration_seconds = first.duration_seconds.or(video.duration_seconds);
+                    first.published_at = first.published_at.or(video.published_at);
+                    first.likes_count = first.likes_count.or(video.likes_count);
+                    first.view_count = first.view_count.or(video.view_count);
+                    first.comments_count = first.comments_count.or(video.comments_count);
+                    first.added_at = first.added_at.or(video.added_at);
+
+                    for tag in video.tags {
+                        if !first.tags.contains(&tag) {
+                            first.tags.push(tag);
+                        }
+                    }
+                }
+                None => {
+                    index.insert(video.id.clone(), videos.len());
+                    videos.push(video);
+                }
+            }
+        }
+
+        self.channels = channels;
+        self.videos = videos;
+    }
+}
+
+/// Reads an export file, or every accepted file below a directory.
+///
+/// Files of a directory that can not be read are reported as skipped.
+pub fn parse_path(importer: &dyn Importer, path: &Path) -> Result<ImportBatch, String> {
+    if !path.is_dir() {
+        let data =
+            std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+        return importer
+            .parse(&data)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e));
+    }
+
+    let mut batch = ImportBatch::default();
+    let mut dirs = vec![path.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        let entries = std::fs::read_dir(&dir)
+            .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+
+        for entry in entries {
+            let path = entry.map_err(|e| e.to_string())?.path();
+
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+
+            let accepted = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| importer.accepts(name));
+
+            if !accepted {
+                continue;
+            }
+
+            let parsed = std::fs::read(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|data| importer.parse(&data));
+
+            match parsed {
+                Ok(parsed) => batch.merge(parsed),
+                Err(e) => {
+                    tracing::warn!("Skipped {}: {}", path.display(), e);
+                    batch.skipped += 1;
+                }
+            }
+        }
+    }
+
+    batch.dedup();
+
+    Ok(batch)
+}
+
+#[derive(utoipa::ToSchema, Serialize, Debug, Default, TS)]
+#[ts(export)]
+pub struct ImportOutcomeCounts {
+    #[ts(type = "number")]
+    created: usize,
+    #[ts(type = "number")]
+    updated: usize,
+    #[ts(type = "number")]
+    unchanged: usize,
+}
+
+impl ImportOutcomeCounts {
+    fn add(&mut self, outcome: Outcome) {
+        match outcome {
+            Outcome::Created => self.created += 1,
+            Outcome::Updated => self.updated += 1,
+            Outcome::Unchanged => self.unchanged += 1,
+        }
+    }
+}
+
+#[derive(utoipa::ToSchema, Serialize, Debug, Default, TS)]
+#[ts(export)]
+pub struct ImportSummary {
+    channels: ImportOutcomeCounts,
+    videos: ImportOutcomeCounts,
+    watch_history: ImportOutcomeCounts,
+    #[ts(type = "number")]
+    skipped: usize,
+    /// Nothing was saved, the counts show what the import would change
+    dry_run: bool,
+}
+
+/// Saves an import batch in one transaction through the same upserts as the browser extension.
+///
+/// Watches are deduplicated by video and start time so importing the same export again only
+/// reports unchanged records. A dry run rolls the transaction back and reports what would change.
+pub fn apply(
+    conn: &mut SqliteConnection,
+    batch: &ImportBatch,
+    dry_run: bool,
+) -> QueryResult<ImportSummary> {
+    let mut dry_run_summary = None;
+
+    let result = conn.transaction(|conn| {
+        let summary = apply_batch(conn, batch)?;
+
+        if dry_run {
+            dry_run_summary = Some(ImportSummary {
+                dry_run: true,
+                ..summary
+            });
+
+            return Err(diesel::result::Error::RollbackTransaction);
+        }
+
+        Ok(summary)
+    });
+
+    match (result, dry_run_summary) {
+        (Err(diesel::result::Error::RollbackTransaction), Some(summary)) => Ok(summary),
+        (result, _) => result,
+    }
+}
+
+fn apply_batch(conn: &mut SqliteConnection, batch: &ImportBatch) -> QueryResult<ImportSummary> {
+    use schema::channels::dsl as channels_dsl;
+    use schema::tags::dsl as tags_dsl;
+
+    let mut summary = ImportSummary {
+        skipped: batch.skipped,
+        ..Default::default()
+    };
+
+    for channel in &batch.channels {
+        summary.channels.add(ingest::upsert_channel(conn, channel)?);
+    }
+
+    for channel_id in &batch.subscriptions {
+        let Some(name) = channels_dsl::channels
+            .find(channel_id)
+            .select(channels_dsl::name)
+            .first::<String>(conn)
+            .optional()?
+        else {
+            summary.skipped += 1;
+            continue;
+        };
+
+        let outcome = ingest::upsert_channel(
+            conn,
+            &ChannelUpsert {
+                id: channel_id.clone(),
+                name,
+                url: None,
+                avatar_url: None,
+                is_subscribed: Some(true),
+                subscribers_count: None,
+                added_at: None,
+                fill_only: false,
+            },
+        )?;
+
+        summary.channels.add(outcome);
+    }
+
+    for tag in &batch.tags {
+        diesel::insert_or_ignore_into(tags_dsl::tags)
+            .values(tag)
+            .execute(conn)?;
+    }
+
+    for video in &batch.videos {
+        summary.videos.add(ingest::upsert_video(conn, video)?);
+    }
+
+    for watch_history in estimate_watch_durations(conn, &batch.watches)? {
+        let (outcome, _) = ingest::save_watch_session(conn, watch_history)?;
+        summary.watch_history.add(outcome);
+    }
+
+    for watch_history in &batch.history {
+        let (outcome, _) = ingest::save_watch_session(conn, watch_history.clone())?;
+        summary.watch_history.add(outcome);
+    }
+
+    Ok(summary)
+}
+
+/// Builds the watch history rows of imported watches.
+///
+/// A watch without a duration is assumed to last until the next watched video, at most the
+/// length of the video. Watches of videos with an unknown length are saved with no duration.
+fn estimate_watch_durations(
+    conn: &mut SqliteConnection,
+    watches: &[ImportedWatch],
+) -> QueryResult<Vec<models::WatchHistory>> {
+    use schema::videos::dsl as videos_dsl;
+
+    let mut watched_at = watches.iter().map(|w| w.watched_at).collect::<Vec<i64>>();
+    watched_at.sort_unstable();
+    watched_at.dedup();
+
+    let mut video_ids = watches
+        .iter()
+        .filter(|watch| matches!(watch.watch_duration, ImportedDuration::FromVideoLength))
+        .map(|watch| &watch.video_id)
+        .collect::<Vec<&String>>();
+    video_ids.sort_unstable();
+    video_ids.dedup();
+
+    let mut video_durations: HashMap<String, i64> = HashMap::new();
+
+    for chunk in video_ids.chunks(IDS_PER_QUERY) {
+        video_durations.extend(
+            videos_dsl::videos
+                .filter(videos_dsl::id.eq_any(chunk))
+                .select((videos_dsl::id, videos_dsl::duration_seconds))
+                .load::<(String, i64)>(conn)?,
+        );
+    }
+
+    let mut rows = Vec::with_capacity(watches.len());
+
+    for watch in watches {
+        let (watch_duration_seconds, duration_source) = match watch.watch_duration {
+            ImportedDuration::Reported(seconds) => {
+                (seconds, models::WatchDurationSource::Estimated)
+            }
+            ImportedDuration::FromVideoLength => {
+                // Videos are saved before their watches
+                let video_duration = video_durations.get(&watch.video_id).copied().unwrap_or(0);
+
+                let next_watch = watched_at
+                    .get(watched_at.partition_point(|&t| t <= watch.watched_at))
+                    .map(|next| next - watch.watched_at);
+
+                match (video_duration, next_watch) {
+                    (0, _) => (0, models::WatchDurationSource::Unknown),
+                    (duration, Some(gap)) => {
+                        (duration.min(gap), models::WatchDurationSource::Estimated)
+                    }
+                    (duration, None) => (duration, models::WatchDurationSource::Estimated),
+                }
+            }
+        };
+
+        let mut row = models::WatchHistory::new(
+            watch.video_id.clone(),
+            watch.channel_id.clone(),
+            watch_duration_seconds,
+            watch.watched_at,
+            watch.watched_at + watch_duration_seconds,
+            None,
+        );
+        row.duration_source = duration_source.as_str().to_string();
+
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+/// Reads the `v` parameter of a youtube watch url
+fn video_id_from_url(url: &str) -> Option<String> {
+    let (_, query) = url.split_once('?')?;
+
+    query
+        .split(['&', '#'])
+        .find_map(|param| param.strip_prefix("v="))
+        .filter(|id| validation::is_video_id(id))
+        .map(String::from)
+}
+
+/// Reads the channel id of a `/channel/<id>` url
+fn channel_id_from_url(url: &str) -> Option<String> {
+    let (_, rest) = url.split_once("/channel/")?;
+
+    rest.split(['/', '?', '#'])
+        .next()
+        .filter(|id| validation::is_channel_id(id))
+        .map(String::from)
+}
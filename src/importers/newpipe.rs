@@ -0,0 +1,189 @@
+//! NewPipe export zip (`Settings > Backup and restore > Export database`) or the `newpipe.db`
+//! SQLite database inside it.
+
+use super::{
+    ImportBatch, ImportedDuration, ImportedWatch, Importer, channel_id_from_url, video_id_from_url,
+};
+use crate::ingest::{ChannelUpsert, VideoUpsert};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Nullable, Text};
+use std::io::Read;
+use std::path::Path;
+
+/// NewPipe stores the streaming service of every row, youtube is `0`
+const YOUTUBE_SERVICE_ID: i64 = 0;
+
+#[derive(QueryableByName)]
+struct HistoryRow {
+    #[diesel(sql_type = Text)]
+    url: String,
+    #[diesel(sql_type = Text)]
+    title: String,
+    #[diesel(sql_type = BigInt)]
+    duration: i64,
+    #[diesel(sql_type = Text)]
+    uploader: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    uploader_url: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    thumbnail_url: Option<String>,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    view_count: Option<i64>,
+    /// Milliseconds
+    #[diesel(sql_type = Nullable<BigInt>)]
+    upload_date: Option<i64>,
+    /// Milliseconds
+    #[diesel(sql_type = BigInt)]
+    access_date: i64,
+    /// Milliseconds
+    #[diesel(sql_type = Nullable<BigInt>)]
+    progress_time: Option<i64>,
+}
+
+#[derive(QueryableByName)]
+struct SubscriptionRow {
+    #[diesel(sql_type = Text)]
+    url: String,
+    #[diesel(sql_type = Text)]
+    name: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    avatar_url: Option<String>,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    subscriber_count: Option<i64>,
+}
+
+pub struct NewPipe;
+
+impl Importer for NewPipe {
+    fn name(&self) -> &'static str {
+        "newpipe"
+    }
+
+    fn parse(&self, data: &[u8]) -> Result<ImportBatch, String> {
+        let database = if data.starts_with(b"SQLite format 3\0") {
+            data.to_vec()
+        } else {
+            let mut archive = zip::ZipArchive::new(std::io::Cursor::new(data))
+                .map_err(|e| format!("Not a NewPipe export: {}", e))?;
+            let mut file = archive
+                .by_name("newpipe.db")
+                .map_err(|e| format!("Not a NewPipe export: {}", e))?;
+
+            let mut database = Vec::new();
+            file.read_to_end(&mut database).map_err(|e| e.to_string())?;
+
+            database
+        };
+
+        // SQLite can only open databases from a file
+        let path = std::env::temp_dir().join(format!("chianti-newpipe-{}.db", nanoid::nanoid!()));
+        std::fs::write(&path, database).map_err(|e| e.to_string())?;
+
+        let batch = read_database(&path);
+
+        if let Err(e) = std::fs::remove_file(&path) {
+            tracing::warn!("Failed to remove {}: {}", path.display(), e);
+        }
+
+        batch
+    }
+}
+
+fn read_database(path: &Path) -> Result<ImportBatch, String> {
+    let mut conn =
+        SqliteConnection::establish(&path.to_string_lossy()).map_err(|e| e.to_string())?;
+
+    let subscriptions = diesel::sql_query(
+        "SELECT url, name, avatar_url, subscriber_count FROM subscriptions WHERE service_id = ?",
+    )
+    .bind::<BigInt, _>(YOUTUBE_SERVICE_ID)
+    .load::<SubscriptionRow>(&mut conn)
+    .map_err(|e| format!("Failed to read NewPipe subscriptions: {}", e))?;
+
+    let history = diesel::sql_query(
+        "SELECT s.url, s.title, s.duration, s.uploader, s.uploader_url, s.thumbnail_url, \
+            s.view_count, s.upload_date, h.access_date, st.progress_time \
+        FROM stream_history h \
+        JOIN streams s ON s.uid = h.stream_id \
+        LEFT JOIN stream_state st ON st.stream_id = s.uid \
+        WHERE s.service_id = ? \
+        ORDER BY h.access_date DESC",
+    )
+    .bind::<BigInt, _>(YOUTUBE_SERVICE_ID)
+    .load::<HistoryRow>(&mut conn)
+    .map_err(|e| format!("Failed to read NewPipe watch history: {}", e))?;
+
+    let mut batch = ImportBatch::default();
+
+    for subscription in subscriptions {
+        let Some(channel_id) = channel_id_from_url(&subscription.url) else {
+            batch.skipped += 1;
+            continue;
+        };
+
+        batch.channels.push(ChannelUpsert {
+            id: channel_id,
+            name: subscription.name,
+            url: Some(subscription.url),
+            avatar_url: subscription.avatar_url,
+            is_subscribed: Some(true),
+            subscribers_count: subscription.subscriber_count.filter(|&c| c >= 0),
+            added_at: None,
+            fill_only: false,
+        });
+    }
+
+    for row in history {
+        let (Some(video_id), Some(channel_id)) = (
+            video_id_from_url(&row.url),
+            row.uploader_url.as_deref().and_then(channel_id_from_url),
+        ) else {
+            batch.skipped += 1;
+            continue;
+        };
+
+        batch.channels.push(ChannelUpsert {
+            id: channel_id.clone(),
+            name: row.uploader,
+            url: row.uploader_url,
+            avatar_url: None,
+            is_subscribed: None,
+            subscribers_count: None,
+            added_at: None,
+            fill_only: true,
+        });
+
+        batch.videos.push(VideoUpsert {
+            id: video_id.clone(),
+            channel_id: channel_id.clone(),
+            title: row.title,
+            description: None,
+            thumbnail_url: row.thumbnail_url,
+            tags: Vec::new(),
+            duration_seconds: Some(row.duration).filter(|&d| d > 0),
+            published_at: row.upload_date.map(|date| date / 1000),
+            likes_count: None,
+            view_count: row.view_count.filter(|&c| c >= 0),
+            comments_count: None,
+            refresh_thumbnail: false,
+            added_at: None,
+            fill_only: true,
+        });
+
+        let watch_duration = match row.progress_time {
+            Some(progress) if progress > 0 => ImportedDuration::Reported(progress / 1000),
+            _ => ImportedDuration::FromVideoLength,
+        };
+
+        batch.watches.push(ImportedWatch {
+            video_id,
+            channel_id,
+            watched_at: row.access_date / 1000,
+            watch_duration,
+        });
+    }
+
+    batch.dedup();
+
+    Ok(batch)
+}
@@ -0,0 +1,312 @@
+//! Google Takeout `watch-history.json` and `watch-history.html` files.
+
+use super::{
+    ImportBatch, ImportedDuration, ImportedWatch, Importer, channel_id_from_url, video_id_from_url,
+};
+use crate::ingest::{ChannelUpsert, VideoUpsert};
+use chrono::{FixedOffset, NaiveDateTime, TimeZone};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonEntry {
+    title: String,
+    title_url: Option<String>,
+    #[serde(default)]
+    subtitles: Vec<JsonSubtitle>,
+    time: String,
+    /// Only set for ads, e.g. "From Google Ads"
+    #[serde(default)]
+    details: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct JsonSubtitle {
+    name: String,
+    url: Option<String>,
+}
+
+/// A watched video read from either format
+struct Entry {
+    video_id: String,
+    title: String,
+    channel_id: String,
+    channel_name: String,
+    watched_at: i64,
+}
+
+pub struct Takeout;
+
+impl Importer for Takeout {
+    fn name(&self) -> &'static str {
+        "takeout"
+    }
+
+    fn parse(&self, data: &[u8]) -> Result<ImportBatch, String> {
+        let contents = std::str::from_utf8(data).map_err(|e| e.to_string())?;
+
+        parse(contents)
+    }
+}
+
+/// Parses a takeout watch history file, the format is detected from the contents.
+fn parse(contents: &str) -> Result<ImportBatch, String> {
+    let entries = if contents.trim_start().starts_with('[') {
+        parse_json(contents)?
+    } else if contents.contains("content-cell") {
+        parse_html(contents)
+    } else {
+        return Err("Not a takeout watch history file".to_string());
+    };
+
+    let mut batch = ImportBatch::default();
+
+    for entry in entries {
+        let Some(entry) = entry else {
+            batch.skipped += 1;
+            continue;
+        };
+
+        batch.channels.push(ChannelUpsert {
+            id: entry.channel_id.clone(),
+            name: entry.channel_name,
+            url: None,
+            avatar_url: None,
+            is_subscribed: None,
+            subscribers_count: None,
+            added_at: None,
+            fill_only: true,
+        });
+
+        batch.videos.push(VideoUpsert {
+            thumbnail_url: Some(format!(
+                "https://i.ytimg.com/vi/{}/hqdefault.jpg",
+                entry.video_id
+            )),
+            id: entry.video_id.clone(),
+            channel_id: entry.channel_id.clone(),
+            title: entry.title,
+            description: None,
+            tags: Vec::new(),
+            duration_seconds: None,
+            published_at: None,
+            likes_count: None,
+            view_count: None,
+            comments_count: None,
+            refresh_thumbnail: false,
+            added_at: None,
+            fill_only: true,
+        });
+
+        batch.watches.push(ImportedWatch {
+            video_id: entry.video_id,
+            channel_id: entry.channel_id,
+            watched_at: entry.watched_at,
+            watch_duration: ImportedDuration::FromVideoLength,
+        });
+    }
+
+    batch.dedup();
+
+    Ok(batch)
+}
+
+/// Entries that are not a watched video that still exists are `None`
+fn parse_json(contents: &str) -> Result<Vec<Option<Entry>>, String> {
+    let entries = serde_json::from_str::<Vec<JsonEntry>>(contents).map_err(|e| e.to_string())?;
+
+    Ok(entries
+        .into_iter()
+        .map(|entry| {
+            if !entry.details.is_empty() {
+                return None;
+            }
+
+            let video_id = video_id_from_url(entry.title_url.as_deref()?)?;
+            let channel = entry.subtitles.first()?;
+            let channel_id = channel_id_from_url(channel.url.as_deref()?)?;
+            let watched_at = chrono::DateTime::parse_from_rfc3339(&entry.time)
+                .ok()?
+                .timestamp();
+
+            let title = entry
+                .title
+                .strip_prefix("Watched ")
+                .unwrap_or(&entry.title)
+                .to_string();
+
+            Some(Entry {
+                video_id,
+                title,
+                channel_id,
+                channel_name: channel.name.clone(),
+                watched_at,
+            })
+        })
+        .collect())
+}
+
+/// Every entry is an `outer-cell` div, its `body-1` content cell holds the video link, the
+/// channel link and the date separated by `<br>`. Ads carry "From Google Ads" in their caption.
+fn parse_html(contents: &str) -> Vec<Option<Entry>> {
+    contents
+        .split("<div class=\"outer-cell")
+        .skip(1)
+        .map(|cell| {
+            if cell.contains("From Google Ads") {
+                return None;
+            }
+
+            let body = cell.split("mdl-typography--body-1\">").nth(1)?;
+            let body = &body[..body.find("</div>")?];
+
+            let links = html_links(body);
+            let (video_url, title) = links.iter().find(|(href, _)| href.contains("v="))?;
+            let (channel_url, channel_name) =
+                links.iter().find(|(href, _)| href.contains("/channel/"))?;
+
+            let date = body
+                .split("<br>")
+                .map(|part| decode_entities(part).trim().to_string())
+                .filter(|part| !part.is_empty() && !part.contains('<'))
+                .last()?;
+
+            Some(Entry {
+                video_id: video_id_from_url(video_url)?,
+                title: title.clone(),
+                channel_id: channel_id_from_url(channel_url)?,
+                channel_name: channel_name.clone(),
+                watched_at: parse_html_date(&date)?,
+            })
+        })
+        .collect()
+}
+
+/// Returns the decoded `href` and text of every `<a>` element
+fn html_links(html: &str) -> Vec<(String, String)> {
+    html.split("<a href=\"")
+        .skip(1)
+        .filter_map(|part| {
+            let (href, rest) = part.split_once('"')?;
+            let text = &rest[rest.find('>')? + 1..rest.find("</a>")?];
+
+            Some((decode_entities(href), decode_entities(text)))
+        })
+        .collect()
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest.find(';').filter(|&end| end <= 10).and_then(|end| {
+            let c = match &rest[1..end] {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                "nbsp" => ' ',
+                name => {
+                    let code = match name.strip_prefix("#x").or(name.strip_prefix("#X")) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => name.strip_prefix('#')?.parse().ok()?,
+                    };
+
+                    char::from_u32(code)?
+                }
+            };
+
+            Some((c, end))
+        });
+
+        match entity {
+            Some((c, end)) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Dates are written in the locale of the account, e.g. `Jan 5, 2024, 9:03:41 PM CET`.
+/// Unknown time zones are read as UTC.
+fn parse_html_date(date: &str) -> Option<i64> {
+    const FORMATS: [&str; 3] = [
+        "%b %d, %Y, %I:%M:%S %p",
+        "%b %d, %Y, %H:%M:%S",
+        "%d %b %Y, %H:%M:%S",
+    ];
+
+    let date = date.replace(['\u{a0}', '\u{202f}'], " ");
+    let (local, zone) = date.rsplit_once(' ')?;
+
+    let naive = FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(local, format).ok())?;
+
+    let offset = zone_offset(zone).unwrap_or_else(|| {
+        tracing::debug!("Unknown time zone {}, using UTC", zone);
+        0
+    });
+
+    FixedOffset::east_opt(offset)?
+        .from_local_datetime(&naive)
+        .single()
+        .map(|date| date.timestamp())
+}
+
+/// Offset in seconds of a time zone like `GMT+01:00`, `UTC-5` or a common abbreviation
+fn zone_offset(zone: &str) -> Option<i32> {
+    let hours = |h: f32| Some((h * 3600.0) as i32);
+
+    if let Some(offset) = zone
+        .strip_prefix("GMT")
+        .or_else(|| zone.strip_prefix("UTC"))
+    {
+        if offset.is_empty() {
+            return Some(0);
+        }
+
+        let sign = match offset.chars().next()? {
+            '+' => 1,
+            '-' => -1,
+            _ => return None,
+        };
+        let (h, m) = offset[1..].split_once(':').unwrap_or((&offset[1..], "0"));
+
+        return Some(sign * (h.parse::<i32>().ok()? * 3600 + m.parse::<i32>().ok()? * 60));
+    }
+
+    match zone {
+        "Z" | "WET" => hours(0.0),
+        "BST" | "CET" | "WEST" => hours(1.0),
+        "CEST" | "EET" | "SAST" => hours(2.0),
+        "EEST" | "MSK" => hours(3.0),
+        "IST" => hours(5.5),
+        "JST" | "KST" => hours(9.0),
+        "AEST" => hours(10.0),
+        "AEDT" => hours(11.0),
+        "NZST" => hours(12.0),
+        "NZDT" => hours(13.0),
+        "AST" | "EDT" => hours(-4.0),
+        "EST" | "CDT" => hours(-5.0),
+        "CST" | "MDT" => hours(-6.0),
+        "MST" | "PDT" => hours(-7.0),
+        "PST" | "AKDT" => hours(-8.0),
+        "AKST" => hours(-9.0),
+        "HST" => hours(-10.0),
+        _ => None,
+    }
+}
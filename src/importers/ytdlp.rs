@@ -0,0 +1,113 @@
+//! yt-dlp `.info.json` files, written with `--write-info-json`.
+//!
+//! Only channels, videos and tags are imported. View, like and comment counts are left out as
+//! they are as old as the download.
+
+use super::{ImportBatch, Importer};
+use crate::ingest::{ChannelUpsert, VideoUpsert};
+use crate::validation;
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct InfoJson {
+    #[serde(rename = "_type")]
+    kind: Option<String>,
+    id: String,
+    title: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    duration: Option<f64>,
+    #[serde(default)]
+    timestamp: Option<i64>,
+    /// `YYYYMMDD`
+    #[serde(default)]
+    upload_date: Option<String>,
+    #[serde(default)]
+    thumbnail: Option<String>,
+    #[serde(default)]
+    channel_id: Option<String>,
+    #[serde(default)]
+    channel: Option<String>,
+    #[serde(default)]
+    uploader: Option<String>,
+    #[serde(default)]
+    channel_url: Option<String>,
+}
+
+pub struct YtDlp;
+
+impl Importer for YtDlp {
+    fn name(&self) -> &'static str {
+        "ytdlp"
+    }
+
+    fn accepts(&self, file_name: &str) -> bool {
+        file_name.ends_with(".info.json")
+    }
+
+    fn parse(&self, data: &[u8]) -> Result<ImportBatch, String> {
+        let info = serde_json::from_slice::<InfoJson>(data)
+            .map_err(|e| format!("Not a yt-dlp info file: {}", e))?;
+
+        let mut batch = ImportBatch::default();
+
+        // Playlists and channels have their own info files
+        if info.kind.as_deref().is_some_and(|kind| kind != "video") {
+            return Ok(batch);
+        }
+
+        let (true, Some(channel_id)) = (
+            validation::is_video_id(&info.id),
+            info.channel_id.filter(|id| validation::is_channel_id(id)),
+        ) else {
+            batch.skipped += 1;
+            return Ok(batch);
+        };
+
+        let Some(channel_name) = info.channel.or(info.uploader) else {
+            batch.skipped += 1;
+            return Ok(batch);
+        };
+
+        let published_at = info.timestamp.or_else(|| {
+            NaiveDate::parse_from_str(info.upload_date.as_deref()?, "%Y%m%d")
+                .ok()?
+                .and_hms_opt(0, 0, 0)
+                .map(|date| date.and_utc().timestamp())
+        });
+
+        batch.channels.push(ChannelUpsert {
+            id: channel_id.clone(),
+            name: channel_name,
+            url: info.channel_url,
+            avatar_url: None,
+            is_subscribed: None,
+            subscribers_count: None,
+            added_at: None,
+            fill_only: false,
+        });
+
+        batch.videos.push(VideoUpsert {
+            id: info.id,
+            channel_id,
+            title: info.title,
+            description: info.description,
+            thumbnail_url: info.thumbnail,
+            tags: info.tags,
+            duration_seconds: info.duration.map(|duration| duration.round() as i64),
+            published_at,
+            likes_count: None,
+            view_count: None,
+            comments_count: None,
+            refresh_thumbnail: false,
+            added_at: None,
+            fill_only: false,
+        });
+
+        Ok(batch)
+    }
+}
@@ -0,0 +1,496 @@
+use crate::api_prelude::rowid;
+use crate::database::models;
+use crate::jobs;
+use crate::schema;
+use diesel::dsl::{insert_into, update};
+use diesel::prelude::*;
+
+/// Minimum age of the last thumbnail download of a video before a refresh downloads it again,
+/// videos are watched over and over but their thumbnails rarely change
+const THUMBNAIL_REFRESH_SECONDS: i64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Created,
+    Updated,
+    Unchanged,
+}
+
+/// Channel info from the browser extension or an import, `None` keeps the stored value.
+pub struct ChannelUpsert {
+    pub id: String,
+    pub name: String,
+    pub url: Option<String>,
+    pub avatar_url: Option<String>,
+    pub is_subscribed: Option<bool>,
+    pub subscribers_count: Option<i64>,
+    /// When the channel was first seen, only used when the channel is created
+    pub added_at: Option<i64>,
+    /// Only set the values a stored channel is missing, for exports that hold what the channel
+    /// looked like back then
+    pub fill_only: bool,
+}
+
+/// Video info from the browser extension or an import, `None` keeps the stored value.
+///
+/// The metric counts are only stored as a snapshot when all of them are known, tags are added to
+/// the tags already linked to the video.
+pub struct VideoUpsert {
+    pub id: String,
+    pub channel_id: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub thumbnail_url: Option<String>,
+    pub tags: Vec<String>,
+    pub duration_seconds: Option<i64>,
+    pub published_at: Option<i64>,
+    pub likes_count: Option<i64>,
+    pub view_count: Option<i64>,
+    pub comments_count: Option<i64>,
+    /// Download the thumbnail again even if its url did not change, at most once per
+    /// [`THUMBNAIL_REFRESH_SECONDS`]
+    pub refresh_thumbnail: bool,
+    /// When the video was first seen, only used when the video is created
+    pub added_at: Option<i64>,
+    /// Only set the values a stored video is missing, for exports that hold the title and
+    /// thumbnail the video had back then
+    pub fill_only: bool,
+}
+
+/// Inserts or updates a channel.
+///
+/// Changes are recorded by [`record_channel_history`], the avatar is downloaded by a job when the
+/// channel is new or its avatar url changed.
+pub fn upsert_channel(
+    conn: &mut SqliteConnection,
+    payload: &ChannelUpsert,
+) -> QueryResult<Outcome> {
+    use schema::channels::dsl as channels_dsl;
+
+    let existing = channels_dsl::channels
+        .find(&payload.id)
+        .get_result::<models::Channel>(conn)
+        .optional()?;
+
+    let channel = match &existing {
+        Some(existing) if payload.fill_only => models::Channel {
+            avatar_url: existing
+                .avatar_url
+                .clone()
+                .or_else(|| payload.avatar_url.clone()),
+            ..existing.clone()
+        },
+        Some(existing) => models::Channel {
+            name: payload.name.clone(),
+            url: payload.url.clone().unwrap_or_else(|| existing.url.clone()),
+            avatar_url: payload
+                .avatar_url
+                .clone()
+                .or_else(|| existing.avatar_url.clone()),
+            is_subscribed: payload.is_subscribed.unwrap_or(existing.is_subscribed),
+            subscribers_count: payload
+                .subscribers_count
+                .unwrap_or(existing.subscribers_count),
+            ..existing.clone()
+        },
+        None => {
+            let channel = models::Channel::new(models::NewChannelParams {
+                id: payload.id.clone(),
+                name: payload.name.clone(),
+                url: payload
+                    .url
+                    .clone()
+                    .unwrap_or_else(|| format!("https://www.youtube.com/channel/{}", payload.id)),
+                is_subscribed: payload.is_subscribed.unwrap_or(false),
+                subscribers_count: payload.subscribers_count.unwrap_or(0),
+                avatar_url: payload.avatar_url.clone(),
+            });
+
+            models::Channel {
+                added_at: payload.added_at.unwrap_or(channel.added_at),
+                ..channel
+            }
+        }
+    };
+
+    if existing.as_ref() == Some(&channel) {
+        return Ok(Outcome::Unchanged);
+    }
+
+    insert_into(channels_dsl::channels)
+        .values(&channel)
+        .on_conflict(channels_dsl::id)
+        .do_update()
+        .set((
+            channels_dsl::name.eq(&channel.name),
+            channels_dsl::url.eq(&channel.url),
+            channels_dsl::is_subscribed.eq(channel.is_subscribed),
+            channels_dsl::subscribers_count.eq(channel.subscribers_count),
+            channels_dsl::avatar_url.eq(&channel.avatar_url),
+        ))
+        .execute(conn)?;
+
+    record_channel_history(conn, existing.as_ref(), &channel)?;
+
+    if let Some(avatar_url) = &channel.avatar_url
+        && existing
+            .as_ref()
+            .is_none_or(|existing| existing.avatar_url != channel.avatar_url)
+    {
+        jobs::enqueue(
+            conn,
+            models::JobKind::ChannelAvatar,
+            &channel.id,
+            avatar_url,
+        )?;
+    }
+
+    match existing {
+        Some(_) => Ok(Outcome::Updated),
+        None => Ok(Outcome::Created),
+    }
+}
+
+/// Inserts or updates a video and links its tags, the channel must already be saved.
+///
+/// The video metrics are stored as a new snapshot so earlier values are kept, title changes are
+/// recorded by [`record_video_revision`]. Image downloads are queued as jobs that run once the
+/// transaction is committed.
+pub fn upsert_video(conn: &mut SqliteConnection, payload: &VideoUpsert) -> QueryResult<Outcome> {
+    use schema::video_metric_snapshots::dsl as video_metric_snapshots_dsl;
+    use schema::videos::dsl as videos_dsl;
+
+    let existing = videos_dsl::videos
+        .find(&payload.id)
+        .get_result::<models::Video>(conn)
+        .optional()?;
+
+    let video = match &existing {
+        Some(existing) if payload.fill_only => models::Video {
+            thumbnail_url: existing
+                .thumbnail_url
+                .clone()
+                .or_else(|| payload.thumbnail_url.clone()),
+            ..existing.clone()
+        },
+        Some(existing) => models::Video {
+            channel_id: payload.channel_id.clone(),
+            title: payload.title.clone(),
+            description: payload
+                .description
+                .clone()
+                .unwrap_or_else(|| existing.description.clone()),
+            duration_seconds: payload
+                .duration_seconds
+                .unwrap_or(existing.duration_seconds),
+            likes_count: payload.likes_count.unwrap_or(existing.likes_count),
+            view_count: payload.view_count.unwrap_or(existing.view_count),
+            comments_count: payload.comments_count.unwrap_or(existing.comments_count),
+            published_at: payload.published_at.unwrap_or(existing.published_at),
+            thumbnail_url: payload
+                .thumbnail_url
+                .clone()
+                .or_else(|| existing.thumbnail_url.clone()),
+            ..existing.clone()
+        },
+        None => {
+            let video = models::Video::new(models::NewVideoParams {
+                id: payload.id.clone(),
+                channel_id: payload.channel_id.clone(),
+                title: payload.title.clone(),
+                description: payload.description.clone().unwrap_or_default(),
+                duration_seconds: payload.duration_seconds.unwrap_or(0),
+                likes_count: payload.likes_count.unwrap_or(0),
+                view_count: payload.view_count.unwrap_or(0),
+                comments_count: payload.comments_count.unwrap_or(0),
+                published_at: payload.published_at.unwrap_or(0),
+                thumbnail_url: payload.thumbnail_url.clone(),
+            });
+
+            models::Video {
+                added_at: payload.added_at.unwrap_or(video.added_at),
+                ..video
+            }
+        }
+    };
+
+    let changed = existing.as_ref() != Some(&video);
+
+    if changed {
+        insert_into(videos_dsl::videos)
+            .values(&video)
+            .on_conflict(videos_dsl::id)
+            .do_update()
+            .set((
+                videos_dsl::channel_id.eq(&video.channel_id),
+                videos_dsl::title.eq(&video.title),
+                videos_dsl::description.eq(&video.description),
+                videos_dsl::duration_seconds.eq(video.duration_seconds),
+                videos_dsl::view_count.eq(video.view_count),
+                videos_dsl::likes_count.eq(video.likes_count),
+                videos_dsl::comments_count.eq(video.comments_count),
+                videos_dsl::published_at.eq(video.published_at),
+                videos_dsl::thumbnail_url.eq(&video.thumbnail_url),
+            ))
+            .execute(conn)?;
+
+        record_video_revision(conn, existing.as_ref(), &video)?;
+    }
+
+    if let (Some(view_count), Some(likes_count), Some(comments_count)) = (
+        payload.view_count,
+        payload.likes_count,
+        payload.comments_count,
+    ) {
+        insert_into(video_metric_snapshots_dsl::video_metric_snapshots)
+            .values(&models::VideoMetricSnapshot::new(
+                video.id.clone(),
+                view_count,
+                likes_count,
+                comments_count,
+            ))
+            .execute(conn)?;
+    }
+
+    let tags_linked = link_tags(conn, &video.id, &payload.tags)?;
+
+    let thumbnail_url_changed = existing
+        .as_ref()
+        .is_none_or(|existing| existing.thumbnail_url != video.thumbnail_url);
+
+    if let Some(thumbnail_url) = &video.thumbnail_url
+        && (thumbnail_url_changed
+            || (payload.refresh_thumbnail
+                && !jobs::queued_since(
+                    conn,
+                    models::JobKind::VideoThumbnail,
+                    &video.id,
+                    THUMBNAIL_REFRESH_SECONDS,
+                )?))
+    {
+        jobs::enqueue(
+            conn,
+            models::JobKind::VideoThumbnail,
+            &video.id,
+            thumbnail_url,
+        )?;
+    }
+
+    match existing {
+        None => Ok(Outcome::Created),
+        Some(_) if changed || tags_linked => Ok(Outcome::Updated),
+        Some(_) => Ok(Outcome::Unchanged),
+    }
+}
+
+/// Links tags to a video, creating missing tags. Returns whether a new link was added.
+fn link_tags(conn: &mut SqliteConnection, video_id: &str, tags: &[String]) -> QueryResult<bool> {
+    use schema::tags::dsl as tags_dsl;
+    use schema::video_tags::dsl as video_tags_dsl;
+
+    let mut linked = false;
+
+    for tag_name in tags {
+        let tag = match tags_dsl::tags
+            .filter(tags_dsl::name.eq(tag_name))
+            .get_result::<models::Tag>(conn)
+            .optional()?
+        {
+            Some(r) => r,
+            None => {
+                let new_tag = models::Tag::new(tag_name.clone());
+
+                insert_into(tags_dsl::tags)
+                    .values(&new_tag)
+                    .on_conflict_do_nothing()
+                    .execute(conn)?;
+
+                new_tag
+            }
+        };
+
+        let video_tag = models::VideoTags::new(video_id.to_string(), tag.id);
+
+        linked |= insert_into(video_tags_dsl::video_tags)
+            .values(&video_tag)
+            .on_conflict_do_nothing()
+            .execute(conn)?
+            > 0;
+    }
+
+    Ok(linked)
+}
+
+/// Stores a channel snapshot and events for every value of `channel` that differs from the stored
+/// `existing` channel.
+///
+/// A channel seen for the first time gets a snapshot, and a `subscribed` event if it is
+/// subscribed to.
+fn record_channel_history(
+    conn: &mut SqliteConnection,
+    existing: Option<&models::Channel>,
+    channel: &models::Channel,
+) -> QueryResult<()> {
+    use models::ChannelEventKind;
+    use schema::channel_events::dsl as channel_events_dsl;
+    use schema::channel_snapshots::dsl as channel_snapshots_dsl;
+
+    let mut events = Vec::new();
+
+    match existing {
+        Some(existing) => {
+            if existing.name == channel.name
+                && existing.is_subscribed == channel.is_subscribed
+                && existing.subscribers_count == channel.subscribers_count
+            {
+                return Ok(());
+            }
+
+            if existing.name != channel.name {
+                events.push(models::ChannelEvent::new(
+                    channel.id.clone(),
+                    ChannelEventKind::Renamed,
+                    Some(existing.name.clone()),
+                    Some(channel.name.clone()),
+                ));
+            }
+
+            if existing.is_subscribed != channel.is_subscribed {
+                let kind = if channel.is_subscribed {
+                    ChannelEventKind::Subscribed
+                } else {
+                    ChannelEventKind::Unsubscribed
+                };
+
+                events.push(models::ChannelEvent::new(
+                    channel.id.clone(),
+                    kind,
+                    None,
+                    None,
+                ));
+            }
+        }
+        None => {
+            if channel.is_subscribed {
+                events.push(models::ChannelEvent::new(
+                    channel.id.clone(),
+                    ChannelEventKind::Subscribed,
+                    None,
+                    None,
+                ));
+            }
+        }
+    }
+
+    insert_into(channel_snapshots_dsl::channel_snapshots)
+        .values(&models::ChannelSnapshot::new(channel))
+        .execute(conn)?;
+
+    if !events.is_empty() {
+        insert_into(channel_events_dsl::channel_events)
+            .values(&events)
+            .execute(conn)?;
+    }
+
+    Ok(())
+}
+
+/// Stores a new video revision when the video is seen for the first time or its title changed.
+///
+/// The revision keeps the thumbnail of the previous revision, thumbnail changes are recorded by
+/// [`crate::images::save_video_thumbnail`].
+fn record_video_revision(
+    conn: &mut SqliteConnection,
+    existing: Option<&models::Video>,
+    video: &models::Video,
+) -> QueryResult<()> {
+    use schema::video_revisions::dsl as video_revisions_dsl;
+
+    if existing.is_some_and(|existing| existing.title == video.title) {
+        return Ok(());
+    }
+
+    let thumbnail_file = video_revisions_dsl::video_revisions
+        .filter(video_revisions_dsl::video_id.eq(&video.id))
+        .order((
+            video_revisions_dsl::added_at.desc(),
+            rowid("video_revisions").desc(),
+        ))
+        .select(video_revisions_dsl::thumbnail_file)
+        .first::<Option<String>>(conn)
+        .optional()?
+        .flatten();
+
+    insert_into(video_revisions_dsl::video_revisions)
+        .values(&models::VideoRevision::new(
+            video.id.clone(),
+            video.title.clone(),
+            thumbnail_file,
+        ))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Inserts a watch history row for an already saved video and returns the id of the stored row.
+///
+/// A session with a `session_key` that was already saved for the same video is not inserted
+/// again, keys are unique per video like the `watch_history_session_key` index. The stored
+/// session keeps the longer watch duration and the later end date so only new sessions bump
+/// `watch_counter`. Without a key, a session is a duplicate when the same video already has a
+/// session starting at the same time.
+pub fn save_watch_session(
+    conn: &mut SqliteConnection,
+    watch_history: models::WatchHistory,
+) -> QueryResult<(Outcome, String)> {
+    use schema::watch_history::dsl as watch_history_dsl;
+
+    if let Some(session_key) = &watch_history.session_key {
+        let existing = watch_history_dsl::watch_history
+            .filter(watch_history_dsl::session_key.eq(session_key))
+            .filter(watch_history_dsl::video_id.eq(&watch_history.video_id))
+            .first::<models::WatchHistory>(conn)
+            .optional()?;
+
+        if let Some(existing) = existing {
+            let watch_duration_seconds = existing
+                .watch_duration_seconds
+                .max(watch_history.watch_duration_seconds);
+            let session_end_date = existing
+                .session_end_date
+                .max(watch_history.session_end_date);
+
+            if watch_duration_seconds == existing.watch_duration_seconds
+                && session_end_date == existing.session_end_date
+            {
+                return Ok((Outcome::Unchanged, existing.id));
+            }
+
+            update(watch_history_dsl::watch_history.find(&existing.id))
+                .set((
+                    watch_history_dsl::watch_duration_seconds.eq(watch_duration_seconds),
+                    watch_history_dsl::session_end_date.eq(session_end_date),
+                ))
+                .execute(conn)?;
+
+            return Ok((Outcome::Updated, existing.id));
+        }
+    } else {
+        let existing = watch_history_dsl::watch_history
+            .filter(watch_history_dsl::video_id.eq(&watch_history.video_id))
+            .filter(watch_history_dsl::session_start_date.eq(watch_history.session_start_date))
+            .select(watch_history_dsl::id)
+            .first::<String>(conn)
+            .optional()?;
+
+        if let Some(existing_id) = existing {
+            return Ok((Outcome::Unchanged, existing_id));
+        }
+    }
+
+    insert_into(watch_history_dsl::watch_history)
+        .values(&watch_history)
+        .execute(conn)?;
+
+    Ok((Outcome::Created, watch_history.id))
+}
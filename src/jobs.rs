@@ -0,0 +1,333 @@
+use crate::config::ImagesConfig;
+use crate::database::models;
+use crate::images;
+use crate::schema;
+use crate::state::AppState;
+use crate::utils;
+use diesel::dsl::{delete, insert_into, update};
+use diesel::prelude::*;
+use std::collections::HashSet;
+use std::time::Duration;
+
+/// Delay before the first retry, doubled after every failed attempt
+const RETRY_BASE_DELAY_SECONDS: i64 = 30;
+/// How long an idle worker waits before looking for new jobs
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Queues a job, a pending job for the same target only gets its url updated.
+pub fn enqueue(
+    conn: &mut SqliteConnection,
+    kind: models::JobKind,
+    target_id: &str,
+    url: &str,
+) -> QueryResult<()> {
+    use schema::jobs::dsl as jobs_dsl;
+
+    let pending = jobs_dsl::jobs
+        .filter(jobs_dsl::kind.eq(kind.as_str()))
+        .filter(jobs_dsl::target_id.eq(target_id))
+        .filter(jobs_dsl::status.eq(models::JobStatus::Pending.as_str()))
+        .select(jobs_dsl::id)
+        .first::<String>(conn)
+        .optional()?;
+
+    match pending {
+        Some(id) => {
+            update(jobs_dsl::jobs.find(id))
+                .set((
+                    jobs_dsl::url.eq(url),
+                    jobs_dsl::updated_at.eq(utils::unix_now()),
+                ))
+                .execute(conn)?;
+        }
+        None => {
+            insert_into(jobs_dsl::jobs)
+                .values(&models::Job::new(
+                    kind,
+                    target_id.to_string(),
+                    url.to_string(),
+                ))
+                .execute(conn)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether a request may download an image itself. Not while a job for it is pending or running,
+/// which includes a job waiting for its next retry, and not after a job used up its attempts on
+/// the same url.
+pub fn allows_fetch(
+    conn: &mut SqliteConnection,
+    kind: models::JobKind,
+    target_id: &str,
+    url: &str,
+) -> QueryResult<bool> {
+    use schema::jobs::dsl as jobs_dsl;
+
+    let blocking = jobs_dsl::jobs
+        .filter(jobs_dsl::kind.eq(kind.as_str()))
+        .filter(jobs_dsl::target_id.eq(target_id))
+        .filter(
+            jobs_dsl::status
+                .eq_any([
+                    models::JobStatus::Pending.as_str(),
+                    models::JobStatus::Running.as_str(),
+                ])
+                .or(jobs_dsl::status
+                    .eq(models::JobStatus::Failed.as_str())
+                    .and(jobs_dsl::url.eq(url))),
+        )
+        .count()
+        .get_result::<i64>(conn)?;
+
+    Ok(blocking == 0)
+}
+
+/// Ids of the targets of `kind` with a pending or running job, which includes a job waiting for
+/// its next retry
+pub fn queued_targets(
+    conn: &mut SqliteConnection,
+    kind: models::JobKind,
+) -> QueryResult<HashSet<String>> {
+    use schema::jobs::dsl as jobs_dsl;
+
+    let target_ids = jobs_dsl::jobs
+        .filter(jobs_dsl::kind.eq(kind.as_str()))
+        .filter(jobs_dsl::status.eq_any([
+            models::JobStatus::Pending.as_str(),
+            models::JobStatus::Running.as_str(),
+        ]))
+        .select(jobs_dsl::target_id)
+        .load::<String>(conn)?;
+
+    Ok(target_ids.into_iter().collect())
+}
+
+/// Queues the retry of a download that failed outside the workers, the failure counts as the
+/// first attempt of the job.
+pub fn enqueue_retry(
+    conn: &mut SqliteConnection,
+    kind: models::JobKind,
+    target_id: &str,
+    url: &str,
+    error: String,
+) -> QueryResult<()> {
+    use schema::jobs::dsl as jobs_dsl;
+
+    let mut job = models::Job::new(kind, target_id.to_string(), url.to_string());
+    job.attempts = 1;
+    job.last_error = Some(error);
+    job.run_at = utils::unix_now() + RETRY_BASE_DELAY_SECONDS;
+
+    insert_into(jobs_dsl::jobs).values(&job).execute(conn)?;
+
+    Ok(())
+}
+
+/// Whether a job for the target was queued within the last `seconds`, finished jobs count until
+/// they are deleted after the retention period.
+pub fn queued_since(
+    conn: &mut SqliteConnection,
+    kind: models::JobKind,
+    target_id: &str,
+    seconds: i64,
+) -> QueryResult<bool> {
+    use schema::jobs::dsl as jobs_dsl;
+
+    let queued = jobs_dsl::jobs
+        .filter(jobs_dsl::kind.eq(kind.as_str()))
+        .filter(jobs_dsl::target_id.eq(target_id))
+        .filter(jobs_dsl::added_at.ge(utils::unix_now() - seconds))
+        .count()
+        .get_result::<i64>(conn)?;
+
+    Ok(queued > 0)
+}
+
+/// Puts jobs that were still running when the server stopped back in the queue.
+pub fn requeue_interrupted(conn: &mut SqliteConnection) -> QueryResult<usize> {
+    use schema::jobs::dsl as jobs_dsl;
+
+    update(jobs_dsl::jobs.filter(jobs_dsl::status.eq(models::JobStatus::Running.as_str())))
+        .set((
+            jobs_dsl::status.eq(models::JobStatus::Pending.as_str()),
+            jobs_dsl::updated_at.eq(utils::unix_now()),
+        ))
+        .execute(conn)
+}
+
+/// Retry and retention settings of the workers
+#[derive(Clone, Copy)]
+struct Limits {
+    /// Attempts before a job is marked as failed
+    max_attempts: i64,
+    /// Finished jobs are deleted after this many seconds
+    retention_seconds: i64,
+}
+
+pub fn spawn_workers(state: AppState, config: &ImagesConfig) {
+    let limits = Limits {
+        max_attempts: config.max_attempts.value,
+        retention_seconds: config.retention_days.value * 24 * 60 * 60,
+    };
+
+    for worker in 0..config.workers.value {
+        let state = state.clone();
+
+        tokio::spawn(async move { run_worker(state, worker, limits).await });
+    }
+}
+
+async fn run_worker(state: AppState, worker: usize, limits: Limits) {
+    loop {
+        let job = match state
+            .db(|conn| claim_next(conn).map_err(|e| e.to_string()))
+            .await
+        {
+            Ok(job) => job,
+            Err(e) => {
+                tracing::error!("Job worker {} failed to claim a job: {}", worker, e);
+                None
+            }
+        };
+
+        let Some(job) = job else {
+            if let Err(e) = state
+                .db(move |conn| {
+                    purge_done(conn, limits.retention_seconds).map_err(|e| e.to_string())
+                })
+                .await
+            {
+                tracing::error!("Failed to delete finished jobs: {}", e);
+            }
+
+            tokio::time::sleep(POLL_INTERVAL).await;
+            continue;
+        };
+
+        tracing::debug!(
+            "Job worker {} running {} job for {}",
+            worker,
+            job.kind,
+            job.target_id
+        );
+
+        let result = run_job(&state, &job).await;
+
+        if let Err(e) = &result {
+            tracing::warn!(
+                "{} job for {} failed (attempt {}): {}",
+                job.kind,
+                job.target_id,
+                job.attempts + 1,
+                e
+            );
+        }
+
+        let job_id = job.id.clone();
+
+        if let Err(e) = state
+            .db(move |conn| {
+                finish(conn, &job, result, limits.max_attempts).map_err(|e| e.to_string())
+            })
+            .await
+        {
+            tracing::error!("Failed to update job {}: {}", job_id, e);
+        }
+    }
+}
+
+/// Marks the oldest due pending job as running and returns it.
+fn claim_next(conn: &mut SqliteConnection) -> QueryResult<Option<models::Job>> {
+    use schema::jobs::dsl as jobs_dsl;
+
+    let now = utils::unix_now();
+
+    let Some(id) = jobs_dsl::jobs
+        .filter(jobs_dsl::status.eq(models::JobStatus::Pending.as_str()))
+        .filter(jobs_dsl::run_at.le(now))
+        .order(jobs_dsl::run_at.asc())
+        .select(jobs_dsl::id)
+        .first::<String>(conn)
+        .optional()?
+    else {
+        return Ok(None);
+    };
+
+    // Another worker may have claimed the job in the meantime
+    update(
+        jobs_dsl::jobs
+            .find(id)
+            .filter(jobs_dsl::status.eq(models::JobStatus::Pending.as_str())),
+    )
+    .set((
+        jobs_dsl::status.eq(models::JobStatus::Running.as_str()),
+        jobs_dsl::updated_at.eq(now),
+    ))
+    .get_result::<models::Job>(conn)
+    .optional()
+}
+
+async fn run_job(state: &AppState, job: &models::Job) -> Result<(), String> {
+    match models::JobKind::from_str(&job.kind) {
+        Some(models::JobKind::ChannelAvatar) => {
+            let image = images::fetch_image_as_webp(&state.http_client, &job.url).await?;
+
+            images::save_channel_avatar(state, &job.target_id, image).await
+        }
+        Some(models::JobKind::VideoThumbnail) => {
+            let image = images::fetch_image_as_webp(&state.http_client, &job.url).await?;
+
+            images::save_video_thumbnail(state, &job.target_id, image).await
+        }
+        None => Err(format!("Unknown job kind {}", job.kind)),
+    }
+}
+
+/// Stores the result of a job run, failed runs are retried with exponential backoff until
+/// `max_attempts` is reached.
+fn finish(
+    conn: &mut SqliteConnection,
+    job: &models::Job,
+    result: Result<(), String>,
+    max_attempts: i64,
+) -> QueryResult<()> {
+    use schema::jobs::dsl as jobs_dsl;
+
+    let now = utils::unix_now();
+    let attempts = job.attempts + 1;
+
+    let (status, run_at, last_error) = match result {
+        Ok(()) => (models::JobStatus::Done, job.run_at, None),
+        Err(e) if attempts >= max_attempts => (models::JobStatus::Failed, job.run_at, Some(e)),
+        Err(e) => {
+            let delay = RETRY_BASE_DELAY_SECONDS << (attempts - 1).min(10);
+
+            (models::JobStatus::Pending, now + delay, Some(e))
+        }
+    };
+
+    update(jobs_dsl::jobs.find(&job.id))
+        .set((
+            jobs_dsl::status.eq(status.as_str()),
+            jobs_dsl::attempts.eq(attempts),
+            jobs_dsl::last_error.eq(last_error),
+            jobs_dsl::run_at.eq(run_at),
+            jobs_dsl::updated_at.eq(now),
+        ))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+fn purge_done(conn: &mut SqliteConnection, retention_seconds: i64) -> QueryResult<usize> {
+    use schema::jobs::dsl as jobs_dsl;
+
+    delete(
+        jobs_dsl::jobs
+            .filter(jobs_dsl::status.eq(models::JobStatus::Done.as_str()))
+            .filter(jobs_dsl::updated_at.lt(utils::unix_now() - retention_seconds)),
+    )
+    .execute(conn)
+}
@@ -0,0 +1,127 @@
+use crate::api_prelude::*;
+use diesel::prelude::*;
+
+type GetJobsResponse = PaginatedResponse<models::Job>;
+
+#[derive(Deserialize, Debug, utoipa::IntoParams)]
+pub struct GetJobsParams {
+    /// Data list offset
+    offset: Option<i64>,
+    /// Data list limit
+    limit: Option<i64>,
+    /// Only list jobs with specified status (`pending`, `running`, `done` or `failed`)
+    status: Option<String>,
+    /// Only list jobs of specified kind (`channel_avatar` or `video_thumbnail`)
+    kind: Option<String>,
+}
+
+/// Returns background jobs
+///
+/// Lists image download jobs, most recently updated first
+#[utoipa::path(
+    get,
+    path = "/jobs",
+    tag = "Admin",
+    params(
+        GetJobsParams
+    ),
+    responses(
+        (status = OK, description = "List of jobs", body = PaginatedResponse<models::Job>),
+    )
+)]
+pub async fn get_jobs(
+    State(state): State<AppState>,
+    Query(params): Query<GetJobsParams>,
+) -> ApiResult<(StatusCode, Json<GetJobsResponse>)> {
+    state.db(move |conn| load_jobs(conn, params)).await
+}
+
+fn load_jobs(
+    conn: &mut SqliteConnection,
+    params: GetJobsParams,
+) -> ApiResult<(StatusCode, Json<GetJobsResponse>)> {
+    use schema::jobs::dsl as jobs_dsl;
+
+    let filtered = || {
+        let mut query = jobs_dsl::jobs.into_boxed();
+
+        if let Some(status) = &params.status {
+            query = query.filter(jobs_dsl::status.eq(status.clone()));
+        }
+
+        if let Some(kind) = &params.kind {
+            query = query.filter(jobs_dsl::kind.eq(kind.clone()));
+        }
+
+        query
+    };
+
+    let mut query = filtered().order((jobs_dsl::updated_at.desc(), jobs_dsl::id.asc()));
+
+    if let Some(offset) = params.offset {
+        query = query.offset(offset);
+    }
+
+    if let Some(limit) = params.limit {
+        query = query.limit(limit);
+    }
+
+    let list = query.load::<models::Job>(conn).map_err(internal_error)?;
+
+    let total = filtered().count().get_result::<i64>(conn).unwrap_or(0);
+
+    let res = GetJobsResponse::new(list, params.offset, params.limit, total);
+
+    Ok((StatusCode::OK, Json(res)))
+}
+
+/// Retry a background job
+///
+/// Puts a job back in the queue with its attempts reset
+#[utoipa::path(
+    post,
+    path = "/jobs/{id}/retry",
+    tag = "Admin",
+    params(
+        ("id" = String, Path, description = "Job id")
+    ),
+    responses(
+        (status = OK, description = "Job was queued", body = models::Job),
+        (status = NOT_FOUND, description = "Job not found or currently running"),
+    )
+)]
+pub async fn retry_job(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<(StatusCode, Json<models::Job>)> {
+    state.db(move |conn| requeue_job(conn, id)).await
+}
+
+fn requeue_job(
+    conn: &mut SqliteConnection,
+    id: String,
+) -> ApiResult<(StatusCode, Json<models::Job>)> {
+    use schema::jobs::dsl as jobs_dsl;
+
+    let now = utils::unix_now();
+
+    let job = diesel::update(
+        jobs_dsl::jobs
+            .find(&id)
+            .filter(jobs_dsl::status.ne(models::JobStatus::Running.as_str())),
+    )
+    .set((
+        jobs_dsl::status.eq(models::JobStatus::Pending.as_str()),
+        jobs_dsl::attempts.eq(0),
+        jobs_dsl::run_at.eq(now),
+        jobs_dsl::updated_at.eq(now),
+    ))
+    .get_result::<models::Job>(conn)
+    .optional()
+    .map_err(internal_error)?;
+
+    match job {
+        Some(job) => Ok((StatusCode::OK, Json(job))),
+        None => Err((StatusCode::NOT_FOUND, "Job not found".to_string())),
+    }
+}
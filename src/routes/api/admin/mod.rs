@@ -0,0 +1,10 @@
+use crate::state::AppState;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+mod jobs;
+
+pub fn routes() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(jobs::get_jobs))
+        .routes(routes!(jobs::retry_job))
+}
@@ -1,27 +1,81 @@
 use crate::api_prelude::*;
+use crate::filter;
+use crate::search;
+use diesel::expression::SqlLiteral;
 use diesel::prelude::*;
+use diesel::sql_types::BigInt;
+use diesel::sqlite::Sqlite;
 
 type GetChannelsResponse = PaginatedResponse<ChannelWithVideosResponse>;
 
-#[derive(Debug, Deserialize, utoipa::ToSchema)]
+#[derive(utoipa::ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ChannelHistoryResponse {
+    pub channel_id: String,
+    pub snapshots: Vec<models::ChannelSnapshot>,
+    pub events: Vec<models::ChannelEvent>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, utoipa::ToSchema)]
 #[serde(rename_all = "snake_case")]
 enum SortBy {
     Name,
     IsSubscribed,
     SubscribersCount,
+    AddedAt,
+    /// Seconds watched over all watch history of the channel
+    WatchTime,
+    /// Videos of the channel
+    VideosCount,
+}
+
+impl SortKey for SortBy {
+    fn name(&self) -> &'static str {
+        match self {
+            SortBy::Name => "name",
+            SortBy::IsSubscribed => "is_subscribed",
+            SortBy::SubscribersCount => "subscribers_count",
+            SortBy::AddedAt => "added_at",
+            SortBy::WatchTime => "watch_time",
+            SortBy::VideosCount => "videos_count",
+        }
+    }
+}
+
+/// Seconds watched over all watch history of each channel
+fn watch_time() -> SqlLiteral<BigInt> {
+    diesel::dsl::sql::<BigInt>(
+        "(SELECT COALESCE(SUM(watch_history.watch_duration_seconds), 0) FROM watch_history
+        WHERE watch_history.channel_id = channels.id)",
+    )
+}
+
+/// Number of videos of each channel
+fn videos_count() -> SqlLiteral<BigInt> {
+    diesel::dsl::sql::<BigInt>(
+        "(SELECT COUNT(*) FROM videos WHERE videos.channel_id = channels.id)",
+    )
 }
 
 #[derive(Deserialize, Debug, utoipa::IntoParams)]
 pub struct GetChannelsParams {
-    /// Sort order
+    /// Comma separated sort keys, most significant first, a key with a leading `-` sorts in
+    /// descending order, for example `-watch_time,name`. Keys are the `sort_by` fields, `name`
+    /// by default. Can not be combined with `sort_by` and `sort_order`.
+    sort: Option<String>,
+    /// Sort order, `asc` by default
     sort_order: Option<SortOrder>,
-    /// Sort by specified field
+    /// Sort by specified field, `name` by default
     sort_by: Option<SortBy>,
     /// Data list offset
     offset: Option<i64>,
     /// Data list limit
     limit: Option<i64>,
-    /// Search channels by name
+    /// Filter expression, for example `subscribers_count>1000 and (is_subscribed=true or
+    /// tag:music)`. Comparisons use `=`, `!=`, `>`, `>=`, `<`, `<=` or `~` (contains) and are
+    /// combined with `and`, `or`, `not` and parentheses.
+    filter: Option<String>,
+    /// Search channels by name, see `/api/search` for the query syntax
     search: Option<String>,
     /// List only channels that are subscribed to
     is_subscribed: Option<bool>,
@@ -41,35 +95,37 @@ pub struct GetChannelsParams {
     path = "/channels",
     tag = "Channel",
     params(
-        GetChannelsParams
+        GetChannelsParams,
+        TagFilters
     ),
     responses(
         (status = OK, description = "List of channels", body = PaginatedResponse<ChannelWithVideosResponse>),
+        (status = BAD_REQUEST, description = "Sort or filter is invalid"),
     )
 )]
 pub async fn get_channels(
     State(state): State<AppState>,
     Query(params): Query<GetChannelsParams>,
+    Query(tag_filters): Query<TagFilters>,
 ) -> ApiResult<(StatusCode, Json<GetChannelsResponse>)> {
+    state
+        .db(move |conn| load_channels(conn, params, tag_filters))
+        .await
+}
+
+/// Channels that match the filters of `params` and `tag_filters`, the page and its total are both
+/// built from it
+fn filtered_channels<'a>(
+    params: &'a GetChannelsParams,
+    tag_filters: &TagFilters,
+) -> ApiResult<schema::channels::BoxedQuery<'a, Sqlite>> {
     use schema::channels::dsl as channels_dsl;
-    use schema::tags::dsl as tags_dsl;
-    use schema::video_tags::dsl as video_tags_dsl;
     use schema::videos::dsl as videos_dsl;
 
-    let mut conn = state.pool.get().map_err(internal_error)?;
-
     let mut query = channels_dsl::channels.into_boxed();
 
-    if let Some(offset) = params.offset {
-        query = query.offset(offset);
-    }
-
-    if let Some(limit) = params.limit {
-        query = query.limit(limit);
-    }
-
-    if let Some(search) = params.search {
-        query = query.filter(channels_dsl::name.like(format!("%{search}%")));
+    if let Some(fts_query) = params.search.as_deref().and_then(search::fts_query) {
+        query = query.filter(search::matching("channels", fts_query));
     }
 
     if let Some(is_subscribed) = params.is_subscribed {
@@ -88,37 +144,80 @@ pub async fn get_channels(
         query = query.filter(channels_dsl::subscribers_count.lt(max_subscribers_count));
     }
 
-    if let Some(sort_by) = params.sort_by {
-        query = match sort_by {
-            SortBy::Name => apply_sort!(query, channels_dsl::name, params.sort_order),
-            SortBy::IsSubscribed => {
-                apply_sort!(query, channels_dsl::is_subscribed, params.sort_order)
-            }
-            SortBy::SubscribersCount => {
-                apply_sort!(query, channels_dsl::subscribers_count, params.sort_order)
-            }
+    if !tag_filters.is_empty() {
+        query = query.filter(channels_dsl::id.eq_any(filter_tags!(
+            videos_dsl::videos
+                .select(videos_dsl::channel_id)
+                .into_boxed(),
+            videos_dsl::id,
+            tag_filters
+        )));
+    }
+
+    if let Some(input) = &params.filter {
+        query = query.filter(filter::channels(input)?);
+    }
+
+    Ok(query)
+}
+
+fn load_channels(
+    conn: &mut SqliteConnection,
+    params: GetChannelsParams,
+    tag_filters: TagFilters,
+) -> ApiResult<(StatusCode, Json<GetChannelsResponse>)> {
+    use schema::channels::dsl as channels_dsl;
+
+    let sort = Sort::new(
+        params.sort.as_deref(),
+        params.sort_by,
+        params.sort_order,
+        (SortBy::Name, SortOrder::Asc),
+    )?;
+
+    let mut query = filtered_channels(&params, &tag_filters)?;
+
+    if let Some(offset) = params.offset {
+        query = query.offset(offset);
+    }
+
+    if let Some(limit) = params.limit {
+        query = query.limit(limit);
+    }
+
+    for &(key, order) in &sort.keys {
+        query = match key {
+            SortBy::Name => apply_sort!(query, channels_dsl::name, order),
+            SortBy::IsSubscribed => apply_sort!(query, channels_dsl::is_subscribed, order),
+            SortBy::SubscribersCount => apply_sort!(query, channels_dsl::subscribers_count, order),
+            SortBy::AddedAt => apply_sort!(query, channels_dsl::added_at, order),
+            SortBy::WatchTime => apply_sort!(query, watch_time(), order),
+            SortBy::VideosCount => apply_sort!(query, videos_count(), order),
         };
     }
 
+    query = apply_sort!(query, channels_dsl::id, sort.id_order());
+
     let data = query
-        .load::<models::Channel>(&mut conn)
+        .load::<models::Channel>(conn)
         .map_err(internal_error)?;
 
-    let list: Vec<ChannelWithVideosResponse> = data
+    let videos = models::Video::belonging_to(&data)
+        .load::<models::Video>(conn)
+        .map_err(internal_error)?;
+
+    let mut tags =
+        load_video_tags(conn, videos.iter().map(|video| &video.id)).map_err(internal_error)?;
+
+    let list: Vec<ChannelWithVideosResponse> = videos
+        .grouped_by(&data)
         .into_iter()
-        .map(|channel| {
-            let videos: Vec<VideoResponse> = videos_dsl::videos
-                .filter(videos_dsl::channel_id.eq(&channel.id))
-                .load::<models::Video>(&mut conn)
-                .unwrap_or(Vec::new())
+        .zip(data)
+        .map(|(videos, channel)| {
+            let videos: Vec<VideoResponse> = videos
                 .into_iter()
                 .map(|video| {
-                    let tags = tags_dsl::tags
-                        .inner_join(video_tags_dsl::video_tags)
-                        .filter(video_tags_dsl::video_id.eq(&video.id))
-                        .select(tags_dsl::name)
-                        .load(&mut conn)
-                        .unwrap_or(Vec::new());
+                    let tags = tags.remove(&video.id).unwrap_or_default();
 
                     VideoResponse::new(video, tags, None)
                 })
@@ -130,10 +229,10 @@ pub async fn get_channels(
         })
         .collect();
 
-    let total = channels_dsl::channels
+    let total = filtered_channels(&params, &tag_filters)?
         .count()
-        .get_result::<i64>(&mut conn)
-        .unwrap_or(0);
+        .get_result::<i64>(conn)
+        .map_err(internal_error)?;
 
     let res = GetChannelsResponse::new(list, params.offset, params.limit, total);
 
@@ -158,30 +257,31 @@ pub async fn get_channel(
     State(state): State<AppState>,
     Path(id): Path<String>,
 ) -> ApiResult<(StatusCode, Json<ChannelWithVideosResponse>)> {
-    use schema::channels::dsl as channels_dsl;
-    use schema::tags::dsl as tags_dsl;
-    use schema::video_tags::dsl as video_tags_dsl;
-    use schema::videos::dsl as videos_dsl;
+    state.db(move |conn| load_channel(conn, id)).await
+}
 
-    let mut conn = state.pool.get().map_err(internal_error)?;
+fn load_channel(
+    conn: &mut SqliteConnection,
+    id: String,
+) -> ApiResult<(StatusCode, Json<ChannelWithVideosResponse>)> {
+    use schema::channels::dsl as channels_dsl;
 
     let channel = channels_dsl::channels
         .filter(channels_dsl::id.eq(id))
-        .get_result::<models::Channel>(&mut conn)
+        .get_result::<models::Channel>(conn)
         .map_err(internal_error)?;
 
-    let videos: Vec<VideoResponse> = videos_dsl::videos
-        .filter(videos_dsl::channel_id.eq(&channel.id))
-        .load::<models::Video>(&mut conn)
-        .unwrap_or(Vec::new())
+    let videos = models::Video::belonging_to(&channel)
+        .load::<models::Video>(conn)
+        .map_err(internal_error)?;
+
+    let mut tags =
+        load_video_tags(conn, videos.iter().map(|video| &video.id)).map_err(internal_error)?;
+
+    let videos: Vec<VideoResponse> = videos
         .into_iter()
         .map(|video| {
-            let tags = tags_dsl::tags
-                .inner_join(video_tags_dsl::video_tags)
-                .filter(video_tags_dsl::video_id.eq(&video.id))
-                .select(tags_dsl::name)
-                .load(&mut conn)
-                .unwrap_or(Vec::new());
+            let tags = tags.remove(&video.id).unwrap_or_default();
 
             VideoResponse::new(video, tags, None)
         })
@@ -193,3 +293,97 @@ pub async fn get_channel(
 
     Ok((StatusCode::OK, Json(response)))
 }
+
+/// Returns channel history
+///
+/// Lists every recorded change of the channel name, subscription and subscribers count, oldest
+/// first
+#[utoipa::path(
+    get,
+    path = "/channels/{id}/history",
+    tag = "Channel",
+    params(
+        ("id" = String, Path, description = "Channel id")
+    ),
+    responses(
+        (status = OK, description = "Channel history", body = ChannelHistoryResponse),
+        (status = NOT_FOUND, description = "Channel not found"),
+    )
+)]
+pub async fn get_channel_history(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<(StatusCode, Json<ChannelHistoryResponse>)> {
+    state.db(move |conn| load_channel_history(conn, id)).await
+}
+
+fn load_channel_history(
+    conn: &mut SqliteConnection,
+    id: String,
+) -> ApiResult<(StatusCode, Json<ChannelHistoryResponse>)> {
+    use schema::channel_events::dsl as channel_events_dsl;
+    use schema::channel_snapshots::dsl as channel_snapshots_dsl;
+    use schema::channels::dsl as channels_dsl;
+
+    let Some(channel) = channels_dsl::channels
+        .find(&id)
+        .get_result::<models::Channel>(conn)
+        .optional()
+        .map_err(internal_error)?
+    else {
+        return Err((StatusCode::NOT_FOUND, "Channel not found".to_string()));
+    };
+
+    let snapshots = models::ChannelSnapshot::belonging_to(&channel)
+        .order((
+            channel_snapshots_dsl::added_at.asc(),
+            rowid("channel_snapshots").asc(),
+        ))
+        .load::<models::ChannelSnapshot>(conn)
+        .map_err(internal_error)?;
+
+    let events = models::ChannelEvent::belonging_to(&channel)
+        .order((
+            channel_events_dsl::added_at.asc(),
+            rowid("channel_events").asc(),
+        ))
+        .load::<models::ChannelEvent>(conn)
+        .map_err(internal_error)?;
+
+    let response = ChannelHistoryResponse {
+        channel_id: channel.id,
+        snapshots,
+        events,
+    };
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing;
+
+    fn load(conn: &mut SqliteConnection, limit: i64) -> (usize, usize) {
+        let params = serde_json::from_value(serde_json::json!({ "limit": limit })).unwrap();
+        let tag_filters = serde_json::from_value(serde_json::json!({})).unwrap();
+
+        let (result, queries) =
+            testing::count_queries(conn, |conn| load_channels(conn, params, tag_filters));
+        let (_, Json(response)) = result.unwrap();
+
+        (response.data.len(), queries)
+    }
+
+    #[test]
+    fn queries_do_not_grow_with_the_page() {
+        let mut conn = testing::connection();
+        testing::seed(&mut conn, 120);
+
+        let (one, one_queries) = load(&mut conn, 1);
+        let (hundred, hundred_queries) = load(&mut conn, 100);
+
+        assert_eq!((one, hundred), (1, 100));
+        assert_eq!(one_queries, hundred_queries);
+    }
+}
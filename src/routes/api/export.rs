@@ -0,0 +1,77 @@
+use crate::api_prelude::*;
+use crate::export::{self, ExportFormat};
+use crate::routes::api::WatchHistoryFilters;
+use tokio_util::io::{ReaderStream, SyncIoBridge};
+
+/// Bytes buffered between the export and the response body
+const STREAM_BUFFER_BYTES: usize = 64 * 1024;
+
+#[derive(Deserialize, Debug, utoipa::IntoParams)]
+pub struct ExportParams {
+    /// Export format, `json` by default
+    format: Option<ExportFormat>,
+}
+
+/// Export channels, videos, tags and watch history
+///
+/// The export is streamed as it is read from the database.
+///
+/// | Format | Content |
+/// |---|---|
+/// | `json` | One document with `channels`, `videos`, `tags` and `watch_history` lists |
+/// | `ndjson` | One record per line, e.g. `{"channel": {...}}` |
+/// | `csv` | A zip archive with one CSV file per table |
+///
+/// Records use the same shapes as the other endpoints. Filters select watch history records,
+/// the channels, videos and tags of the selected records are exported with them. Without
+/// filters everything is exported.
+///
+/// The `json` export can be imported again with the `chianti` import source.
+#[utoipa::path(
+    get,
+    path = "/export",
+    tag = "Export",
+    params(
+        ExportParams,
+        WatchHistoryFilters
+    ),
+    responses(
+        (status = OK, description = "Export file", content(
+            (Vec<u8> = "application/json"),
+            (Vec<u8> = "application/x-ndjson"),
+            (Vec<u8> = "application/zip"),
+        )),
+    )
+)]
+pub async fn export(
+    State(state): State<AppState>,
+    Query(params): Query<ExportParams>,
+    Query(filters): Query<WatchHistoryFilters>,
+) -> ApiResult<Response> {
+    let format = params.format.unwrap_or_default();
+    let (reader, writer) = tokio::io::duplex(STREAM_BUFFER_BYTES);
+
+    // The export is written while the response streams, failures can only be logged
+    tokio::spawn(async move {
+        let result = state
+            .db(move |conn| {
+                let out = std::io::BufWriter::new(SyncIoBridge::new(writer));
+
+                export::write(conn, format, &filters, out)
+            })
+            .await;
+
+        if let Err(e) = result {
+            tracing::error!("{}", e);
+        }
+    });
+
+    Response::builder()
+        .header("Content-Type", format.content_type())
+        .header(
+            "Content-Disposition",
+            format!("attachment; filename=\"{}\"", format.file_name()),
+        )
+        .body(Body::from_stream(ReaderStream::new(reader)))
+        .map_err(internal_error)
+}
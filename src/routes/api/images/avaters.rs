@@ -1,4 +1,5 @@
 use crate::api_prelude::*;
+use crate::images;
 
 /// Returns channel avater
 #[utoipa::path(
@@ -9,8 +10,8 @@ use crate::api_prelude::*;
         ("id" = String, Path, description = "Channel id")
     ),
     responses(
-        (status = OK, description = "Image was found on disk", content_type = "image/webp", body = Vec<u8>),
-        (status = NOT_FOUND, description = "Image not found on disk"),
+        (status = OK, description = "Image was found on disk or fetched from its source url", content_type = "image/webp", body = Vec<u8>),
+        (status = NOT_FOUND, description = "Image not found on disk and could not be fetched, or its download is queued or failed before"),
     )
 )]
 pub async fn get_channel_avater(
@@ -21,6 +22,15 @@ pub async fn get_channel_avater(
         .channel_avaters_dir
         .join(utils::build_avater_cache_image_filename(&id));
 
+    if !tokio::fs::try_exists(&avater_file_path)
+        .await
+        .unwrap_or(false)
+        && let Err(e) = images::fetch_missing_channel_avatar(&state, &id).await
+    {
+        tracing::debug!("Failed to fetch missing image {}: {}", id, e);
+        return Err((StatusCode::NOT_FOUND, "Image not found".to_string()));
+    }
+
     let Ok(file) = tokio::fs::File::open(&avater_file_path).await else {
         return Err((StatusCode::NOT_FOUND, "Image not found on disk".to_string()));
     };
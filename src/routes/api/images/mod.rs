@@ -8,4 +8,5 @@ pub fn routes() -> OpenApiRouter<AppState> {
     OpenApiRouter::new()
         .routes(routes!(avaters::get_channel_avater))
         .routes(routes!(thumbnails::get_video_thumbnail))
+        .routes(routes!(thumbnails::get_video_thumbnail_revision))
 }
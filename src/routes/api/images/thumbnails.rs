@@ -1,4 +1,6 @@
 use crate::api_prelude::*;
+use crate::images;
+use diesel::prelude::*;
 
 /// Returns video thumbnail
 #[utoipa::path(
@@ -9,8 +11,8 @@ use crate::api_prelude::*;
         ("id" = String, Path, description = "Video id")
     ),
     responses(
-        (status = OK, description = "Image was found on disk", content_type = "image/webp", body = Vec<u8>),
-        (status = NOT_FOUND, description = "Image not found on disk"),
+        (status = OK, description = "Image was found on disk or fetched from its source url", content_type = "image/webp", body = Vec<u8>),
+        (status = NOT_FOUND, description = "Image not found on disk and could not be fetched, or its download is queued or failed before"),
     )
 )]
 pub async fn get_video_thumbnail(
@@ -21,6 +23,70 @@ pub async fn get_video_thumbnail(
         .video_thumbnails_dir
         .join(utils::build_thumbnail_cache_image_filename(&id));
 
+    if !tokio::fs::try_exists(&thumbnail_file_path)
+        .await
+        .unwrap_or(false)
+        && let Err(e) = images::fetch_missing_video_thumbnail(&state, &id).await
+    {
+        tracing::debug!("Failed to fetch missing image {}: {}", id, e);
+        return Err((StatusCode::NOT_FOUND, "Image not found".to_string()));
+    }
+
+    let Ok(file) = tokio::fs::File::open(&thumbnail_file_path).await else {
+        return Err((StatusCode::NOT_FOUND, "Image not found on disk".to_string()));
+    };
+
+    let content_type = mime_guess::from_path(&thumbnail_file_path)
+        .first_raw()
+        .unwrap_or("application/octet-stream");
+
+    let stream = tokio_util::io::ReaderStream::new(file);
+    let body = Body::from_stream(stream);
+
+    Response::builder()
+        .header("Content-Type", content_type)
+        .body(body)
+        .map_err(internal_error)
+}
+
+/// Returns video thumbnail revision
+#[utoipa::path(
+    get,
+    path = "/thumbnails/{id}/revisions/{revision_id}",
+    tag = "Images",
+    params(
+        ("id" = String, Path, description = "Video id"),
+        ("revision_id" = String, Path, description = "Video revision id")
+    ),
+    responses(
+        (status = OK, description = "Image was found on disk", content_type = "image/webp", body = Vec<u8>),
+        (status = NOT_FOUND, description = "Image not found on disk"),
+    )
+)]
+pub async fn get_video_thumbnail_revision(
+    State(state): State<AppState>,
+    Path((id, revision_id)): Path<(String, String)>,
+) -> ApiResult<impl IntoResponse> {
+    use schema::video_revisions::dsl as video_revisions_dsl;
+
+    let Some(thumbnail_file) = state
+        .db(move |conn| {
+            video_revisions_dsl::video_revisions
+                .filter(video_revisions_dsl::id.eq(&revision_id))
+                .filter(video_revisions_dsl::video_id.eq(&id))
+                .select(video_revisions_dsl::thumbnail_file)
+                .first::<Option<String>>(conn)
+                .optional()
+                .map_err(internal_error)
+        })
+        .await?
+        .flatten()
+    else {
+        return Err((StatusCode::NOT_FOUND, "Image not found on disk".to_string()));
+    };
+
+    let thumbnail_file_path = state.video_thumbnail_revisions_dir.join(thumbnail_file);
+
     let Ok(file) = tokio::fs::File::open(&thumbnail_file_path).await else {
         return Err((StatusCode::NOT_FOUND, "Image not found on disk".to_string()));
     };
@@ -0,0 +1,15 @@
+use crate::state::AppState;
+use axum::extract::DefaultBodyLimit;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+mod upload;
+
+/// Largest accepted upload, all files are held in memory while they are imported. Watch
+/// histories of many years are tens of megabytes, larger exports can be imported from the CLI
+const MAX_UPLOAD_BYTES: usize = 128 * 1024 * 1024;
+
+pub fn routes() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(upload::import_file))
+        .layer(DefaultBodyLimit::max(MAX_UPLOAD_BYTES))
+}
@@ -0,0 +1,111 @@
+use crate::api_prelude::*;
+use crate::importers::{self, ImportSummary};
+use axum::extract::Multipart;
+
+#[derive(utoipa::ToSchema)]
+#[allow(dead_code)]
+pub struct ImportFileForm {
+    /// Export files of the service
+    #[schema(value_type = Vec<String>, format = Binary)]
+    file: Vec<Vec<u8>>,
+}
+
+#[derive(Deserialize, Debug, utoipa::IntoParams)]
+pub struct ImportFileParams {
+    /// Report what the import would create or update without saving anything
+    dry_run: Option<bool>,
+}
+
+/// Import watch history from another service
+///
+/// Creates channels, videos and watch history records and marks subscribed channels from an
+/// export file, importing the same file again does not create duplicates.
+///
+/// | Source | File |
+/// |---|---|
+/// | `takeout` | Google Takeout `watch-history.json` or `watch-history.html` |
+/// | `freetube` | FreeTube `history.db` and/or `profiles.db` |
+/// | `newpipe` | NewPipe export zip or its `newpipe.db` |
+/// | `invidious` | Invidious JSON export, only subscriptions to saved channels are imported |
+/// | `ytdlp` | yt-dlp `.info.json` files, only channels, videos and tags are imported |
+/// | `chianti` | `json` or `ndjson` export of `/api/export` |
+///
+/// The `file` field can be repeated to import several files at once.
+///
+/// Exports without watch durations are saved with a duration estimated from the length of the
+/// video and the time of the next watched video, see `duration_source`.
+#[utoipa::path(
+    post,
+    path = "/{source}",
+    tag = "Import",
+    params(
+        ("source" = String, Path, description = "Service the file was exported from"),
+        ImportFileParams
+    ),
+    request_body(content_type = "multipart/form-data", content = ImportFileForm),
+    responses(
+        (status = OK, description = "File was imported", body = ImportSummary),
+        (status = BAD_REQUEST, description = "File is missing or could not be read"),
+        (status = NOT_FOUND, description = "Unknown source"),
+        (status = PAYLOAD_TOO_LARGE, description = "Files are larger than 128 MiB together"),
+    )
+)]
+pub async fn import_file(
+    State(state): State<AppState>,
+    Path(source): Path<String>,
+    Query(params): Query<ImportFileParams>,
+    mut multipart: Multipart,
+) -> ApiResult<(StatusCode, Json<ImportSummary>)> {
+    let Some(importer) = importers::find(&source) else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!(
+                "Unknown source, expected one of {}",
+                importers::names().join(", ")
+            ),
+        ));
+    };
+
+    let mut files = Vec::new();
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| (e.status(), e.body_text()))?
+    {
+        if field.name() == Some("file") {
+            files.push(
+                field
+                    .bytes()
+                    .await
+                    .map_err(|e| (e.status(), e.body_text()))?,
+            );
+        }
+    }
+
+    if files.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Missing file field".to_string()));
+    }
+
+    let dry_run = params.dry_run.unwrap_or(false);
+
+    let summary = state
+        .db(move |conn| {
+            let mut batch = importers::ImportBatch::default();
+
+            for data in files {
+                batch.merge(
+                    importer
+                        .parse(&data)
+                        .map_err(|e| (StatusCode::BAD_REQUEST, e))?,
+                );
+            }
+
+            batch.dedup();
+
+            importers::apply(conn, &batch, dry_run).map_err(internal_error)
+        })
+        .await?;
+
+    Ok((StatusCode::OK, Json(summary)))
+}
@@ -1,13 +1,19 @@
+mod admin;
 mod channels;
+mod export;
 mod images;
+mod imports;
 mod ping;
+mod search;
 mod statistics;
 mod tags;
 mod videos;
 mod watch_history;
 
 use crate::state::AppState;
+pub use statistics::load_overview;
 use utoipa_axum::{router::OpenApiRouter, routes};
+pub use watch_history::{WatchHistoryFilters, finalize_stale_watch_sessions};
 
 pub fn routes() -> OpenApiRouter<AppState> {
     OpenApiRouter::new()
@@ -16,12 +22,23 @@ pub fn routes() -> OpenApiRouter<AppState> {
             watch_history::get_watch_history,
             watch_history::create_watch_history
         ))
+        .routes(routes!(watch_history::start_watch_session))
+        .routes(routes!(watch_history::watch_session_heartbeat))
+        .routes(routes!(watch_history::end_watch_session))
         .routes(routes!(videos::get_videos))
         .routes(routes!(videos::get_video))
+        .routes(routes!(videos::get_video_metrics))
+        .routes(routes!(videos::get_video_progress))
+        .routes(routes!(videos::get_video_revisions))
         .routes(routes!(channels::get_channels))
         .routes(routes!(channels::get_channel))
+        .routes(routes!(channels::get_channel_history))
         .routes(routes!(tags::get_tags))
         .routes(routes!(tags::get_tag))
+        .routes(routes!(search::search))
+        .routes(routes!(export::export))
         .nest("/statistics", statistics::routes())
         .nest("/images", images::routes())
+        .nest("/admin", admin::routes())
+        .nest("/import", imports::routes())
 }
@@ -0,0 +1,206 @@
+use crate::api_prelude::*;
+use crate::search;
+use diesel::prelude::*;
+use std::collections::HashMap;
+
+/// Matches listed per kind by default
+const DEFAULT_LIMIT: i64 = 20;
+const MAX_LIMIT: i64 = 100;
+
+#[derive(Deserialize, Debug, utoipa::IntoParams)]
+pub struct SearchParams {
+    /// Words that must all match, `"a phrase"` matches the words in order and `word*` matches
+    /// words that start with `word`
+    q: String,
+    /// Matches listed per kind, 20 by default and at most 100
+    limit: Option<i64>,
+}
+
+/// Matched text with `<mark>` around each match, the rest of the text is HTML escaped
+#[derive(utoipa::ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct VideoHighlight {
+    title: String,
+    /// Part of the description around the matches
+    description: String,
+    /// Tag names separated by `, `
+    tags: String,
+}
+
+#[derive(utoipa::ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct VideoSearchMatch {
+    /// Higher is a better match
+    score: f64,
+    highlight: VideoHighlight,
+    video: VideoResponse,
+}
+
+#[derive(utoipa::ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ChannelSearchMatch {
+    /// Higher is a better match
+    score: f64,
+    /// Channel name with `<mark>` around each match, the rest of the name is HTML escaped
+    highlight: String,
+    channel: ChannelResponse,
+}
+
+#[derive(utoipa::ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct TagSearchMatch {
+    /// Higher is a better match
+    score: f64,
+    /// Tag name with `<mark>` around each match, the rest of the name is HTML escaped
+    highlight: String,
+    tag: models::Tag,
+}
+
+/// Best matches of each kind first
+#[derive(utoipa::ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SearchResponse {
+    videos: Vec<VideoSearchMatch>,
+    channels: Vec<ChannelSearchMatch>,
+    tags: Vec<TagSearchMatch>,
+}
+
+/// Searches videos, channels and tags
+///
+/// This endpoint is used to search video titles, descriptions and tags, channel names and tag
+/// names, ranked by relevance
+#[utoipa::path(
+    get,
+    path = "/search",
+    tag = "Search",
+    params(
+        SearchParams
+    ),
+    responses(
+        (status = OK, description = "Ranked matches", body = SearchResponse),
+        (status = BAD_REQUEST, description = "Query has nothing to search for"),
+    )
+)]
+pub async fn search(
+    State(state): State<AppState>,
+    Query(params): Query<SearchParams>,
+) -> ApiResult<(StatusCode, Json<SearchResponse>)> {
+    state.db(move |conn| load_search(conn, params)).await
+}
+
+fn load_search(
+    conn: &mut SqliteConnection,
+    params: SearchParams,
+) -> ApiResult<(StatusCode, Json<SearchResponse>)> {
+    let Some(query) = search::fts_query(&params.q) else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Query has nothing to search for".to_string(),
+        ));
+    };
+
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    let res = SearchResponse {
+        videos: search_videos(conn, &query, limit).map_err(internal_error)?,
+        channels: search_channels(conn, &query, limit).map_err(internal_error)?,
+        tags: search_tags(conn, &query, limit).map_err(internal_error)?,
+    };
+
+    Ok((StatusCode::OK, Json(res)))
+}
+
+fn search_videos(
+    conn: &mut SqliteConnection,
+    query: &str,
+    limit: i64,
+) -> QueryResult<Vec<VideoSearchMatch>> {
+    use schema::channels::dsl as channels_dsl;
+    use schema::videos::dsl as videos_dsl;
+
+    let matches = search::search_videos(conn, query, limit)?;
+
+    let mut videos = videos_dsl::videos
+        .inner_join(channels_dsl::channels)
+        .filter(videos_dsl::id.eq_any(matches.iter().map(|m| &m.id)))
+        .load::<(models::Video, models::Channel)>(conn)?
+        .into_iter()
+        .map(|(video, channel)| (video.id.clone(), (video, channel)))
+        .collect::<HashMap<String, (models::Video, models::Channel)>>();
+
+    let mut tags = load_video_tags(conn, videos.keys())?;
+
+    Ok(matches
+        .into_iter()
+        .filter_map(|m| {
+            let (video, channel) = videos.remove(&m.id)?;
+            let tags = tags.remove(&video.id).unwrap_or_default();
+
+            Some(VideoSearchMatch {
+                score: m.score,
+                highlight: VideoHighlight {
+                    title: search::highlighted(&m.title),
+                    description: search::highlighted(&m.description),
+                    tags: search::highlighted(&m.tags),
+                },
+                video: VideoResponse::new(video, tags, Some(ChannelResponse::new(channel))),
+            })
+        })
+        .collect())
+}
+
+fn search_channels(
+    conn: &mut SqliteConnection,
+    query: &str,
+    limit: i64,
+) -> QueryResult<Vec<ChannelSearchMatch>> {
+    use schema::channels::dsl as channels_dsl;
+
+    let matches = search::search_names(conn, "channels_fts", query, limit)?;
+
+    let mut channels = channels_dsl::channels
+        .filter(channels_dsl::id.eq_any(matches.iter().map(|m| &m.id)))
+        .load::<models::Channel>(conn)?
+        .into_iter()
+        .map(|channel| (channel.id.clone(), channel))
+        .collect::<HashMap<String, models::Channel>>();
+
+    Ok(matches
+        .into_iter()
+        .filter_map(|m| {
+            Some(ChannelSearchMatch {
+                score: m.score,
+                highlight: search::highlighted(&m.name),
+                channel: ChannelResponse::new(channels.remove(&m.id)?),
+            })
+        })
+        .collect())
+}
+
+fn search_tags(
+    conn: &mut SqliteConnection,
+    query: &str,
+    limit: i64,
+) -> QueryResult<Vec<TagSearchMatch>> {
+    use schema::tags::dsl as tags_dsl;
+
+    let matches = search::search_names(conn, "tags_fts", query, limit)?;
+
+    let mut tags = tags_dsl::tags
+        .filter(tags_dsl::id.eq_any(matches.iter().map(|m| &m.id)))
+        .load::<models::Tag>(conn)?
+        .into_iter()
+        .map(|tag| (tag.id.clone(), tag))
+        .collect::<HashMap<String, models::Tag>>();
+
+    Ok(matches
+        .into_iter()
+        .filter_map(|m| {
+            Some(TagSearchMatch {
+                score: m.score,
+                highlight: search::highlighted(&m.name),
+                tag: tags.remove(&m.id)?,
+            })
+        })
+        .collect())
+}
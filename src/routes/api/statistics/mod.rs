@@ -2,7 +2,12 @@ use crate::state::AppState;
 use utoipa_axum::{router::OpenApiRouter, routes};
 
 mod overview;
+mod subscriptions;
+
+pub use overview::load_overview;
 
 pub fn routes() -> OpenApiRouter<AppState> {
-    OpenApiRouter::new().routes(routes!(overview::get_overview))
+    OpenApiRouter::new()
+        .routes(routes!(overview::get_overview))
+        .routes(routes!(subscriptions::get_subscription_timeline))
 }
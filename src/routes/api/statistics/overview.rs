@@ -34,28 +34,30 @@ pub struct OverviewResponse {
 pub async fn get_overview(
     State(state): State<AppState>,
 ) -> ApiResult<(StatusCode, Json<OverviewResponse>)> {
+    let overview = state
+        .db(|conn| load_overview(conn).map_err(internal_error))
+        .await?;
+
+    Ok((StatusCode::OK, Json(overview)))
+}
+
+/// Computes the overview, shared with `chianti stats`
+pub fn load_overview(conn: &mut SqliteConnection) -> QueryResult<OverviewResponse> {
     use schema::channels::dsl as channels_dsl;
     use schema::tags::dsl as tags_dsl;
     use schema::videos::dsl as videos_dsl;
     use schema::watch_history::dsl as watch_history_dsl;
 
-    let mut conn = state.pool.get().map_err(internal_error)?;
+    let watch_history_list = watch_history_dsl::watch_history.load::<models::WatchHistory>(conn)?;
 
-    let watch_history_list = watch_history_dsl::watch_history
-        .load::<models::WatchHistory>(&mut conn)
-        .map_err(internal_error)?;
+    let videos_list = videos_dsl::videos.load::<models::Video>(conn)?;
 
-    let videos_list = videos_dsl::videos
-        .load::<models::Video>(&mut conn)
-        .map_err(internal_error)?;
+    let channels_list = channels_dsl::channels.load::<models::Channel>(conn)?;
 
-    let channels_list = channels_dsl::channels
-        .load::<models::Channel>(&mut conn)
-        .map_err(internal_error)?;
+    let tags_list = tags_dsl::tags.load::<models::Tag>(conn)?;
 
-    let tags_list = tags_dsl::tags
-        .load::<models::Tag>(&mut conn)
-        .map_err(internal_error)?;
+    // Averages are 0 while the watch history is empty
+    let sessions = (watch_history_list.len() as i64).max(1);
 
     let total_watch_time_seconds = watch_history_list
         .iter()
@@ -67,25 +69,22 @@ pub async fn get_overview(
         .iter()
         .map(|s| s.watch_duration_seconds)
         .sum::<i64>()
-        / watch_history_list.len() as i64;
+        / sessions;
     let total_channels = channels_list.len() as i64;
     let total_tags = tags_list.len() as i64;
     let average_session_duration_seconds = watch_history_list
         .iter()
         .map(|s| s.session_end_date - s.session_start_date)
         .sum::<i64>()
-        / watch_history_list.len() as i64;
+        / sessions;
 
-    Ok((
-        StatusCode::OK,
-        Json(OverviewResponse {
-            total_watch_time_seconds,
-            total_videos_watched,
-            total_channels,
-            total_tags,
-            total_unique_videos_watched,
-            average_watch_time_per_session_seconds,
-            average_session_duration_seconds,
-        }),
-    ))
+    Ok(OverviewResponse {
+        total_watch_time_seconds,
+        total_videos_watched,
+        total_channels,
+        total_tags,
+        total_unique_videos_watched,
+        average_watch_time_per_session_seconds,
+        average_session_duration_seconds,
+    })
 }
@@ -0,0 +1,88 @@
+use crate::api_prelude::*;
+use diesel::prelude::*;
+
+#[derive(utoipa::ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SubscriptionTimelineEntry {
+    pub channel: ChannelResponse,
+    pub subscribed: bool,
+    #[ts(type = "number")]
+    pub date: i64,
+}
+
+#[derive(Deserialize, Debug, utoipa::IntoParams)]
+pub struct GetSubscriptionTimelineParams {
+    /// Only list events after specified timestamp
+    after: Option<i64>,
+    /// Only list events before specified timestamp
+    before: Option<i64>,
+}
+
+/// Returns subscription timeline
+///
+/// Lists every subscribe and unsubscribe event, oldest first
+#[utoipa::path(
+    get,
+    path = "/subscriptions",
+    tag = "Statistics",
+    params(
+        GetSubscriptionTimelineParams
+    ),
+    responses(
+        (status = OK, body = Vec<SubscriptionTimelineEntry>)
+    )
+)]
+pub async fn get_subscription_timeline(
+    State(state): State<AppState>,
+    Query(params): Query<GetSubscriptionTimelineParams>,
+) -> ApiResult<(StatusCode, Json<Vec<SubscriptionTimelineEntry>>)> {
+    state
+        .db(move |conn| load_subscription_timeline(conn, params))
+        .await
+}
+
+fn load_subscription_timeline(
+    conn: &mut SqliteConnection,
+    params: GetSubscriptionTimelineParams,
+) -> ApiResult<(StatusCode, Json<Vec<SubscriptionTimelineEntry>>)> {
+    use models::ChannelEventKind;
+    use schema::channel_events::dsl as channel_events_dsl;
+    use schema::channels::dsl as channels_dsl;
+
+    let mut query = channel_events_dsl::channel_events
+        .inner_join(channels_dsl::channels)
+        .filter(channel_events_dsl::kind.eq_any([
+            ChannelEventKind::Subscribed.as_str(),
+            ChannelEventKind::Unsubscribed.as_str(),
+        ]))
+        .order((
+            channel_events_dsl::added_at.asc(),
+            rowid("channel_events").asc(),
+        ))
+        .select((
+            channel_events_dsl::channel_events::all_columns(),
+            channels_dsl::channels::all_columns(),
+        ))
+        .into_boxed();
+
+    if let Some(after) = params.after {
+        query = query.filter(channel_events_dsl::added_at.gt(after));
+    }
+
+    if let Some(before) = params.before {
+        query = query.filter(channel_events_dsl::added_at.lt(before));
+    }
+
+    let list = query
+        .load::<(models::ChannelEvent, models::Channel)>(conn)
+        .map_err(internal_error)?
+        .into_iter()
+        .map(|(event, channel)| SubscriptionTimelineEntry {
+            subscribed: event.kind == ChannelEventKind::Subscribed.as_str(),
+            date: event.added_at,
+            channel: ChannelResponse::new(channel),
+        })
+        .collect();
+
+    Ok((StatusCode::OK, Json(list)))
+}
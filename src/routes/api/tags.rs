@@ -1,24 +1,54 @@
 use crate::api_prelude::*;
+use crate::search;
+use diesel::expression::SqlLiteral;
 use diesel::prelude::*;
+use diesel::sql_types::BigInt;
+use diesel::sqlite::Sqlite;
 
 type GetTagsResponse = PaginatedResponse<models::Tag>;
 
-#[derive(Debug, Deserialize, utoipa::ToSchema)]
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, utoipa::ToSchema)]
 #[serde(rename_all = "snake_case")]
 enum SortBy {
     Name,
+    AddedAt,
+    /// Videos that have the tag
+    UsageCount,
+}
+
+impl SortKey for SortBy {
+    fn name(&self) -> &'static str {
+        match self {
+            SortBy::Name => "name",
+            SortBy::AddedAt => "added_at",
+            SortBy::UsageCount => "usage_count",
+        }
+    }
+}
+
+/// Number of videos that have each tag
+fn usage_count() -> SqlLiteral<BigInt> {
+    diesel::dsl::sql::<BigInt>(
+        "(SELECT COUNT(*) FROM video_tags WHERE video_tags.tag_id = tags.id)",
+    )
 }
 
 #[derive(Deserialize, Debug, utoipa::IntoParams)]
 pub struct GetTagsParams {
-    /// Sort order
+    /// Comma separated sort keys, most significant first, a key with a leading `-` sorts in
+    /// descending order, for example `-usage_count,name`. Keys are the `sort_by` fields, `name`
+    /// by default. Can not be combined with `sort_by` and `sort_order`.
+    sort: Option<String>,
+    /// Sort order, `asc` by default
     sort_order: Option<SortOrder>,
-    /// Sort by specified field
+    /// Sort by specified field, `name` by default
     sort_by: Option<SortBy>,
     /// Data list offset
     offset: Option<i64>,
     /// Data list limit
     limit: Option<i64>,
+    /// Search tags by name, see `/api/search` for the query syntax
+    search: Option<String>,
 }
 
 /// Returns Video tags
@@ -33,18 +63,44 @@ pub struct GetTagsParams {
     ),
     responses(
         (status = OK, description = "List of video tags", body = PaginatedResponse<models::Tag>),
+        (status = BAD_REQUEST, description = "Sort is invalid"),
     )
 )]
 pub async fn get_tags(
     State(state): State<AppState>,
     Query(params): Query<GetTagsParams>,
 ) -> ApiResult<(StatusCode, Json<GetTagsResponse>)> {
-    use schema::tags::dsl as tags_dsl;
+    state.db(move |conn| load_tags(conn, params)).await
+}
 
-    let mut conn = state.pool.get().map_err(internal_error)?;
+/// Tags that match the filters of `params`, the page and its total are both built from it
+fn filtered_tags(params: &GetTagsParams) -> schema::tags::BoxedQuery<'_, Sqlite> {
+    use schema::tags::dsl as tags_dsl;
 
     let mut query = tags_dsl::tags.into_boxed();
 
+    if let Some(fts_query) = params.search.as_deref().and_then(search::fts_query) {
+        query = query.filter(search::matching("tags", fts_query));
+    }
+
+    query
+}
+
+fn load_tags(
+    conn: &mut SqliteConnection,
+    params: GetTagsParams,
+) -> ApiResult<(StatusCode, Json<GetTagsResponse>)> {
+    use schema::tags::dsl as tags_dsl;
+
+    let sort = Sort::new(
+        params.sort.as_deref(),
+        params.sort_by,
+        params.sort_order,
+        (SortBy::Name, SortOrder::Asc),
+    )?;
+
+    let mut query = filtered_tags(&params);
+
     if let Some(offset) = params.offset {
         query = query.offset(offset);
     }
@@ -53,20 +109,22 @@ pub async fn get_tags(
         query = query.limit(limit);
     }
 
-    if let Some(sort_by) = params.sort_by {
-        query = match sort_by {
-            SortBy::Name => apply_sort!(query, tags_dsl::name, params.sort_order),
+    for &(key, order) in &sort.keys {
+        query = match key {
+            SortBy::Name => apply_sort!(query, tags_dsl::name, order),
+            SortBy::AddedAt => apply_sort!(query, tags_dsl::added_at, order),
+            SortBy::UsageCount => apply_sort!(query, usage_count(), order),
         };
     }
 
-    let list = query
-        .load::<models::Tag>(&mut conn)
-        .map_err(internal_error)?;
+    query = apply_sort!(query, tags_dsl::id, sort.id_order());
+
+    let list = query.load::<models::Tag>(conn).map_err(internal_error)?;
 
-    let total = tags_dsl::tags
+    let total = filtered_tags(&params)
         .count()
-        .get_result::<i64>(&mut conn)
-        .unwrap_or(0);
+        .get_result::<i64>(conn)
+        .map_err(internal_error)?;
 
     let res = GetTagsResponse::new(list, params.offset, params.limit, total);
 
@@ -91,13 +149,15 @@ pub async fn get_tag(
     State(state): State<AppState>,
     Path(id): Path<String>,
 ) -> ApiResult<(StatusCode, Json<models::Tag>)> {
-    use schema::tags::dsl as tags_dsl;
+    state.db(move |conn| load_tag(conn, id)).await
+}
 
-    let mut conn = state.pool.get().map_err(internal_error)?;
+fn load_tag(conn: &mut SqliteConnection, id: String) -> ApiResult<(StatusCode, Json<models::Tag>)> {
+    use schema::tags::dsl as tags_dsl;
 
     let list = tags_dsl::tags
         .filter(tags_dsl::id.eq(id))
-        .get_result::<models::Tag>(&mut conn)
+        .get_result::<models::Tag>(conn)
         .map_err(internal_error)?;
 
     Ok((StatusCode::OK, Json(list)))
@@ -1,9 +1,69 @@
 use crate::api_prelude::*;
+use crate::filter;
+use crate::search;
+use diesel::dsl::{InnerJoin, IntoBoxed};
+use diesel::expression::SqlLiteral;
 use diesel::prelude::*;
+use diesel::sql_types::BigInt;
+use diesel::sqlite::Sqlite;
 
 type GetVideosResponse = PaginatedResponse<VideoResponse>;
 
-#[derive(Debug, Deserialize, utoipa::ToSchema)]
+type VideosQuery<'a> =
+    IntoBoxed<'a, InnerJoin<schema::videos::table, schema::channels::table>, Sqlite>;
+
+#[derive(utoipa::ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct VideoMetricsResponse {
+    pub video_id: String,
+    pub snapshots: Vec<models::VideoMetricSnapshot>,
+}
+
+#[derive(utoipa::ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct VideoRevisionResponse {
+    #[serde(flatten)]
+    pub revision: models::VideoRevision,
+    pub thumbnail_endpoint: Option<String>,
+}
+
+impl VideoRevisionResponse {
+    pub fn new(revision: models::VideoRevision) -> Self {
+        Self {
+            thumbnail_endpoint: revision.thumbnail_file.as_ref().map(|_| {
+                format!(
+                    "/api/images/thumbnails/{}/revisions/{}",
+                    revision.video_id, revision.id
+                )
+            }),
+            revision,
+        }
+    }
+}
+
+#[derive(utoipa::ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct VideoRevisionsResponse {
+    pub video_id: String,
+    pub revisions: Vec<VideoRevisionResponse>,
+}
+
+#[derive(utoipa::ToSchema, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct VideoProgressResponse {
+    pub video_id: String,
+    #[ts(type = "number")]
+    pub duration_seconds: i64,
+    #[ts(type = "number")]
+    pub watched_seconds: i64,
+    pub completion_percentage: f64,
+    #[ts(type = "number | null")]
+    pub last_position_seconds: Option<i64>,
+    #[ts(type = "number | null")]
+    pub last_watched_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, utoipa::ToSchema)]
 #[serde(rename_all = "snake_case")]
 enum SortBy {
     Title,
@@ -14,19 +74,124 @@ enum SortBy {
     ViewCount,
     CommentsCount,
     PublishedAt,
+    AddedAt,
+    /// Seconds watched over all watch history of the video
+    WatchTime,
+}
+
+impl SortKey for SortBy {
+    fn name(&self) -> &'static str {
+        match self {
+            SortBy::Title => "title",
+            SortBy::Description => "description",
+            SortBy::WatchCounter => "watch_counter",
+            SortBy::DurationSeconds => "duration_seconds",
+            SortBy::LikesCount => "likes_count",
+            SortBy::ViewCount => "view_count",
+            SortBy::CommentsCount => "comments_count",
+            SortBy::PublishedAt => "published_at",
+            SortBy::AddedAt => "added_at",
+            SortBy::WatchTime => "watch_time",
+        }
+    }
+}
+
+impl SortBy {
+    /// Value of this key for `video` in the cursor of the next page
+    fn key(&self, conn: &mut SqliteConnection, video: &models::Video) -> QueryResult<CursorKey> {
+        use schema::videos::dsl as videos_dsl;
+
+        Ok(match self {
+            SortBy::Title => CursorKey::Text(video.title.clone()),
+            SortBy::Description => CursorKey::Text(video.description.clone()),
+            SortBy::WatchCounter => CursorKey::Integer(video.watch_counter),
+            SortBy::DurationSeconds => CursorKey::Integer(video.duration_seconds),
+            SortBy::LikesCount => CursorKey::Integer(video.likes_count),
+            SortBy::ViewCount => CursorKey::Integer(video.view_count),
+            SortBy::CommentsCount => CursorKey::Integer(video.comments_count),
+            SortBy::PublishedAt => CursorKey::Integer(video.published_at),
+            SortBy::AddedAt => CursorKey::Integer(video.added_at),
+            SortBy::WatchTime => CursorKey::Integer(
+                videos_dsl::videos
+                    .filter(videos_dsl::id.eq(&video.id))
+                    .select(watch_time())
+                    .get_result(conn)?,
+            ),
+        })
+    }
+
+    /// Conditions that a video comes after `value` of this key in `order` and that it ties with it
+    fn keyset_step(
+        self,
+        order: SortOrder,
+        value: CursorKey,
+    ) -> ApiResult<(
+        Condition<filter::VideosSource>,
+        Condition<filter::VideosSource>,
+    )> {
+        use schema::videos::dsl as videos_dsl;
+
+        Ok(match self {
+            SortBy::Title => keyset_step!(videos_dsl::title, order, value.text()?),
+            SortBy::Description => keyset_step!(videos_dsl::description, order, value.text()?),
+            SortBy::WatchCounter => {
+                keyset_step!(videos_dsl::watch_counter, order, value.integer()?)
+            }
+            SortBy::DurationSeconds => {
+                keyset_step!(videos_dsl::duration_seconds, order, value.integer()?)
+            }
+            SortBy::LikesCount => keyset_step!(videos_dsl::likes_count, order, value.integer()?),
+            SortBy::ViewCount => keyset_step!(videos_dsl::view_count, order, value.integer()?),
+            SortBy::CommentsCount => {
+                keyset_step!(videos_dsl::comments_count, order, value.integer()?)
+            }
+            SortBy::PublishedAt => keyset_step!(videos_dsl::published_at, order, value.integer()?),
+            SortBy::AddedAt => keyset_step!(videos_dsl::added_at, order, value.integer()?),
+            SortBy::WatchTime => keyset_step!(watch_time(), order, value.integer()?),
+        })
+    }
+
+    /// [`SortBy::keyset_step`] of the id that breaks ties
+    fn id_step(
+        order: SortOrder,
+        id: String,
+    ) -> (
+        Condition<filter::VideosSource>,
+        Condition<filter::VideosSource>,
+    ) {
+        keyset_step!(schema::videos::id, order, id)
+    }
+}
+
+/// Seconds watched over all watch history of each video
+fn watch_time() -> SqlLiteral<BigInt> {
+    diesel::dsl::sql::<BigInt>(
+        "(SELECT COALESCE(SUM(watch_history.watch_duration_seconds), 0) FROM watch_history
+        WHERE watch_history.video_id = videos.id)",
+    )
 }
 
 #[derive(Deserialize, Debug, utoipa::IntoParams)]
 pub struct GetVideosParams {
-    /// Sort order
+    /// Comma separated sort keys, most significant first, a key with a leading `-` sorts in
+    /// descending order, for example `-watch_counter,title`. Keys are the `sort_by` fields,
+    /// `-added_at` by default. Can not be combined with `sort_by` and `sort_order`.
+    sort: Option<String>,
+    /// Sort order, `desc` by default
     sort_order: Option<SortOrder>,
-    /// Sort by specified field
+    /// Sort by specified field, `added_at` by default
     sort_by: Option<SortBy>,
-    /// Data list offset
+    /// Data list offset, can not be combined with `cursor`
     offset: Option<i64>,
     /// Data list limit
     limit: Option<i64>,
-    /// Search videos by title
+    /// `next_cursor` of the previous page, it only continues the same sort
+    cursor: Option<String>,
+    /// Filter expression, for example `duration_seconds>600 and (channel.is_subscribed=true or
+    /// tag:music)`. Comparisons use `=`, `!=`, `>`, `>=`, `<`, `<=` or `~` (contains) and are
+    /// combined with `and`, `or`, `not` and parentheses.
+    filter: Option<String>,
+    /// Search video titles, descriptions and tags, see `/api/search` for the query syntax
     search: Option<String>,
     /// List only videos that belong to specified channel
     channel_id: Option<String>,
@@ -38,8 +203,6 @@ pub struct GetVideosParams {
     min_subscribers_count: Option<i64>,
     /// Video subscribers_count less than specified value
     max_subscribers_count: Option<i64>,
-    /// List only videos that have specified tags
-    tags: Option<Vec<String>>,
     /// Video watch_counter equal to specified value
     watch_counter: Option<i64>,
     /// Video watch_counter greater than specified value
@@ -90,46 +253,42 @@ pub struct GetVideosParams {
     path = "/videos",
     tag = "Video",
     params(
-        GetVideosParams
+        GetVideosParams,
+        TagFilters
     ),
     responses(
         (status = OK, description = "List of videos", body = PaginatedResponse<VideoResponse>),
+        (status = BAD_REQUEST, description = "Sort, filter or cursor is invalid, or the cursor is combined with offset"),
     )
 )]
 pub async fn get_videos(
     State(state): State<AppState>,
     Query(params): Query<GetVideosParams>,
+    Query(tag_filters): Query<TagFilters>,
 ) -> ApiResult<(StatusCode, Json<GetVideosResponse>)> {
+    state
+        .db(move |conn| load_videos(conn, params, tag_filters))
+        .await
+}
+
+/// Videos and their channel that match the filters of `params` and `tag_filters`, the page and
+/// its total are both built from it
+fn filtered_videos<'a>(
+    params: &'a GetVideosParams,
+    tag_filters: &TagFilters,
+) -> ApiResult<VideosQuery<'a>> {
     use schema::channels::dsl as channels_dsl;
-    use schema::tags::dsl as tags_dsl;
-    use schema::video_tags::dsl as video_tags_dsl;
     use schema::videos::dsl as videos_dsl;
 
-    let mut conn = state.pool.get().map_err(internal_error)?;
-
     let mut query = videos_dsl::videos
         .inner_join(channels_dsl::channels)
-        .left_join(video_tags_dsl::video_tags.inner_join(tags_dsl::tags))
-        .select((
-            videos_dsl::videos::all_columns(),
-            channels_dsl::channels::all_columns(),
-        ))
-        .distinct()
         .into_boxed();
 
-    if let Some(offset) = params.offset {
-        query = query.offset(offset);
-    }
-
-    if let Some(limit) = params.limit {
-        query = query.limit(limit);
-    }
-
-    if let Some(search) = params.search {
-        query = query.filter(videos_dsl::title.like(format!("%{search}%")));
+    if let Some(fts_query) = params.search.as_deref().and_then(search::fts_query) {
+        query = query.filter(search::matching("videos", fts_query));
     }
 
-    if let Some(channel_id) = params.channel_id {
+    if let Some(channel_id) = &params.channel_id {
         query = query.filter(channels_dsl::id.eq(channel_id));
     }
 
@@ -149,8 +308,10 @@ pub async fn get_videos(
         query = query.filter(channels_dsl::subscribers_count.lt(max_subscribers_count));
     }
 
-    if let Some(tags) = params.tags {
-        query = query.filter(tags_dsl::name.eq_any(tags));
+    query = filter_tags!(query, videos_dsl::id, tag_filters);
+
+    if let Some(input) = &params.filter {
+        query = query.filter(filter::videos(input)?);
     }
 
     if let Some(watch_counter) = params.watch_counter {
@@ -233,42 +394,86 @@ pub async fn get_videos(
         query = query.filter(videos_dsl::published_at.gt(published_after));
     }
 
-    if let Some(sort_by) = params.sort_by {
-        query = match sort_by {
-            SortBy::Title => apply_sort!(query, videos_dsl::title, params.sort_order),
-            SortBy::Description => {
-                apply_sort!(query, videos_dsl::description, params.sort_order)
-            }
-            SortBy::WatchCounter => {
-                apply_sort!(query, videos_dsl::watch_counter, params.sort_order)
-            }
-            SortBy::LikesCount => apply_sort!(query, videos_dsl::likes_count, params.sort_order),
-            SortBy::ViewCount => apply_sort!(query, videos_dsl::view_count, params.sort_order),
-            SortBy::CommentsCount => {
-                apply_sort!(query, videos_dsl::comments_count, params.sort_order)
-            }
-            SortBy::DurationSeconds => {
-                apply_sort!(query, videos_dsl::duration_seconds, params.sort_order)
-            }
-            SortBy::PublishedAt => {
-                apply_sort!(query, videos_dsl::published_at, params.sort_order)
-            }
+    Ok(query)
+}
+
+fn load_videos(
+    conn: &mut SqliteConnection,
+    params: GetVideosParams,
+    tag_filters: TagFilters,
+) -> ApiResult<(StatusCode, Json<GetVideosResponse>)> {
+    use schema::videos::dsl as videos_dsl;
+
+    check_cursor_offset(&params.cursor, params.offset)?;
+
+    let sort = Sort::new(
+        params.sort.as_deref(),
+        params.sort_by,
+        params.sort_order,
+        (SortBy::AddedAt, SortOrder::Desc),
+    )?;
+
+    let mut query = filtered_videos(&params, &tag_filters)?;
+
+    if let Some(cursor) = &params.cursor {
+        query = query.filter(after_cursor(
+            &sort,
+            Cursor::decode(cursor, &sort.to_string())?,
+            SortBy::keyset_step,
+            SortBy::id_step,
+        )?);
+    }
+
+    for &(key, order) in &sort.keys {
+        query = match key {
+            SortBy::Title => apply_sort!(query, videos_dsl::title, order),
+            SortBy::Description => apply_sort!(query, videos_dsl::description, order),
+            SortBy::WatchCounter => apply_sort!(query, videos_dsl::watch_counter, order),
+            SortBy::DurationSeconds => apply_sort!(query, videos_dsl::duration_seconds, order),
+            SortBy::LikesCount => apply_sort!(query, videos_dsl::likes_count, order),
+            SortBy::ViewCount => apply_sort!(query, videos_dsl::view_count, order),
+            SortBy::CommentsCount => apply_sort!(query, videos_dsl::comments_count, order),
+            SortBy::PublishedAt => apply_sort!(query, videos_dsl::published_at, order),
+            SortBy::AddedAt => apply_sort!(query, videos_dsl::added_at, order),
+            SortBy::WatchTime => apply_sort!(query, watch_time(), order),
         };
     }
 
-    let data = query
-        .load::<(models::Video, models::Channel)>(&mut conn)
+    query = apply_sort!(query, videos_dsl::id, sort.id_order());
+
+    if let Some(offset) = params.offset {
+        query = query.offset(offset);
+    }
+
+    if let Some(limit) = params.limit {
+        query = query.limit(limit.max(0) + 1);
+    }
+
+    let mut data = query
+        .load::<(models::Video, models::Channel)>(conn)
         .map_err(internal_error)?;
 
+    let next_cursor = match last_of_page(&mut data, params.limit) {
+        Some((video, _)) => {
+            let keys = sort
+                .keys
+                .iter()
+                .map(|(key, _)| key.key(conn, video))
+                .collect::<QueryResult<Vec<CursorKey>>>()
+                .map_err(internal_error)?;
+
+            Some(Cursor::new(sort.to_string(), keys, video.id.clone()))
+        }
+        None => None,
+    };
+
+    let mut tags =
+        load_video_tags(conn, data.iter().map(|(video, _)| &video.id)).map_err(internal_error)?;
+
     let list: Vec<VideoResponse> = data
         .into_iter()
         .map(|(video, channel)| {
-            let tags = tags_dsl::tags
-                .inner_join(video_tags_dsl::video_tags)
-                .filter(video_tags_dsl::video_id.eq(&video.id))
-                .select(tags_dsl::name)
-                .load(&mut conn)
-                .unwrap_or(Vec::new());
+            let tags = tags.remove(&video.id).unwrap_or_default();
 
             let channel_response = ChannelResponse::new(channel);
 
@@ -276,12 +481,13 @@ pub async fn get_videos(
         })
         .collect();
 
-    let total = videos_dsl::videos
+    let total = filtered_videos(&params, &tag_filters)?
         .count()
-        .get_result::<i64>(&mut conn)
-        .unwrap_or(0);
+        .get_result::<i64>(conn)
+        .map_err(internal_error)?;
 
-    let res = GetVideosResponse::new(list, params.offset, params.limit, total);
+    let res = GetVideosResponse::new(list, params.offset, params.limit, total)
+        .with_next_cursor(next_cursor);
 
     Ok((StatusCode::OK, Json(res)))
 }
@@ -303,25 +509,30 @@ pub async fn get_videos(
 pub async fn get_video(
     State(state): State<AppState>,
     Path(id): Path<String>,
+) -> ApiResult<(StatusCode, Json<VideoResponse>)> {
+    state.db(move |conn| load_video(conn, id)).await
+}
+
+fn load_video(
+    conn: &mut SqliteConnection,
+    id: String,
 ) -> ApiResult<(StatusCode, Json<VideoResponse>)> {
     use schema::channels::dsl as channels_dsl;
     use schema::tags::dsl as tags_dsl;
     use schema::video_tags::dsl as video_tags_dsl;
     use schema::videos::dsl as videos_dsl;
 
-    let mut conn = state.pool.get().map_err(internal_error)?;
-
     let (video, channel) = videos_dsl::videos
         .filter(videos_dsl::id.eq(id))
         .inner_join(channels_dsl::channels)
-        .get_result::<(models::Video, models::Channel)>(&mut conn)
+        .get_result::<(models::Video, models::Channel)>(conn)
         .map_err(internal_error)?;
 
     let tags = tags_dsl::tags
         .inner_join(video_tags_dsl::video_tags)
         .filter(video_tags_dsl::video_id.eq(&video.id))
         .select(tags_dsl::name)
-        .load(&mut conn)
+        .load(conn)
         .unwrap_or(Vec::new());
 
     let channel_response = ChannelResponse::new(channel);
@@ -330,3 +541,256 @@ pub async fn get_video(
 
     Ok((StatusCode::OK, Json(response)))
 }
+
+/// Returns video metrics history
+///
+/// Lists the view, like and comment counts that were recorded every time the video was watched,
+/// oldest first
+#[utoipa::path(
+    get,
+    path = "/videos/{id}/metrics",
+    tag = "Video",
+    params(
+        ("id" = String, Path, description = "Video id")
+    ),
+    responses(
+        (status = OK, description = "Video metric snapshots", body = VideoMetricsResponse),
+        (status = NOT_FOUND, description = "Video not found"),
+    )
+)]
+pub async fn get_video_metrics(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<(StatusCode, Json<VideoMetricsResponse>)> {
+    state.db(move |conn| load_video_metrics(conn, id)).await
+}
+
+fn load_video_metrics(
+    conn: &mut SqliteConnection,
+    id: String,
+) -> ApiResult<(StatusCode, Json<VideoMetricsResponse>)> {
+    use schema::video_metric_snapshots::dsl as video_metric_snapshots_dsl;
+    use schema::videos::dsl as videos_dsl;
+
+    let Some(video) = videos_dsl::videos
+        .find(&id)
+        .get_result::<models::Video>(conn)
+        .optional()
+        .map_err(internal_error)?
+    else {
+        return Err((StatusCode::NOT_FOUND, "Video not found".to_string()));
+    };
+
+    let snapshots = models::VideoMetricSnapshot::belonging_to(&video)
+        .order((
+            video_metric_snapshots_dsl::added_at.asc(),
+            rowid("video_metric_snapshots").asc(),
+        ))
+        .load::<models::VideoMetricSnapshot>(conn)
+        .map_err(internal_error)?;
+
+    let response = VideoMetricsResponse {
+        video_id: video.id,
+        snapshots,
+    };
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Returns video revisions
+///
+/// Lists every title and thumbnail the video had and when it was first seen, oldest first
+#[utoipa::path(
+    get,
+    path = "/videos/{id}/revisions",
+    tag = "Video",
+    params(
+        ("id" = String, Path, description = "Video id")
+    ),
+    responses(
+        (status = OK, description = "Video revisions", body = VideoRevisionsResponse),
+        (status = NOT_FOUND, description = "Video not found"),
+    )
+)]
+pub async fn get_video_revisions(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<(StatusCode, Json<VideoRevisionsResponse>)> {
+    state.db(move |conn| load_video_revisions(conn, id)).await
+}
+
+fn load_video_revisions(
+    conn: &mut SqliteConnection,
+    id: String,
+) -> ApiResult<(StatusCode, Json<VideoRevisionsResponse>)> {
+    use schema::video_revisions::dsl as video_revisions_dsl;
+    use schema::videos::dsl as videos_dsl;
+
+    let Some(video) = videos_dsl::videos
+        .find(&id)
+        .get_result::<models::Video>(conn)
+        .optional()
+        .map_err(internal_error)?
+    else {
+        return Err((StatusCode::NOT_FOUND, "Video not found".to_string()));
+    };
+
+    let revisions = models::VideoRevision::belonging_to(&video)
+        .order((
+            video_revisions_dsl::added_at.asc(),
+            rowid("video_revisions").asc(),
+        ))
+        .load::<models::VideoRevision>(conn)
+        .map_err(internal_error)?
+        .into_iter()
+        .map(VideoRevisionResponse::new)
+        .collect();
+
+    let response = VideoRevisionsResponse {
+        video_id: video.id,
+        revisions,
+    };
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Returns video watch progress
+///
+/// Completion counts every second of the video that was played at least once, rewatching the same
+/// part does not increase it. `last_position_seconds` is where the latest session stopped and can
+/// be used to resume playback.
+#[utoipa::path(
+    get,
+    path = "/videos/{id}/progress",
+    tag = "Video",
+    params(
+        ("id" = String, Path, description = "Video id")
+    ),
+    responses(
+        (status = OK, description = "Video watch progress", body = VideoProgressResponse),
+        (status = NOT_FOUND, description = "Video not found"),
+    )
+)]
+pub async fn get_video_progress(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<(StatusCode, Json<VideoProgressResponse>)> {
+    state.db(move |conn| load_video_progress(conn, id)).await
+}
+
+fn load_video_progress(
+    conn: &mut SqliteConnection,
+    id: String,
+) -> ApiResult<(StatusCode, Json<VideoProgressResponse>)> {
+    use schema::videos::dsl as videos_dsl;
+    use schema::watch_history::dsl as watch_history_dsl;
+    use schema::watch_segments::dsl as watch_segments_dsl;
+
+    let Some(video) = videos_dsl::videos
+        .find(&id)
+        .get_result::<models::Video>(conn)
+        .optional()
+        .map_err(internal_error)?
+    else {
+        return Err((StatusCode::NOT_FOUND, "Video not found".to_string()));
+    };
+
+    let segments = watch_segments_dsl::watch_segments
+        .inner_join(watch_history_dsl::watch_history)
+        .filter(watch_history_dsl::video_id.eq(&video.id))
+        .order((
+            watch_history_dsl::session_end_date.asc(),
+            watch_segments_dsl::segment_index.asc(),
+        ))
+        .select((
+            watch_segments_dsl::watch_segments::all_columns(),
+            watch_history_dsl::session_end_date,
+        ))
+        .load::<(models::WatchSegment, i64)>(conn)
+        .map_err(internal_error)?;
+
+    let last_watched_at = watch_history_dsl::watch_history
+        .filter(watch_history_dsl::video_id.eq(&video.id))
+        .select(diesel::dsl::max(watch_history_dsl::session_end_date))
+        .get_result::<Option<i64>>(conn)
+        .map_err(internal_error)?;
+
+    let last_position_seconds = segments
+        .last()
+        .filter(|(_, session_end_date)| Some(*session_end_date) == last_watched_at)
+        .map(|(segment, _)| segment.end_seconds);
+
+    let mut ranges = segments
+        .iter()
+        .map(|(segment, _)| {
+            let end = if video.duration_seconds > 0 {
+                segment.end_seconds.min(video.duration_seconds)
+            } else {
+                segment.end_seconds
+            };
+
+            (segment.start_seconds.max(0), end)
+        })
+        .filter(|(start, end)| start < end)
+        .collect::<Vec<(i64, i64)>>();
+
+    ranges.sort_unstable();
+
+    let mut watched_seconds = 0;
+    let mut covered_until = i64::MIN;
+
+    for (start, end) in ranges {
+        let start = start.max(covered_until);
+
+        if end > start {
+            watched_seconds += end - start;
+            covered_until = end;
+        }
+    }
+
+    let completion_percentage = if video.duration_seconds > 0 {
+        watched_seconds as f64 / video.duration_seconds as f64 * 100.0
+    } else {
+        0.0
+    };
+
+    let response = VideoProgressResponse {
+        video_id: video.id,
+        duration_seconds: video.duration_seconds,
+        watched_seconds,
+        completion_percentage,
+        last_position_seconds,
+        last_watched_at,
+    };
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing;
+
+    fn load(conn: &mut SqliteConnection, limit: i64) -> (usize, usize) {
+        let params = serde_json::from_value(serde_json::json!({ "limit": limit })).unwrap();
+        let tag_filters = serde_json::from_value(serde_json::json!({})).unwrap();
+
+        let (result, queries) =
+            testing::count_queries(conn, |conn| load_videos(conn, params, tag_filters));
+        let (_, Json(response)) = result.unwrap();
+
+        (response.data.len(), queries)
+    }
+
+    #[test]
+    fn queries_do_not_grow_with_the_page() {
+        let mut conn = testing::connection();
+        testing::seed(&mut conn, 120);
+
+        let (one, one_queries) = load(&mut conn, 1);
+        let (hundred, hundred_queries) = load(&mut conn, 100);
+
+        assert_eq!((one, hundred), (1, 100));
+        assert_eq!(one_queries, hundred_queries);
+    }
+}
@@ -1,6 +1,15 @@
 use crate::api_prelude::*;
+use crate::filter;
+use crate::ingest;
+use crate::validation::{
+    self, FieldError, FieldErrorCode, Validate, ValidationErrorResponse, Validator,
+};
 use diesel::prelude::*;
-use diesel::{ExpressionMethods, RunQueryDsl, dsl::insert_into};
+use diesel::sqlite::Sqlite;
+use diesel::{
+    ExpressionMethods, RunQueryDsl, SqliteConnection,
+    dsl::{InnerJoin, IntoBoxed, insert_into, update},
+};
 
 #[derive(utoipa::ToSchema, Deserialize, TS)]
 #[ts(export)]
@@ -34,6 +43,17 @@ pub struct CreateWatchHistoryVideo {
     published_at: i64,
 }
 
+#[derive(utoipa::ToSchema, Deserialize, TS)]
+#[ts(export)]
+pub struct CreateWatchHistorySegment {
+    /// Position in the video the segment starts at
+    #[ts(type = "number")]
+    start_seconds: i64,
+    /// Position in the video the segment ends before, a segment of one second at 10 ends at 11
+    #[ts(type = "number")]
+    end_seconds: i64,
+}
+
 #[derive(utoipa::ToSchema, Deserialize, TS)]
 #[ts(export)]
 pub struct CreateWatchHistoryRequest {
@@ -43,323 +63,966 @@ pub struct CreateWatchHistoryRequest {
     session_start_date: i64,
     #[ts(type = "number")]
     session_end_date: i64,
+    session_key: Option<String>,
+    segments: Option<Vec<CreateWatchHistorySegment>>,
 
     channel: CreateWatchHistoryChannel,
     video: CreateWatchHistoryVideo,
 }
 
+/// Watched seconds may exceed the length of a session by this much before a record is rejected
+const WATCH_DURATION_SLACK_SECONDS: i64 = 60;
+
+impl Validate for CreateWatchHistoryChannel {
+    fn validate(&self, v: &mut Validator) {
+        if !validation::is_channel_id(&self.id) {
+            v.error(
+                "id",
+                FieldErrorCode::InvalidFormat,
+                "Not a youtube channel id",
+            );
+        }
+
+        v.non_negative("subscribers_count", self.subscribers_count);
+    }
+}
+
+impl Validate for CreateWatchHistoryVideo {
+    fn validate(&self, v: &mut Validator) {
+        if !validation::is_video_id(&self.id) {
+            v.error(
+                "id",
+                FieldErrorCode::InvalidFormat,
+                "Not a youtube video id",
+            );
+        }
+
+        v.non_negative("likes_count", self.likes_count);
+        v.non_negative("view_count", self.view_count);
+        v.non_negative("comments_count", self.comments_count);
+        v.non_negative("duration", self.duration);
+        v.non_negative("published_at", self.published_at);
+        v.not_in_future("published_at", self.published_at, utils::unix_now());
+    }
+}
+
+impl Validate for CreateWatchHistorySegment {
+    fn validate(&self, v: &mut Validator) {
+        v.non_negative("start_seconds", self.start_seconds);
+
+        if self.end_seconds < self.start_seconds {
+            v.error(
+                "end_seconds",
+                FieldErrorCode::OutOfOrder,
+                "Must not be before start_seconds",
+            );
+        }
+    }
+}
+
+impl Validate for CreateWatchHistoryRequest {
+    fn validate(&self, v: &mut Validator) {
+        let now = utils::unix_now();
+
+        v.non_negative("watch_duration_seconds", self.watch_duration_seconds);
+        v.non_negative("session_start_date", self.session_start_date);
+        v.not_in_future("session_end_date", self.session_end_date, now);
+
+        if self.session_end_date < self.session_start_date {
+            v.error(
+                "session_end_date",
+                FieldErrorCode::OutOfOrder,
+                "Must not be before session_start_date",
+            );
+        } else if self.watch_duration_seconds
+            > self.session_end_date - self.session_start_date + WATCH_DURATION_SLACK_SECONDS
+        {
+            v.error(
+                "watch_duration_seconds",
+                FieldErrorCode::ExceedsSession,
+                "Must not be longer than the session",
+            );
+        }
+
+        for (index, segment) in self.segments.iter().flatten().enumerate() {
+            v.nested(format!("segments[{index}]"), |v| segment.validate(v));
+        }
+
+        v.nested("channel", |v| self.channel.validate(v));
+        v.nested("video", |v| self.video.validate(v));
+    }
+}
+
+#[derive(Debug, Default, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum CreateWatchHistoryMode {
+    #[default]
+    Atomic,
+    PerItem,
+}
+
+#[derive(Deserialize, Debug, utoipa::IntoParams)]
+pub struct CreateWatchHistoryParams {
+    /// `atomic` saves nothing if one record is rejected, `per_item` commits every record on its own
+    mode: Option<CreateWatchHistoryMode>,
+}
+
+#[derive(utoipa::ToSchema, Serialize, Debug, PartialEq, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum CreateWatchHistoryStatus {
+    Created,
+    Duplicate,
+    Rejected,
+}
+
+#[derive(utoipa::ToSchema, Serialize, TS)]
+#[ts(export)]
+pub struct CreateWatchHistoryResult {
+    #[ts(type = "number")]
+    index: usize,
+    video_id: String,
+    status: CreateWatchHistoryStatus,
+    reason: Option<String>,
+    errors: Vec<FieldError>,
+}
+
+impl CreateWatchHistoryResult {
+    fn new(
+        index: usize,
+        payload: &CreateWatchHistoryRequest,
+        status: CreateWatchHistoryStatus,
+        reason: Option<String>,
+    ) -> Self {
+        Self {
+            index,
+            video_id: payload.video.id.clone(),
+            status,
+            reason,
+            errors: Vec::new(),
+        }
+    }
+
+    fn invalid(index: usize, payload: &CreateWatchHistoryRequest, errors: Vec<FieldError>) -> Self {
+        Self {
+            errors,
+            ..Self::new(
+                index,
+                payload,
+                CreateWatchHistoryStatus::Rejected,
+                Some("Validation failed".to_string()),
+            )
+        }
+    }
+}
+
+#[derive(utoipa::ToSchema, Serialize, TS)]
+#[ts(export)]
+pub struct CreateWatchHistoryResponse {
+    results: Vec<CreateWatchHistoryResult>,
+}
+
 /// Create new watch history records
 ///
-/// This endpoint is used to create new watch history records
+/// This endpoint is used to create new watch history records.
+/// The response lists the status of every record in the same order as the request body,
+/// clients can drop `created` and `duplicate` records from their retry queue.
+/// Records that fail validation are rejected with the list of invalid fields in `errors`.
 #[utoipa::path(
     post,
     path = "/watch_history",
     tag = "Watch history",
+    params(
+        CreateWatchHistoryParams
+    ),
     responses(
-        (status = CREATED, description = "Watch history record created", body = ()),
+        (status = CREATED, description = "Every record was saved or already existed", body = CreateWatchHistoryResponse),
+        (status = MULTI_STATUS, description = "Some records were rejected (`per_item` mode)", body = CreateWatchHistoryResponse),
+        (status = UNPROCESSABLE_ENTITY, description = "A record failed validation or was rejected and the batch was rolled back (`atomic` mode)", body = CreateWatchHistoryResponse),
     )
 )]
 pub async fn create_watch_history(
     State(state): State<AppState>,
+    Query(params): Query<CreateWatchHistoryParams>,
     Json(payload_list): Json<Vec<CreateWatchHistoryRequest>>,
-) -> ApiResult<StatusCode> {
-    use schema::channels::dsl as channels_dsl;
-    use schema::tags::dsl as tags_dsl;
-    use schema::video_tags::dsl as video_tags_dsl;
-    use schema::videos::dsl as videos_dsl;
-    use schema::watch_history::dsl as watch_history_dsl;
+) -> ApiResult<(StatusCode, Json<CreateWatchHistoryResponse>)> {
+    state
+        .db(move |conn| save_watch_history(conn, params, payload_list))
+        .await
+}
 
-    let mut conn = state.pool.get().map_err(internal_error)?;
-
-    for payload in payload_list {
-        let channel_avater_file_path =
-            state
-                .channel_avaters_dir
-                .join(utils::build_avater_cache_image_filename(
-                    &payload.channel.id,
-                ));
-        let video_thumbnail_file_path =
-            state
-                .video_thumbnails_dir
-                .join(utils::build_thumbnail_cache_image_filename(
-                    &payload.video.id,
-                ));
-
-        if !channel_avater_file_path.exists() {
-            tracing::info!(
-                "Downloading channel avater for channel {}",
-                payload.channel.id
-            );
-            let res = reqwest::get(&payload.channel.avater_url)
-                .await
-                .map_err(internal_error)?;
+fn save_watch_history(
+    conn: &mut SqliteConnection,
+    params: CreateWatchHistoryParams,
+    payload_list: Vec<CreateWatchHistoryRequest>,
+) -> ApiResult<(StatusCode, Json<CreateWatchHistoryResponse>)> {
+    let mut results: Vec<CreateWatchHistoryResult> = Vec::with_capacity(payload_list.len());
 
-            if res.status() == reqwest::StatusCode::OK {
-                let image = res.bytes().await.map_err(internal_error)?;
+    let mut validation_errors: Vec<Option<Vec<FieldError>>> = payload_list
+        .iter()
+        .map(|payload| validation::validate(payload).err())
+        .collect();
 
-                image::load_from_memory(&image)
-                    .map_err(internal_error)?
-                    .save_with_format(&channel_avater_file_path, image::ImageFormat::WebP)
-                    .map_err(internal_error)?;
-            } else {
-                tracing::warn!(
-                    "Failed to download channel avater for channel {}",
-                    payload.channel.id
-                );
-            }
+    let status_code = match params.mode.unwrap_or_default() {
+        CreateWatchHistoryMode::Atomic if validation_errors.iter().any(Option::is_some) => {
+            results = payload_list
+                .iter()
+                .zip(validation_errors)
+                .enumerate()
+                .map(|(index, (payload, errors))| match errors {
+                    Some(errors) => CreateWatchHistoryResult::invalid(index, payload, errors),
+                    None => CreateWatchHistoryResult::new(
+                        index,
+                        payload,
+                        CreateWatchHistoryStatus::Rejected,
+                        Some(
+                            "Batch was rejected because other records failed validation"
+                                .to_string(),
+                        ),
+                    ),
+                })
+                .collect();
+
+            StatusCode::UNPROCESSABLE_ENTITY
         }
+        CreateWatchHistoryMode::Atomic => {
+            let mut rejected: Option<(usize, String)> = None;
 
-        if !video_thumbnail_file_path.exists() {
-            tracing::info!("Downloading video thumbnail for video {}", payload.video.id);
-            let res = reqwest::get(&payload.video.thumbnail_url)
-                .await
-                .map_err(internal_error)?;
+            let outcome = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+                for (index, payload) in payload_list.iter().enumerate() {
+                    match insert_watch_history_record(conn, payload) {
+                        Ok(status) => {
+                            results
+                                .push(CreateWatchHistoryResult::new(index, payload, status, None));
+                        }
+                        Err(e) => {
+                            rejected = Some((index, e.to_string()));
+                            return Err(diesel::result::Error::RollbackTransaction);
+                        }
+                    }
+                }
 
-            if res.status() == reqwest::StatusCode::OK {
-                let image = res.bytes().await.map_err(internal_error)?;
+                Ok(())
+            });
 
-                image::load_from_memory(&image)
-                    .map_err(internal_error)?
-                    .save_with_format(&video_thumbnail_file_path, image::ImageFormat::WebP)
-                    .map_err(internal_error)?;
-            } else {
-                tracing::warn!(
-                    "Failed to download video thumbnail for video {}",
-                    payload.video.id
-                );
+            match (outcome, rejected) {
+                (Ok(()), _) => StatusCode::CREATED,
+                (Err(_), Some((rejected_index, reason))) => {
+                    tracing::warn!(
+                        "Rolled back watch history batch, record {} was rejected: {}",
+                        rejected_index,
+                        reason
+                    );
+
+                    results = payload_list
+                        .iter()
+                        .enumerate()
+                        .map(|(index, payload)| {
+                            let reason = if index == rejected_index {
+                                reason.clone()
+                            } else {
+                                format!("Batch was rolled back because record {rejected_index} was rejected")
+                            };
+
+                            CreateWatchHistoryResult::new(
+                                index,
+                                payload,
+                                CreateWatchHistoryStatus::Rejected,
+                                Some(reason),
+                            )
+                        })
+                        .collect();
+
+                    StatusCode::UNPROCESSABLE_ENTITY
+                }
+                (Err(e), None) => return Err(internal_error(e)),
             }
         }
+        CreateWatchHistoryMode::PerItem => {
+            for (index, payload) in payload_list.iter().enumerate() {
+                if let Some(errors) = validation_errors[index].take() {
+                    tracing::warn!("Rejected invalid watch history record {}", index);
 
-        let channel = models::Channel::new(models::NewChannelParams {
-            id: payload.channel.id.clone(),
-            name: payload.channel.name.clone(),
-            url: payload.channel.url,
-            is_subscribed: payload.channel.is_subscribed,
-            subscribers_count: payload.channel.subscribers_count,
-        });
+                    results.push(CreateWatchHistoryResult::invalid(index, payload, errors));
+                    continue;
+                }
 
-        insert_into(channels_dsl::channels)
-            .values(&channel)
-            .on_conflict(channels_dsl::id)
-            .do_update()
-            .set((
-                channels_dsl::name.eq(payload.channel.name),
-                channels_dsl::is_subscribed.eq(payload.channel.is_subscribed),
-                channels_dsl::subscribers_count.eq(payload.channel.subscribers_count),
-            ))
-            .execute(&mut conn)
-            .map_err(internal_error)?;
-
-        let video = models::Video::new(models::NewVideoParams {
-            id: payload.video.id,
-            channel_id: payload.channel.id,
-            title: payload.video.title.clone(),
-            description: payload.video.description,
-            duration_seconds: payload.video.duration,
-            likes_count: payload.video.likes_count,
-            view_count: payload.video.view_count,
-            comments_count: payload.video.comments_count,
-            published_at: payload.video.published_at,
-        });
+                let result =
+                    match conn.transaction(|conn| insert_watch_history_record(conn, payload)) {
+                        Ok(status) => CreateWatchHistoryResult::new(index, payload, status, None),
+                        Err(e) => {
+                            tracing::warn!("Rejected watch history record {}: {}", index, e);
 
-        insert_into(videos_dsl::videos)
-            .values(&video)
-            .on_conflict(videos_dsl::id)
-            .do_update()
-            .set((
-                videos_dsl::title.eq(payload.video.title),
-                videos_dsl::view_count.eq(payload.video.view_count),
-                videos_dsl::likes_count.eq(payload.video.likes_count),
-                videos_dsl::comments_count.eq(payload.video.comments_count),
-            ))
-            .execute(&mut conn)
-            .map_err(internal_error)?;
-
-        for tag_name in payload.video.tags {
-            let tag = match tags_dsl::tags
-                .filter(tags_dsl::name.eq(&tag_name))
-                .get_result::<models::Tag>(&mut conn)
-            {
-                Ok(r) => r,
-                Err(_) => {
-                    let new_tag = models::Tag::new(tag_name);
+                            CreateWatchHistoryResult::new(
+                                index,
+                                payload,
+                                CreateWatchHistoryStatus::Rejected,
+                                Some(e.to_string()),
+                            )
+                        }
+                    };
 
-                    insert_into(tags_dsl::tags)
-                        .values(&new_tag)
-                        .on_conflict_do_nothing()
-                        .execute(&mut conn)
-                        .map_err(internal_error)?;
+                results.push(result);
+            }
 
-                    new_tag
-                }
-            };
+            if results
+                .iter()
+                .any(|r| r.status == CreateWatchHistoryStatus::Rejected)
+            {
+                StatusCode::MULTI_STATUS
+            } else {
+                StatusCode::CREATED
+            }
+        }
+    };
 
-            let video_tag = models::VideoTags::new(video.id.clone(), tag.id);
+    Ok((status_code, Json(CreateWatchHistoryResponse { results })))
+}
 
-            insert_into(video_tags_dsl::video_tags)
-                .values(&video_tag)
-                .on_conflict_do_nothing()
-                .execute(&mut conn)
-                .map_err(internal_error)?;
-        }
+/// Upserts the channel, video and tags of one record and inserts its watch history row.
+///
+/// Segments replace the stored segments of a resent session, every resend carries the full
+/// list of ranges played so far.
+fn insert_watch_history_record(
+    conn: &mut SqliteConnection,
+    payload: &CreateWatchHistoryRequest,
+) -> QueryResult<CreateWatchHistoryStatus> {
+    use schema::watch_segments::dsl as watch_segments_dsl;
 
-        let new_watch_history = models::WatchHistory::new(
-            video.id,
-            channel.id,
+    upsert_channel_and_video(conn, &payload.channel, &payload.video)?;
+
+    let (outcome, watch_history_id) = ingest::save_watch_session(
+        conn,
+        models::WatchHistory::new(
+            payload.video.id.clone(),
+            payload.channel.id.clone(),
             payload.watch_duration_seconds,
             payload.session_start_date,
             payload.session_end_date,
+            payload.session_key.clone(),
+        ),
+    )?;
+
+    if let Some(segments) = &payload.segments
+        && !segments.is_empty()
+    {
+        diesel::delete(
+            watch_segments_dsl::watch_segments
+                .filter(watch_segments_dsl::watch_history_id.eq(&watch_history_id)),
+        )
+        .execute(conn)?;
+
+        let new_segments = segments
+            .iter()
+            .enumerate()
+            .map(|(index, segment)| {
+                models::WatchSegment::new(
+                    watch_history_id.clone(),
+                    index as i64,
+                    segment.start_seconds,
+                    segment.end_seconds,
+                )
+            })
+            .collect::<Vec<models::WatchSegment>>();
+
+        insert_into(watch_segments_dsl::watch_segments)
+            .values(&new_segments)
+            .execute(conn)?;
+    }
+
+    match outcome {
+        ingest::Outcome::Created => Ok(CreateWatchHistoryStatus::Created),
+        ingest::Outcome::Updated | ingest::Outcome::Unchanged => {
+            Ok(CreateWatchHistoryStatus::Duplicate)
+        }
+    }
+}
+
+/// Saves the channel and video of a record through the shared ingest path, the thumbnail is
+/// refreshed once a day while the video is watched to detect new thumbnails.
+fn upsert_channel_and_video(
+    conn: &mut SqliteConnection,
+    channel_payload: &CreateWatchHistoryChannel,
+    video_payload: &CreateWatchHistoryVideo,
+) -> QueryResult<()> {
+    ingest::upsert_channel(
+        conn,
+        &ingest::ChannelUpsert {
+            id: channel_payload.id.clone(),
+            name: channel_payload.name.clone(),
+            url: Some(channel_payload.url.clone()),
+            avatar_url: Some(channel_payload.avater_url.clone()),
+            is_subscribed: Some(channel_payload.is_subscribed),
+            subscribers_count: Some(channel_payload.subscribers_count),
+            added_at: None,
+            fill_only: false,
+        },
+    )?;
+
+    ingest::upsert_video(
+        conn,
+        &ingest::VideoUpsert {
+            id: video_payload.id.clone(),
+            channel_id: channel_payload.id.clone(),
+            title: video_payload.title.clone(),
+            description: Some(video_payload.description.clone()),
+            thumbnail_url: Some(video_payload.thumbnail_url.clone()),
+            tags: video_payload.tags.clone(),
+            duration_seconds: Some(video_payload.duration),
+            published_at: Some(video_payload.published_at),
+            likes_count: Some(video_payload.likes_count),
+            view_count: Some(video_payload.view_count),
+            comments_count: Some(video_payload.comments_count),
+            refresh_thumbnail: true,
+            added_at: None,
+            fill_only: false,
+        },
+    )?;
+
+    Ok(())
+}
+
+#[derive(utoipa::ToSchema, Deserialize, TS)]
+#[ts(export)]
+pub struct StartWatchSessionRequest {
+    #[ts(type = "number")]
+    session_start_date: i64,
+
+    channel: CreateWatchHistoryChannel,
+    video: CreateWatchHistoryVideo,
+}
+
+impl Validate for StartWatchSessionRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.non_negative("session_start_date", self.session_start_date);
+        v.not_in_future(
+            "session_start_date",
+            self.session_start_date,
+            utils::unix_now(),
         );
 
-        insert_into(watch_history_dsl::watch_history)
-            .values(&new_watch_history)
-            .on_conflict_do_nothing()
-            .execute(&mut conn)
-            .map_err(internal_error)?;
+        v.nested("channel", |v| self.channel.validate(v));
+        v.nested("video", |v| self.video.validate(v));
+    }
+}
+
+#[derive(utoipa::ToSchema, Deserialize, TS)]
+#[ts(export)]
+pub struct WatchSessionHeartbeatRequest {
+    #[ts(type = "number")]
+    watch_duration_seconds: i64,
+}
+
+impl Validate for WatchSessionHeartbeatRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.non_negative("watch_duration_seconds", self.watch_duration_seconds);
+    }
+}
+
+/// Start a watch session
+///
+/// Saves the channel and video right away and returns the session that the client keeps alive
+/// with heartbeats. Sessions that stop sending heartbeats are saved to the watch history
+/// automatically.
+#[utoipa::path(
+    post,
+    path = "/watch_history/sessions",
+    tag = "Watch history",
+    responses(
+        (status = CREATED, description = "Watch session started", body = models::WatchSession),
+        (status = UNPROCESSABLE_ENTITY, description = "Request failed validation", body = ValidationErrorResponse),
+    )
+)]
+pub async fn start_watch_session(
+    State(state): State<AppState>,
+    Json(payload): Json<StartWatchSessionRequest>,
+) -> ApiResult<Response> {
+    if let Err(errors) = validation::validate(&payload) {
+        return Ok(ValidationErrorResponse::from(errors).into_response());
+    }
+
+    state
+        .db(move |conn| open_watch_session(conn, payload))
+        .await
+}
+
+fn open_watch_session(
+    conn: &mut SqliteConnection,
+    payload: StartWatchSessionRequest,
+) -> ApiResult<Response> {
+    use schema::watch_sessions::dsl as watch_sessions_dsl;
+
+    let session = models::WatchSession::new(
+        payload.video.id.clone(),
+        payload.channel.id.clone(),
+        payload.session_start_date,
+    );
+
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        upsert_channel_and_video(conn, &payload.channel, &payload.video)?;
+
+        insert_into(watch_sessions_dsl::watch_sessions)
+            .values(&session)
+            .execute(conn)?;
+
+        Ok(())
+    })
+    .map_err(internal_error)?;
+
+    Ok((StatusCode::CREATED, Json(session)).into_response())
+}
+
+/// Send a watch session heartbeat
+///
+/// Updates the watched seconds of an open session
+#[utoipa::path(
+    post,
+    path = "/watch_history/sessions/{id}/heartbeat",
+    tag = "Watch history",
+    params(
+        ("id" = String, Path, description = "Watch session id")
+    ),
+    responses(
+        (status = OK, description = "Watch session updated", body = models::WatchSession),
+        (status = NOT_FOUND, description = "Watch session does not exist or was already ended"),
+        (status = UNPROCESSABLE_ENTITY, description = "Request failed validation", body = ValidationErrorResponse),
+    )
+)]
+pub async fn watch_session_heartbeat(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(payload): Json<WatchSessionHeartbeatRequest>,
+) -> ApiResult<Response> {
+    if let Err(errors) = validation::validate(&payload) {
+        return Ok(ValidationErrorResponse::from(errors).into_response());
     }
 
-    Ok(StatusCode::CREATED)
+    state
+        .db(move |conn| touch_watch_session(conn, id, payload))
+        .await
+}
+
+fn touch_watch_session(
+    conn: &mut SqliteConnection,
+    id: String,
+    payload: WatchSessionHeartbeatRequest,
+) -> ApiResult<Response> {
+    use schema::watch_sessions::dsl as watch_sessions_dsl;
+
+    let Some(session) = watch_sessions_dsl::watch_sessions
+        .find(&id)
+        .get_result::<models::WatchSession>(conn)
+        .optional()
+        .map_err(internal_error)?
+    else {
+        return Err((StatusCode::NOT_FOUND, "Watch session not found".to_string()));
+    };
+
+    let session = update(watch_sessions_dsl::watch_sessions.find(&session.id))
+        .set((
+            watch_sessions_dsl::watch_duration_seconds.eq(session
+                .watch_duration_seconds
+                .max(payload.watch_duration_seconds)),
+            watch_sessions_dsl::last_heartbeat_at.eq(utils::unix_now()),
+        ))
+        .get_result::<models::WatchSession>(conn)
+        .map_err(internal_error)?;
+
+    Ok((StatusCode::OK, Json(session)).into_response())
+}
+
+/// End a watch session
+///
+/// Saves the session to the watch history and closes it
+#[utoipa::path(
+    post,
+    path = "/watch_history/sessions/{id}/end",
+    tag = "Watch history",
+    params(
+        ("id" = String, Path, description = "Watch session id")
+    ),
+    responses(
+        (status = CREATED, description = "Watch session saved to the watch history", body = models::WatchHistory),
+        (status = NOT_FOUND, description = "Watch session does not exist or was already ended"),
+        (status = UNPROCESSABLE_ENTITY, description = "Request failed validation", body = ValidationErrorResponse),
+    )
+)]
+pub async fn end_watch_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(payload): Json<WatchSessionHeartbeatRequest>,
+) -> ApiResult<Response> {
+    if let Err(errors) = validation::validate(&payload) {
+        return Ok(ValidationErrorResponse::from(errors).into_response());
+    }
+
+    state
+        .db(move |conn| close_watch_session(conn, id, payload))
+        .await
+}
+
+fn close_watch_session(
+    conn: &mut SqliteConnection,
+    id: String,
+    payload: WatchSessionHeartbeatRequest,
+) -> ApiResult<Response> {
+    use schema::watch_history::dsl as watch_history_dsl;
+    use schema::watch_sessions::dsl as watch_sessions_dsl;
+
+    let watch_history = conn
+        .transaction::<_, diesel::result::Error, _>(|conn| {
+            let Some(mut session) = watch_sessions_dsl::watch_sessions
+                .find(&id)
+                .get_result::<models::WatchSession>(conn)
+                .optional()?
+            else {
+                return Ok(None);
+            };
+
+            session.watch_duration_seconds = session
+                .watch_duration_seconds
+                .max(payload.watch_duration_seconds);
+            session.last_heartbeat_at = utils::unix_now();
+
+            finalize_watch_session(conn, &session)?;
+
+            watch_history_dsl::watch_history
+                .filter(watch_history_dsl::session_key.eq(&session.id))
+                .first::<models::WatchHistory>(conn)
+                .map(Some)
+        })
+        .map_err(internal_error)?;
+
+    match watch_history {
+        Some(watch_history) => Ok((StatusCode::CREATED, Json(watch_history)).into_response()),
+        None => Err((StatusCode::NOT_FOUND, "Watch session not found".to_string())),
+    }
+}
+
+/// Moves an open session into the watch history, the session id is used as its `session_key`.
+fn finalize_watch_session(
+    conn: &mut SqliteConnection,
+    session: &models::WatchSession,
+) -> QueryResult<()> {
+    use schema::watch_sessions::dsl as watch_sessions_dsl;
+
+    ingest::save_watch_session(
+        conn,
+        models::WatchHistory::new(
+            session.video_id.clone(),
+            session.channel_id.clone(),
+            session.watch_duration_seconds,
+            session.session_start_date,
+            session.last_heartbeat_at,
+            Some(session.id.clone()),
+        ),
+    )?;
+
+    diesel::delete(watch_sessions_dsl::watch_sessions.find(&session.id)).execute(conn)?;
+
+    Ok(())
+}
+
+/// Saves every session that did not send a heartbeat within `timeout` to the watch history.
+///
+/// Returns the number of finalized sessions.
+pub fn finalize_stale_watch_sessions(
+    conn: &mut SqliteConnection,
+    timeout: std::time::Duration,
+) -> QueryResult<usize> {
+    use schema::watch_sessions::dsl as watch_sessions_dsl;
+
+    let cutoff = utils::unix_now() - timeout.as_secs() as i64;
+
+    conn.transaction(|conn| {
+        let sessions = watch_sessions_dsl::watch_sessions
+            .filter(watch_sessions_dsl::last_heartbeat_at.lt(cutoff))
+            .load::<models::WatchSession>(conn)?;
+
+        for session in &sessions {
+            finalize_watch_session(conn, session)?;
+        }
+
+        Ok(sessions.len())
+    })
 }
 
 type GetWatchHistoryResponse = PaginatedResponse<WatchHistoryResponse>;
 
+type WatchHistoryQuery<'a> = IntoBoxed<
+    'a,
+    InnerJoin<
+        InnerJoin<schema::watch_history::table, schema::channels::table>,
+        schema::videos::table,
+    >,
+    Sqlite,
+>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum SortBy {
+    SessionStartDate,
+    SessionEndDate,
+    WatchDurationSeconds,
+    AddedAt,
+}
+
+impl SortKey for SortBy {
+    fn name(&self) -> &'static str {
+        match self {
+            SortBy::SessionStartDate => "session_start_date",
+            SortBy::SessionEndDate => "session_end_date",
+            SortBy::WatchDurationSeconds => "watch_duration_seconds",
+            SortBy::AddedAt => "added_at",
+        }
+    }
+}
+
+impl SortBy {
+    /// Value of this key for `watch_history` in the cursor of the next page
+    fn key(&self, watch_history: &models::WatchHistory) -> CursorKey {
+        CursorKey::Integer(match self {
+            SortBy::SessionStartDate => watch_history.session_start_date,
+            SortBy::SessionEndDate => watch_history.session_end_date,
+            SortBy::WatchDurationSeconds => watch_history.watch_duration_seconds,
+            SortBy::AddedAt => watch_history.added_at,
+        })
+    }
+
+    /// Conditions that a record comes after `value` of this key in `order` and that it ties with it
+    fn keyset_step(
+        self,
+        order: SortOrder,
+        value: CursorKey,
+    ) -> ApiResult<(
+        Condition<filter::WatchHistorySource>,
+        Condition<filter::WatchHistorySource>,
+    )> {
+        use schema::watch_history::dsl as watch_history_dsl;
+
+        let value = value.integer()?;
+
+        Ok(match self {
+            SortBy::SessionStartDate => {
+                keyset_step!(watch_history_dsl::session_start_date, order, value)
+            }
+            SortBy::SessionEndDate => {
+                keyset_step!(watch_history_dsl::session_end_date, order, value)
+            }
+            SortBy::WatchDurationSeconds => {
+                keyset_step!(watch_history_dsl::watch_duration_seconds, order, value)
+            }
+            SortBy::AddedAt => keyset_step!(watch_history_dsl::added_at, order, value),
+        })
+    }
+
+    /// [`SortBy::keyset_step`] of the id that breaks ties
+    fn id_step(
+        order: SortOrder,
+        id: String,
+    ) -> (
+        Condition<filter::WatchHistorySource>,
+        Condition<filter::WatchHistorySource>,
+    ) {
+        keyset_step!(schema::watch_history::id, order, id)
+    }
+}
+
 #[derive(Deserialize, Debug, utoipa::IntoParams)]
 pub struct GetWatchHistoryParams {
-    /// Data list offset
+    /// Comma separated sort keys, most significant first, a key with a leading `-` sorts in
+    /// descending order, for example `-watch_duration_seconds,session_start_date`. Keys are
+    /// `session_start_date`, `session_end_date`, `watch_duration_seconds` and `added_at`, newest
+    /// session first by default.
+    sort: Option<String>,
+    /// Data list offset, can not be combined with `cursor`
     offset: Option<i64>,
     /// Data list limit
     limit: Option<i64>,
+    /// `next_cursor` of the previous page, it only continues the same sort
+    cursor: Option<String>,
+    /// Filter expression, for example `video.duration_seconds>600 and (channel.is_subscribed=true
+    /// or tag:music)`. Comparisons use `=`, `!=`, `>`, `>=`, `<`, `<=` or `~` (contains) and are
+    /// combined with `and`, `or`, `not` and parentheses.
+    filter: Option<String>,
+}
+
+/// Filters shared by `/api/watch_history`, `/api/export` and `chianti export`
+#[derive(Deserialize, Debug, Default, Clone, PartialEq, utoipa::IntoParams, clap::Args)]
+pub struct WatchHistoryFilters {
     /// Only list records that belong to specified video
-    video_id: Option<String>,
+    #[arg(long)]
+    pub video_id: Option<String>,
     /// Only list records that belong to specified channel
-    channel_id: Option<String>,
+    #[arg(long)]
+    pub channel_id: Option<String>,
     /// Only list records that have specified watch duration
-    watch_duration_seconds: Option<i64>,
+    #[arg(long)]
+    pub watch_duration_seconds: Option<i64>,
     /// Only list records that have watch duration greater than specified value
-    min_watch_duration_seconds: Option<i64>,
+    #[arg(long)]
+    pub min_watch_duration_seconds: Option<i64>,
     /// Only list records that have watch duration less than specified value
-    max_watch_duration_seconds: Option<i64>,
+    #[arg(long)]
+    pub max_watch_duration_seconds: Option<i64>,
     /// Only list records that have been watched at specified timestamp
-    watched_at: Option<i64>,
+    #[arg(long)]
+    pub watched_at: Option<i64>,
     /// Only list records that have been watched before specified timestamp
-    watched_before: Option<i64>,
+    #[arg(long)]
+    pub watched_before: Option<i64>,
     /// Only list records that have been watched after specified timestamp
-    watched_after: Option<i64>,
+    #[arg(long)]
+    pub watched_after: Option<i64>,
     /// Only list records that have been watched in specified year
-    watched_year: Option<i64>,
+    #[arg(long)]
+    pub watched_year: Option<i64>,
     /// Only list records that have been watched in specified month
-    watched_month: Option<i64>,
+    #[arg(long)]
+    pub watched_month: Option<i64>,
     /// Only list records that have been watched in specified day
-    watched_day: Option<i64>,
+    #[arg(long)]
+    pub watched_day: Option<i64>,
+}
+
+impl WatchHistoryFilters {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
 }
 
 /// Returns watch history records
 ///
-/// This endpoint is used to fetch watch history records
+/// This endpoint is used to fetch watch history records, newest session first unless `sort` says
+/// otherwise
 #[utoipa::path(
     get,
     path = "/watch_history",
     tag = "Watch history",
     params(
-        GetWatchHistoryParams
+        GetWatchHistoryParams,
+        WatchHistoryFilters,
+        TagFilters
     ),
     responses(
         (status = OK, description = "List of watch history records", body = PaginatedResponse<WatchHistoryResponse>),
+        (status = BAD_REQUEST, description = "Sort, filter or cursor is invalid, or the cursor is combined with offset"),
     )
 )]
 pub async fn get_watch_history(
     State(state): State<AppState>,
     Query(params): Query<GetWatchHistoryParams>,
+    Query(filters): Query<WatchHistoryFilters>,
+    Query(tag_filters): Query<TagFilters>,
 ) -> ApiResult<(StatusCode, Json<GetWatchHistoryResponse>)> {
+    state
+        .db(move |conn| load_watch_history(conn, params, filters, tag_filters))
+        .await
+}
+
+/// Watch history with its channel and video that matches `filters`, `tag_filters` and the filter
+/// expression of `params`, the page and its total are both built from it
+fn filtered_watch_history<'a>(
+    params: &GetWatchHistoryParams,
+    filters: &'a WatchHistoryFilters,
+    tag_filters: &TagFilters,
+) -> ApiResult<WatchHistoryQuery<'a>> {
     use schema::channels::dsl as channels_dsl;
-    use schema::tags::dsl as tags_dsl;
-    use schema::video_tags::dsl as video_tags_dsl;
     use schema::videos::dsl as videos_dsl;
     use schema::watch_history::dsl as watch_history_dsl;
 
-    let mut conn = state.pool.get().map_err(internal_error)?;
-
-    let mut query = watch_history_dsl::watch_history
+    let query = watch_history_dsl::watch_history
         .inner_join(channels_dsl::channels)
         .inner_join(videos_dsl::videos)
-        .select((
-            watch_history_dsl::watch_history::all_columns(),
-            channels_dsl::channels::all_columns(),
-            videos_dsl::videos::all_columns(),
-        ))
         .into_boxed();
 
-    if let Some(offset) = params.offset {
-        query = query.offset(offset);
-    }
+    let query = filter_watch_history!(query, filters);
 
-    if let Some(limit) = params.limit {
-        query = query.limit(limit);
-    }
+    let mut query = filter_tags!(query, watch_history_dsl::video_id, tag_filters);
 
-    if let Some(video_id) = params.video_id {
-        query = query.filter(videos_dsl::id.eq(video_id));
+    if let Some(input) = &params.filter {
+        query = query.filter(filter::watch_history(input)?);
     }
 
-    if let Some(channel_id) = params.channel_id {
-        query = query.filter(channels_dsl::id.eq(channel_id));
-    }
+    Ok(query)
+}
 
-    if let Some(watch_duration_seconds) = params.watch_duration_seconds {
-        query = query.filter(watch_history_dsl::watch_duration_seconds.eq(watch_duration_seconds));
-    }
+fn load_watch_history(
+    conn: &mut SqliteConnection,
+    params: GetWatchHistoryParams,
+    filters: WatchHistoryFilters,
+    tag_filters: TagFilters,
+) -> ApiResult<(StatusCode, Json<GetWatchHistoryResponse>)> {
+    use schema::watch_history::dsl as watch_history_dsl;
 
-    if let Some(min_watch_duration_seconds) = params.min_watch_duration_seconds {
-        query =
-            query.filter(watch_history_dsl::watch_duration_seconds.gt(min_watch_duration_seconds));
-    }
+    check_cursor_offset(&params.cursor, params.offset)?;
 
-    if let Some(max_watch_duration_seconds) = params.max_watch_duration_seconds {
-        query =
-            query.filter(watch_history_dsl::watch_duration_seconds.lt(max_watch_duration_seconds));
-    }
+    let sort = Sort::new(
+        params.sort.as_deref(),
+        None,
+        None,
+        (SortBy::SessionStartDate, SortOrder::Desc),
+    )?;
 
-    if let Some(watched_at) = params.watched_at {
-        query = query.filter(watch_history_dsl::session_start_date.eq(watched_at));
-    }
+    let mut query = filtered_watch_history(&params, &filters, &tag_filters)?;
 
-    if let Some(watched_before) = params.watched_before {
-        query = query.filter(watch_history_dsl::session_start_date.lt(watched_before));
+    if let Some(cursor) = &params.cursor {
+        query = query.filter(after_cursor(
+            &sort,
+            Cursor::decode(cursor, &sort.to_string())?,
+            SortBy::keyset_step,
+            SortBy::id_step,
+        )?);
     }
 
-    if let Some(watched_after) = params.watched_after {
-        query = query.filter(watch_history_dsl::session_start_date.gt(watched_after));
+    for &(key, order) in &sort.keys {
+        query = match key {
+            SortBy::SessionStartDate => {
+                apply_sort!(query, watch_history_dsl::session_start_date, order)
+            }
+            SortBy::SessionEndDate => {
+                apply_sort!(query, watch_history_dsl::session_end_date, order)
+            }
+            SortBy::WatchDurationSeconds => {
+                apply_sort!(query, watch_history_dsl::watch_duration_seconds, order)
+            }
+            SortBy::AddedAt => apply_sort!(query, watch_history_dsl::added_at, order),
+        };
     }
 
-    if let Some(watched_year) = params.watched_year {
-        query = query
-            .filter(year_unix!(watch_history_dsl::session_start_date).eq(watched_year.to_string()));
-    }
+    query = apply_sort!(query, watch_history_dsl::id, sort.id_order());
 
-    if let Some(watched_month) = params.watched_month {
-        query = query.filter(
-            month_unix!(watch_history_dsl::session_start_date).eq(watched_month.to_string()),
-        );
+    if let Some(offset) = params.offset {
+        query = query.offset(offset);
     }
 
-    if let Some(watched_day) = params.watched_day {
-        query = query
-            .filter(day_unix!(watch_history_dsl::session_start_date).eq(watched_day.to_string()));
+    if let Some(limit) = params.limit {
+        query = query.limit(limit.max(0) + 1);
     }
 
-    let data = query
-        .load::<(models::WatchHistory, models::Channel, models::Video)>(&mut conn)
+    let mut data = query
+        .load::<(models::WatchHistory, models::Channel, models::Video)>(conn)
+        .map_err(internal_error)?;
+
+    let next_cursor = last_of_page(&mut data, params.limit).map(|(watch_history, _, _)| {
+        Cursor::new(
+            sort.to_string(),
+            sort.keys
+                .iter()
+                .map(|(key, _)| key.key(watch_history))
+                .collect(),
+            watch_history.id.clone(),
+        )
+    });
+
+    let tags = load_video_tags(conn, data.iter().map(|(_, _, video)| &video.id))
         .map_err(internal_error)?;
 
     let list = data
         .into_iter()
         .map(|(watch_history, channel, video)| {
-            let tags = tags_dsl::tags
-                .inner_join(video_tags_dsl::video_tags)
-                .filter(video_tags_dsl::video_id.eq(&video.id))
-                .select(tags_dsl::name)
-                .load(&mut conn)
-                .unwrap_or(Vec::new());
+            // A video can be watched more than once on a page
+            let tags = tags.get(&video.id).cloned().unwrap_or_default();
 
             let channel_response = ChannelResponse::new(channel);
 
@@ -369,12 +1032,44 @@ pub async fn get_watch_history(
         })
         .collect::<Vec<WatchHistoryResponse>>();
 
-    let total = watch_history_dsl::watch_history
+    let total = filtered_watch_history(&params, &filters, &tag_filters)?
         .count()
-        .get_result::<i64>(&mut conn)
-        .unwrap_or(0);
+        .get_result::<i64>(conn)
+        .map_err(internal_error)?;
 
-    let res = GetWatchHistoryResponse::new(list, params.offset, params.limit, total);
+    let res = GetWatchHistoryResponse::new(list, params.offset, params.limit, total)
+        .with_next_cursor(next_cursor);
 
     Ok((StatusCode::OK, Json(res)))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing;
+
+    fn load(conn: &mut SqliteConnection, limit: i64) -> (usize, usize) {
+        let params = serde_json::from_value(serde_json::json!({ "limit": limit })).unwrap();
+        let filters = serde_json::from_value(serde_json::json!({})).unwrap();
+        let tag_filters = serde_json::from_value(serde_json::json!({})).unwrap();
+
+        let (result, queries) = testing::count_queries(conn, |conn| {
+            load_watch_history(conn, params, filters, tag_filters)
+        });
+        let (_, Json(response)) = result.unwrap();
+
+        (response.data.len(), queries)
+    }
+
+    #[test]
+    fn queries_do_not_grow_with_the_page() {
+        let mut conn = testing::connection();
+        testing::seed(&mut conn, 120);
+
+        let (one, one_queries) = load(&mut conn, 1);
+        let (hundred, hundred_queries) = load(&mut conn, 100);
+
+        assert_eq!((one, hundred), (1, 100));
+        assert_eq!(one_queries, hundred_queries);
+    }
+}
@@ -2,5 +2,34 @@ pub mod api;
 mod handle_404;
 mod root;
 
+use crate::state::AppState;
+use utoipa_axum::router::OpenApiRouter;
+
 pub use handle_404::handle_404;
 pub use root::root;
+
+/// Routes under `/api` and their OpenAPI spec
+pub fn api_router() -> (axum::Router<AppState>, utoipa::openapi::OpenApi) {
+    let (router, mut api_doc) = OpenApiRouter::<AppState>::new()
+        .nest("/api", api::routes())
+        .split_for_parts();
+
+    let mut contact = utoipa::openapi::Contact::new();
+    contact.name = Some("mesalilac".into());
+    contact.email = Some("mesalilac@proton.me".into());
+    contact.url = Some("https://github.com/mesalilac".into());
+
+    let mut license = utoipa::openapi::License::new("GNU General Public License v3.0 only");
+    license.url = Some("https://www.gnu.org/licenses/gpl-3.0.en.html".into());
+    license.identifier = Some("GPL-3.0-only".into());
+
+    api_doc.info.title = "Chianti API".to_string();
+    api_doc.info.version = env!("CARGO_PKG_VERSION").to_string();
+    api_doc.info.license = Some(license);
+    api_doc.info.contact = Some(contact);
+    api_doc.info.description = Some(String::from(
+        "Collect info about the youtube videos you watch. Web frontend is served from [/web](/web).",
+    ));
+
+    (router, api_doc)
+}
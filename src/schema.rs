@@ -1,5 +1,27 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    channel_events (id) {
+        id -> Text,
+        channel_id -> Text,
+        kind -> Text,
+        old_value -> Nullable<Text>,
+        new_value -> Nullable<Text>,
+        added_at -> BigInt,
+    }
+}
+
+diesel::table! {
+    channel_snapshots (id) {
+        id -> Text,
+        channel_id -> Text,
+        name -> Text,
+        is_subscribed -> Bool,
+        subscribers_count -> BigInt,
+        added_at -> BigInt,
+    }
+}
+
 diesel::table! {
     channels (id) {
         id -> Text,
@@ -8,6 +30,22 @@ diesel::table! {
         is_subscribed -> Bool,
         subscribers_count -> BigInt,
         added_at -> BigInt,
+        avatar_url -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    jobs (id) {
+        id -> Text,
+        kind -> Text,
+        target_id -> Text,
+        url -> Text,
+        status -> Text,
+        attempts -> BigInt,
+        last_error -> Nullable<Text>,
+        run_at -> BigInt,
+        updated_at -> BigInt,
+        added_at -> BigInt,
     }
 }
 
@@ -19,6 +57,16 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    video_revisions (id) {
+        id -> Text,
+        video_id -> Text,
+        title -> Text,
+        thumbnail_file -> Nullable<Text>,
+        added_at -> BigInt,
+    }
+}
+
 diesel::table! {
     video_tags (video_id, tag_id) {
         video_id -> Text,
@@ -26,6 +74,17 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    video_metric_snapshots (id) {
+        id -> Text,
+        video_id -> Text,
+        view_count -> BigInt,
+        likes_count -> BigInt,
+        comments_count -> BigInt,
+        added_at -> BigInt,
+    }
+}
+
 diesel::table! {
     videos (id) {
         id -> Text,
@@ -40,6 +99,7 @@ diesel::table! {
         comments_count -> BigInt,
         published_at -> BigInt,
         added_at -> BigInt,
+        thumbnail_url -> Nullable<Text>,
     }
 }
 
@@ -52,19 +112,58 @@ diesel::table! {
         session_start_date -> BigInt,
         session_end_date -> BigInt,
         added_at -> BigInt,
+        session_key -> Nullable<Text>,
+        duration_source -> Text,
+    }
+}
+
+diesel::table! {
+    watch_segments (id) {
+        id -> Text,
+        watch_history_id -> Text,
+        segment_index -> BigInt,
+        start_seconds -> BigInt,
+        end_seconds -> BigInt,
+        added_at -> BigInt,
+    }
+}
+
+diesel::table! {
+    watch_sessions (id) {
+        id -> Text,
+        video_id -> Text,
+        channel_id -> Text,
+        watch_duration_seconds -> BigInt,
+        session_start_date -> BigInt,
+        last_heartbeat_at -> BigInt,
+        added_at -> BigInt,
     }
 }
 
+diesel::joinable!(video_revisions -> videos (video_id));
 diesel::joinable!(video_tags -> tags (tag_id));
+diesel::joinable!(channel_events -> channels (channel_id));
+diesel::joinable!(channel_snapshots -> channels (channel_id));
+diesel::joinable!(video_metric_snapshots -> videos (video_id));
 diesel::joinable!(video_tags -> videos (video_id));
 diesel::joinable!(videos -> channels (channel_id));
 diesel::joinable!(watch_history -> channels (channel_id));
 diesel::joinable!(watch_history -> videos (video_id));
+diesel::joinable!(watch_segments -> watch_history (watch_history_id));
+diesel::joinable!(watch_sessions -> channels (channel_id));
+diesel::joinable!(watch_sessions -> videos (video_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    channel_events,
+    channel_snapshots,
     channels,
+    jobs,
     tags,
+    video_metric_snapshots,
+    video_revisions,
     video_tags,
     videos,
     watch_history,
+    watch_segments,
+    watch_sessions,
 );
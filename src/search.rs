@@ -0,0 +1,236 @@
+//! Full-text search over the `videos_fts`, `channels_fts` and `tags_fts` tables.
+//!
+//! The tables are FTS5 indexes kept in sync with their source tables by the triggers of the
+//! `search_index` migration. Each index row has the rowid of its source row, which the triggers
+//! and [`matching`] find it by, and stores the id of the source row for search results.
+//!
+//! Rowids of tables without an integer primary key can change on `VACUUM`, an index that no
+//! longer lines up with its table is rebuilt by [`rebuild_if_out_of_sync`].
+
+use diesel::expression::{SqlLiteral, UncheckedBind};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Bool, Double, Text};
+
+/// Start and end of a match in highlighted text before it is escaped, see [`highlighted`]
+const MATCH_START: char = '\u{2}';
+const MATCH_END: char = '\u{3}';
+
+/// Turns user input into an FTS5 query.
+///
+/// Words and `"quoted phrases"` must all match, a word ending in `*` matches as a prefix. Other
+/// FTS5 syntax is matched as text, so any input is a valid query. Returns `None` when the input
+/// has nothing to search for.
+pub fn fts_query(input: &str) -> Option<String> {
+    let mut terms = Vec::new();
+    let mut rest = input.trim_start();
+
+    while !rest.is_empty() {
+        if let Some(after_quote) = rest.strip_prefix('"') {
+            let (phrase, next) = after_quote.split_once('"').unwrap_or((after_quote, ""));
+
+            terms.extend(term(phrase, false));
+            rest = next;
+        } else {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || c == '"')
+                .unwrap_or(rest.len());
+            let word = &rest[..end];
+
+            terms.extend(match word.strip_suffix('*') {
+                Some(prefix) => term(prefix, true),
+                None => term(word, false),
+            });
+            rest = &rest[end..];
+        }
+
+        rest = rest.trim_start();
+    }
+
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+/// A quoted FTS5 phrase, `None` when it has no characters that are indexed
+fn term(text: &str, prefix: bool) -> Option<String> {
+    if !text.chars().any(char::is_alphanumeric) {
+        return None;
+    }
+
+    let phrase = format!("\"{}\"", text.replace('"', "\"\""));
+
+    Some(if prefix { phrase + "*" } else { phrase })
+}
+
+pub type Matching =
+    SqlLiteral<Bool, UncheckedBind<SqlLiteral<Bool>, diesel::dsl::AsExprOf<String, Text>>>;
+
+/// Filter that keeps the rows of `table` whose `table_fts` index matches `fts_query`, for
+/// example `matching("videos", query)` in a query on `videos`
+pub fn matching(table: &'static str, fts_query: String) -> Matching {
+    diesel::dsl::sql::<Bool>(&format!(
+        "{table}.rowid IN (SELECT rowid FROM {table}_fts WHERE {table}_fts MATCH "
+    ))
+    .bind::<Text, _>(fts_query)
+    .sql(")")
+}
+
+/// HTML escapes text returned by `highlight()` or `snippet()` and wraps its matches in `<mark>`
+pub fn highlighted(text: &str) -> String {
+    let mut html = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            MATCH_START => html.push_str("<mark>"),
+            MATCH_END => html.push_str("</mark>"),
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
+    }
+
+    html
+}
+
+#[derive(QueryableByName, Debug)]
+pub struct VideoMatch {
+    #[diesel(sql_type = Text)]
+    pub id: String,
+    /// Higher is a better match
+    #[diesel(sql_type = Double)]
+    pub score: f64,
+    #[diesel(sql_type = Text)]
+    pub title: String,
+    /// Part of the description around the matches
+    #[diesel(sql_type = Text)]
+    pub description: String,
+    #[diesel(sql_type = Text)]
+    pub tags: String,
+}
+
+#[derive(QueryableByName, Debug)]
+pub struct NameMatch {
+    #[diesel(sql_type = Text)]
+    pub id: String,
+    /// Higher is a better match
+    #[diesel(sql_type = Double)]
+    pub score: f64,
+    #[diesel(sql_type = Text)]
+    pub name: String,
+}
+
+/// Best matching videos first, titles weigh more than tags and tags more than descriptions
+pub fn search_videos(
+    conn: &mut SqliteConnection,
+    fts_query: &str,
+    limit: i64,
+) -> QueryResult<Vec<VideoMatch>> {
+    diesel::sql_query(
+        "SELECT id, -rank AS score,
+            highlight(videos_fts, 1, char(2), char(3)) AS title,
+            snippet(videos_fts, 2, char(2), char(3), '…', 24) AS description,
+            highlight(videos_fts, 3, char(2), char(3)) AS tags
+        FROM videos_fts WHERE videos_fts MATCH ? ORDER BY rank LIMIT ?",
+    )
+    .bind::<Text, _>(fts_query)
+    .bind::<BigInt, _>(limit)
+    .load(conn)
+}
+
+/// Best matching rows of `channels_fts` or `tags_fts` first
+pub fn search_names(
+    conn: &mut SqliteConnection,
+    table: &'static str,
+    fts_query: &str,
+    limit: i64,
+) -> QueryResult<Vec<NameMatch>> {
+    diesel::sql_query(format!(
+        "SELECT id, -rank AS score, highlight({table}, 1, char(2), char(3)) AS name
+        FROM {table} WHERE {table} MATCH ? ORDER BY rank LIMIT ?"
+    ))
+    .bind::<Text, _>(fts_query)
+    .bind::<BigInt, _>(limit)
+    .load(conn)
+}
+
+#[derive(QueryableByName)]
+struct Count {
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+/// Rebuilds the search indexes when a row of an index does not have the rowid and id of its source
+/// row, for example after the database was vacuumed. Returns whether they were rebuilt.
+pub fn rebuild_if_out_of_sync(conn: &mut SqliteConnection) -> QueryResult<bool> {
+    let mut mismatched = 0;
+
+    for (table, fts_table) in [
+        ("videos", "videos_fts"),
+        ("channels", "channels_fts"),
+        ("tags", "tags_fts"),
+    ] {
+        mismatched += diesel::sql_query(format!(
+            "SELECT (
+                SELECT COUNT(*) FROM {table}
+                LEFT JOIN {fts_table} ON {fts_table}.rowid = {table}.rowid
+                WHERE {fts_table}.id IS NOT {table}.id
+            ) + (
+                SELECT COUNT(*) FROM {fts_table}
+                LEFT JOIN {table} ON {table}.rowid = {fts_table}.rowid
+                WHERE {table}.id IS NOT {fts_table}.id
+            ) AS count"
+        ))
+        .get_result::<Count>(conn)?
+        .count;
+    }
+
+    if mismatched == 0 {
+        return Ok(false);
+    }
+
+    rebuild(conn)?;
+
+    Ok(true)
+}
+
+/// Refills the search indexes from their source tables.
+///
+/// Rowids of tables without an integer primary key can change when the database is copied, a
+/// restored backup is rebuilt so the triggers find the index rows again. Databases from before the
+/// `search_index` migration have no indexes, the migration fills them.
+pub fn rebuild(conn: &mut SqliteConnection) -> QueryResult<()> {
+    let exists = diesel::sql_query(
+        "SELECT COUNT(*) AS count FROM sqlite_master WHERE type = 'table' AND name = 'videos_fts'",
+    )
+    .get_result::<Count>(conn)?;
+
+    if exists.count == 0 {
+        return Ok(());
+    }
+
+    conn.transaction(|conn| {
+        diesel::sql_query("DELETE FROM videos_fts").execute(conn)?;
+        diesel::sql_query("DELETE FROM channels_fts").execute(conn)?;
+        diesel::sql_query("DELETE FROM tags_fts").execute(conn)?;
+
+        diesel::sql_query(
+            "INSERT INTO videos_fts(rowid, id, title, description, tags)
+            SELECT videos.rowid, videos.id, videos.title, videos.description, COALESCE((
+                SELECT group_concat(tags.name, ', ')
+                FROM video_tags INNER JOIN tags ON tags.id = video_tags.tag_id
+                WHERE video_tags.video_id = videos.id
+            ), '')
+            FROM videos",
+        )
+        .execute(conn)?;
+        diesel::sql_query(
+            "INSERT INTO channels_fts(rowid, id, name) SELECT rowid, id, name FROM channels",
+        )
+        .execute(conn)?;
+        diesel::sql_query("INSERT INTO tags_fts(rowid, id, name) SELECT rowid, id, name FROM tags")
+            .execute(conn)?;
+
+        Ok(())
+    })
+}
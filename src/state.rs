@@ -1,8 +1,24 @@
-use crate::database::connection::DbPool;
+use crate::database::connection::{self, DbPool, RunError};
+use diesel::SqliteConnection;
 
 #[derive(Clone)]
 pub struct AppState {
     pub pool: DbPool,
     pub channel_avaters_dir: std::path::PathBuf,
     pub video_thumbnails_dir: std::path::PathBuf,
+    pub video_thumbnail_revisions_dir: std::path::PathBuf,
+    pub http_client: reqwest::Client,
+}
+
+impl AppState {
+    /// Runs `f` with a database connection without blocking the async runtime, see
+    /// [`connection::run`].
+    pub async fn db<T, E, F>(&self, f: F) -> Result<T, E>
+    where
+        F: FnOnce(&mut SqliteConnection) -> Result<T, E> + Send + 'static,
+        T: Send + 'static,
+        E: From<RunError> + Send + 'static,
+    {
+        connection::run(&self.pool, f).await
+    }
 }
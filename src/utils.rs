@@ -11,6 +11,20 @@ pub fn build_thumbnail_cache_image_filename(id: &String) -> String {
     format!("{id}.webp")
 }
 
+pub fn build_thumbnail_revision_image_filename(video_id: &String, file_id: &String) -> String {
+    format!("{video_id}/{file_id}.webp")
+}
+
+/// Returns the current time as seconds since the unix epoch
+pub fn unix_now() -> i64 {
+    let Ok(now) = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) else {
+        tracing::error!("Failed to get current time");
+        std::process::exit(1);
+    };
+
+    now.as_secs() as i64
+}
+
 /// Utility function for mapping any error into a `500 Internal Server Error`
 /// response.
 pub fn internal_error<E>(err: E) -> (StatusCode, String)
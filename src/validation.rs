@@ -0,0 +1,123 @@
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use ts_rs::TS;
+
+/// How far client timestamps may lie in the future before they are rejected
+pub const CLOCK_SKEW_SECONDS: i64 = 60 * 60;
+
+#[derive(utoipa::ToSchema, Serialize, Debug, Clone, PartialEq, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum FieldErrorCode {
+    Negative,
+    InvalidFormat,
+    OutOfOrder,
+    ExceedsSession,
+    InFuture,
+}
+
+#[derive(utoipa::ToSchema, Serialize, Debug, Clone, TS)]
+#[ts(export)]
+pub struct FieldError {
+    field: String,
+    code: FieldErrorCode,
+    message: String,
+}
+
+#[derive(utoipa::ToSchema, Serialize, Debug, TS)]
+#[ts(export)]
+pub struct ValidationErrorResponse {
+    errors: Vec<FieldError>,
+}
+
+impl IntoResponse for ValidationErrorResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::UNPROCESSABLE_ENTITY, Json(self)).into_response()
+    }
+}
+
+impl From<Vec<FieldError>> for ValidationErrorResponse {
+    fn from(errors: Vec<FieldError>) -> Self {
+        Self { errors }
+    }
+}
+
+pub trait Validate {
+    fn validate(&self, v: &mut Validator);
+}
+
+/// Collects the errors of a payload, fields are named by their dotted path in the payload.
+#[derive(Default)]
+pub struct Validator {
+    path: Vec<String>,
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    /// Validates the fields of a nested object under `name`
+    pub fn nested(&mut self, name: impl Into<String>, f: impl FnOnce(&mut Self)) {
+        self.path.push(name.into());
+        f(self);
+        self.path.pop();
+    }
+
+    pub fn error(&mut self, field: &str, code: FieldErrorCode, message: impl Into<String>) {
+        let field = self
+            .path
+            .iter()
+            .map(String::as_str)
+            .chain(std::iter::once(field))
+            .collect::<Vec<_>>()
+            .join(".");
+
+        self.errors.push(FieldError {
+            field,
+            code,
+            message: message.into(),
+        });
+    }
+
+    pub fn non_negative(&mut self, field: &str, value: i64) {
+        if value < 0 {
+            self.error(field, FieldErrorCode::Negative, "Must not be negative");
+        }
+    }
+
+    pub fn not_in_future(&mut self, field: &str, timestamp: i64, now: i64) {
+        if timestamp > now + CLOCK_SKEW_SECONDS {
+            self.error(field, FieldErrorCode::InFuture, "Must not be in the future");
+        }
+    }
+
+    pub fn finish(self) -> Result<(), Vec<FieldError>> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors)
+        }
+    }
+}
+
+pub fn validate<T: Validate>(value: &T) -> Result<(), Vec<FieldError>> {
+    let mut v = Validator::default();
+    value.validate(&mut v);
+    v.finish()
+}
+
+fn is_id_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '_'
+}
+
+/// Youtube video ids are 11 url safe base64 characters
+pub fn is_video_id(id: &str) -> bool {
+    id.len() == 11 && id.chars().all(is_id_char)
+}
+
+/// Youtube channel ids are `UC` followed by 22 url safe base64 characters
+pub fn is_channel_id(id: &str) -> bool {
+    id.len() == 24 && id.starts_with("UC") && id.chars().all(is_id_char)
+}
@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ChannelEvent } from "./ChannelEvent";
+import type { ChannelSnapshot } from "./ChannelSnapshot";
+
+export type ChannelHistoryResponse = { channel_id: string, snapshots: Array<ChannelSnapshot>, events: Array<ChannelEvent>, };
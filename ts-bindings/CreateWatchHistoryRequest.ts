@@ -1,5 +1,6 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { CreateWatchHistoryChannel } from "./CreateWatchHistoryChannel";
+import type { CreateWatchHistorySegment } from "./CreateWatchHistorySegment";
 import type { CreateWatchHistoryVideo } from "./CreateWatchHistoryVideo";
 
-export type CreateWatchHistoryRequest = { watch_duration_seconds: number, session_start_date: number, session_end_date: number, channel: CreateWatchHistoryChannel, video: CreateWatchHistoryVideo, };
+export type CreateWatchHistoryRequest = { watch_duration_seconds: number, session_start_date: number, session_end_date: number, session_key: string | null, segments: Array<CreateWatchHistorySegment> | null, channel: CreateWatchHistoryChannel, video: CreateWatchHistoryVideo, };
@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CreateWatchHistoryStatus } from "./CreateWatchHistoryStatus";
+import type { FieldError } from "./FieldError";
+
+export type CreateWatchHistoryResult = { index: number, video_id: string, status: CreateWatchHistoryStatus, reason: string | null, errors: Array<FieldError>, };
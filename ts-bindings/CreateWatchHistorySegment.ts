@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type CreateWatchHistorySegment = { 
+/**
+ * Position in the video the segment starts at
+ */
+start_seconds: number, 
+/**
+ * Position in the video the segment ends before, a segment of one second at 10 ends at 11
+ */
+end_seconds: number, };
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { FieldErrorCode } from "./FieldErrorCode";
+
+export type FieldError = { field: string, code: FieldErrorCode, message: string, };
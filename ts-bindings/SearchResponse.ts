@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ChannelSearchMatch } from "./ChannelSearchMatch";
+import type { TagSearchMatch } from "./TagSearchMatch";
+import type { VideoSearchMatch } from "./VideoSearchMatch";
+
+/**
+ * Best matches of each kind first
+ */
+export type SearchResponse = { videos: Array<VideoSearchMatch>, channels: Array<ChannelSearchMatch>, tags: Array<TagSearchMatch>, };
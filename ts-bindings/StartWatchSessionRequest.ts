@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CreateWatchHistoryChannel } from "./CreateWatchHistoryChannel";
+import type { CreateWatchHistoryVideo } from "./CreateWatchHistoryVideo";
+
+export type StartWatchSessionRequest = { session_start_date: number, channel: CreateWatchHistoryChannel, video: CreateWatchHistoryVideo, };
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ChannelResponse } from "./ChannelResponse";
+
+export type SubscriptionTimelineEntry = { channel: ChannelResponse, subscribed: boolean, date: number, };
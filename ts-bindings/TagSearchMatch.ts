@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Tag } from "./Tag";
+
+export type TagSearchMatch = { 
+/**
+ * Higher is a better match
+ */
+score: number, 
+/**
+ * Tag name with `<mark>` around each match, the rest of the name is HTML escaped
+ */
+highlight: string, tag: Tag, };
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type VideoMetricSnapshot = { id: string, view_count: number, likes_count: number, comments_count: number, added_at: number, };
@@ -8,4 +8,30 @@ export * from "./WatchHistoryResponse.ts";
 export * from "./VideoResponse.ts";
 export * from "./ChannelWithVideosResponse.ts";
 export * from "./CreateWatchHistoryVideo.ts";
-export * from "./CreateWatchHistoryRequest.ts";
\ No newline at end of file
+export * from "./CreateWatchHistoryRequest.ts";
+export * from "./CreateWatchHistoryResponse.ts";
+export * from "./CreateWatchHistoryResult.ts";
+export * from "./CreateWatchHistoryStatus.ts";
+export * from "./StartWatchSessionRequest.ts";
+export * from "./WatchSessionHeartbeatRequest.ts";
+export * from "./CreateWatchHistorySegment.ts";
+export * from "./VideoProgressResponse.ts";
+export * from "./VideoMetricSnapshot.ts";
+export * from "./VideoMetricsResponse.ts";
+export * from "./ChannelEvent.ts";
+export * from "./ChannelHistoryResponse.ts";
+export * from "./ChannelSnapshot.ts";
+export * from "./SubscriptionTimelineEntry.ts";
+export * from "./VideoRevisionResponse.ts";
+export * from "./VideoRevisionsResponse.ts";
+export * from "./FieldError.ts";
+export * from "./FieldErrorCode.ts";
+export * from "./ValidationErrorResponse.ts";
+export * from "./ImportOutcomeCounts.ts";
+export * from "./ImportSummary.ts";
+export * from "./ChannelSearchMatch.ts";
+export * from "./SearchResponse.ts";
+export * from "./Tag.ts";
+export * from "./TagSearchMatch.ts";
+export * from "./VideoHighlight.ts";
+export * from "./VideoSearchMatch.ts";
\ No newline at end of file